@@ -3,13 +3,19 @@ uniform mat3 u_view_matrix;
 uniform mat3 u_projection_matrix;
 
 varying vec2 v_uv;
+varying float v_bevel;
+varying vec2 v_normal;
 
 #ifdef VERTEX_SHADER
 attribute vec3 a_pos;
 attribute vec2 a_uv;
+attribute float a_bevel;
+attribute vec2 a_normal;
 
 void main() {
     v_uv = a_uv;
+    v_bevel = a_bevel;
+    v_normal = a_normal;
     vec3 pos = u_projection_matrix * u_view_matrix * u_model_matrix * vec3(a_pos.xy, 1.0);
     gl_Position = vec4(pos.xy, a_pos.z, pos.z);
 }
@@ -17,10 +23,19 @@ void main() {
 
 #ifdef FRAGMENT_SHADER
 uniform sampler2D u_texture;
+uniform float u_alpha;
+
+// How much the rim is lightened or darkened at most
+const float BEVEL_STRENGTH = 0.3;
+const vec2 LIGHT_DIR = vec2(-0.7071, 0.7071);
 
 void main() {
     vec4 texture_color = texture2D(u_texture, v_uv);
-    texture_color.a = 1.0;
+    // Edges facing the light from the top left are lit, the opposite ones are in shadow
+    float facing = length(v_normal) > 0.0 ? dot(normalize(v_normal), LIGHT_DIR) : 0.0;
+    float shade = facing * v_bevel * v_bevel * BEVEL_STRENGTH;
+    texture_color.rgb = mix(texture_color.rgb, vec3(step(0.0, facing)), abs(shade));
+    texture_color.a = u_alpha;
     gl_FragColor = texture_color;
 }
 #endif
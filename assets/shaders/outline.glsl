@@ -1,22 +1,44 @@
-uniform mat3 u_model_matrix;
 uniform mat3 u_view_matrix;
 uniform mat3 u_projection_matrix;
+uniform vec2 u_framebuffer_size;
+
+varying vec4 v_color;
+// Distance from the middle of the line in pixels
+varying float v_dist;
+varying float v_width;
 
 #ifdef VERTEX_SHADER
-attribute vec2 a_pos;
+attribute vec3 a_pos;
+attribute vec2 a_other;
+// Which end of the segment the vertex is at and on which side of the line, -1 or 1
+attribute vec2 a_corner;
+attribute float a_width;
+attribute vec4 a_color;
 
-uniform float u_depth;
+vec2 to_screen(vec2 pos) {
+    vec3 clip = u_projection_matrix * u_view_matrix * vec3(pos, 1.0);
+    return clip.xy / clip.z * u_framebuffer_size / 2.0;
+}
 
 void main() {
-    vec3 pos = u_projection_matrix * u_view_matrix * u_model_matrix * vec3(a_pos, 1.0);
-    gl_Position = vec4(pos.xy, u_depth, pos.z);
+    vec2 pos = to_screen(a_pos.xy);
+    // From the start of the segment to its end at both ends
+    vec2 dir = (to_screen(a_other) - pos) * -a_corner.x;
+    dir = length(dir) > 0.0 ? normalize(dir) : vec2(1.0, 0.0);
+    // One more pixel on every side for the edge to fade out,
+    // ends are extended too so that neighbouring segments join without gaps
+    float half_width = a_width / 2.0 + 1.0;
+    pos += (vec2(-dir.y, dir.x) * a_corner.y + dir * a_corner.x) * half_width;
+    v_color = a_color;
+    v_dist = a_corner.y * half_width;
+    v_width = a_width;
+    gl_Position = vec4(pos * 2.0 / u_framebuffer_size, a_pos.z, 1.0);
 }
 #endif
 
 #ifdef FRAGMENT_SHADER
-uniform vec4 u_color;
-
 void main() {
-    gl_FragColor = u_color;
+    float coverage = clamp(v_width / 2.0 + 0.5 - abs(v_dist), 0.0, 1.0);
+    gl_FragColor = vec4(v_color.rgb, v_color.a * coverage);
 }
 #endif
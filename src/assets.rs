@@ -5,11 +5,26 @@ pub struct Assets {
     pub shaders: Shaders,
     pub sounds: Sounds,
     pub sprites: Sprites,
+    /// As many as [RoomConfig::BUILTIN_IMAGES]
     #[asset(range = "1..=3", path = "images/*.png")]
     pub images: Vec<ugli::Texture>,
     pub hand: HandAssets,
+    pub glove: HandAssets,
+    pub harvester: HandAssets,
+    pub translations: translations::Translations,
 }
 
+impl Assets {
+    pub fn cursor(&self, skin: CursorSkin) -> &HandAssets {
+        match skin {
+            CursorSkin::Hand => &self.hand,
+            CursorSkin::Glove => &self.glove,
+            CursorSkin::Harvester => &self.harvester,
+        }
+    }
+}
+
+/// Cursor sprites of a single [CursorSkin]
 #[derive(geng::Assets)]
 pub struct HandAssets {
     pub grab: ugli::Texture,
@@ -20,13 +35,21 @@ pub struct HandAssets {
 #[derive(geng::Assets)]
 pub struct Shaders {
     pub jigsaw: ugli::Program,
+    /// Antialiased lines of constant width on the screen, see [crate::lines::LineVertex]
     pub outline: ugli::Program,
+    /// Draws colored triangles of many tiles at once, like tints and shadows
+    pub outline_batch: ugli::Program,
 }
 
 #[derive(geng::Assets)]
 pub struct Sounds {
-    pub connect_piece: geng::Sound,
-    pub grab: geng::Sound,
+    /// Variants picked at random each time
+    #[asset(range = "1..=3", path = "connect_piece/*.wav")]
+    pub connect_piece: Vec<geng::Sound>,
+    #[asset(range = "1..=3", path = "grab/*.wav")]
+    pub grab: Vec<geng::Sound>,
+    pub ping: geng::Sound,
+    pub fanfare: geng::Sound,
     #[asset(path = "music.mp3", postprocess = "make_looped")]
     pub music: geng::Sound,
 }
@@ -34,8 +57,54 @@ pub struct Sounds {
 #[derive(geng::Assets)]
 pub struct Sprites {
     pub table: ugli::Texture,
+    #[asset(postprocess = "make_repeated")]
+    pub felt: ugli::Texture,
 }
 
 fn make_looped(sound: &mut geng::Sound) {
     sound.looped = true;
 }
+
+fn make_repeated(texture: &mut ugli::Texture) {
+    texture.set_wrap_mode(ugli::WrapMode::Repeat);
+}
+
+/// Decodes a png or jpeg image into a texture,
+/// scaled down to fit into `max_size` on both sides
+pub fn texture_from_bytes(
+    ugli: &Ugli,
+    bytes: &[u8],
+    max_size: usize,
+) -> Result<ugli::Texture, String> {
+    let reader = || {
+        image::io::Reader::new(std::io::Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(|e| e.to_string())
+    };
+    // Only reads the header
+    let (width, height) = reader()?.into_dimensions().map_err(|e| e.to_string())?;
+    if width as u64 * height as u64 > RoomConfig::MAX_IMAGE_PIXELS {
+        return Err(format!("Image is too large: {width}x{height}"));
+    }
+    let mut image = reader()?.decode().map_err(|e| e.to_string())?;
+    if image.width().max(image.height()) as usize > max_size {
+        // Keeps the aspect ratio
+        image = image.resize(
+            max_size as u32,
+            max_size as u32,
+            image::imageops::FilterType::Triangle,
+        );
+    }
+    let image = image.to_rgba8();
+    let size = vec2(image.width() as usize, image.height() as usize);
+    Ok(ugli::Texture::new_with(ugli, size, |pos| {
+        // Textures go bottom to top
+        let pixel = image.get_pixel(pos.x as u32, (size.y - 1 - pos.y) as u32);
+        Rgba::new(
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+            pixel[3] as f32 / 255.0,
+        )
+    }))
+}
@@ -0,0 +1,75 @@
+use super::*;
+
+use geng::ui::*;
+
+/// Button in the colors of the chosen [UiTheme], larger than the default one
+pub struct Button<'a> {
+    cx: &'a Controller,
+    sense: &'a mut Sense,
+    clicked: bool,
+    text: String,
+    palette: Palette,
+}
+
+impl<'a> Button<'a> {
+    pub fn new(cx: &'a Controller, theme: UiTheme, text: &str) -> Self {
+        let sense: &'a mut Sense = cx.get_state();
+        let clicked = sense.take_clicked();
+        Self {
+            cx,
+            sense,
+            clicked,
+            text: text.to_owned(),
+            palette: theme.palette(),
+        }
+    }
+
+    pub fn was_clicked(&self) -> bool {
+        self.clicked
+    }
+}
+
+impl<'a> Widget for Button<'a> {
+    fn sense(&mut self) -> Option<&mut Sense> {
+        Some(self.sense)
+    }
+    fn update(&mut self, _delta_time: f64) {}
+    fn draw(&mut self, cx: &mut DrawContext) {
+        let aabb = cx.position.map(|x| x as f32);
+        let pressed = self.sense.is_captured();
+        let color = if pressed || self.sense.is_hovered() {
+            self.palette.button_hover
+        } else {
+            self.palette.button
+        };
+        let body = aabb.extend_uniform(-aabb.height() * 0.08);
+        cx.geng.draw_2d(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::Quad::new(body, color),
+        );
+        // Text sinks a bit while pressed
+        let size = body.height() * if pressed { 0.55 } else { 0.6 };
+        cx.geng.default_font().draw(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &self.text,
+            vec2(body.center().x, body.center().y - size * 0.35),
+            geng::TextAlign::CENTER,
+            size,
+            self.palette.text,
+        );
+    }
+
+    fn calc_constraints(&mut self, _children: &ConstraintsContext) -> Constraints {
+        let text_size = self.cx.theme().text_size as f64;
+        Constraints {
+            // Wide enough for the text in most fonts, measuring needs the font
+            min_size: vec2(
+                (self.text.chars().count() as f64 * 0.6 + 2.0) * text_size,
+                text_size * 1.6,
+            ),
+            flex: vec2(0.0, 0.0),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use super::*;
+
+/// Client defaults loaded from a TOML file given by `--config` or `JIGSAW_CONFIG`.
+///
+/// Values set here take priority over saved preferences,
+/// command line arguments take priority over both.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub connect: Option<String>,
+    pub name: Option<String>,
+    pub show_names: Option<bool>,
+    pub show_feed: Option<bool>,
+    pub volume: Option<f64>,
+    pub controls: Option<Controls>,
+    /// Larger custom images are scaled down, lower it for weak GPUs
+    pub max_texture_size: Option<usize>,
+}
+
+impl ClientConfig {
+    const DEFAULT_MAX_TEXTURE_SIZE: usize = 4096;
+
+    pub fn max_texture_size(&self) -> usize {
+        self.max_texture_size
+            .unwrap_or(Self::DEFAULT_MAX_TEXTURE_SIZE)
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read client config {path:?}: {e}"))?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse client config {path:?}: {e}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+impl From<MouseButton> for geng::MouseButton {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => Self::Left,
+            MouseButton::Middle => Self::Middle,
+            MouseButton::Right => Self::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    pub grab: MouseButton,
+    pub pan: MouseButton,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            grab: MouseButton::Left,
+            pan: MouseButton::Right,
+        }
+    }
+}
@@ -1,42 +1,303 @@
 use geng::Camera2d;
 
-use crate::jigsaw::Jigsaw;
+use crate::jigsaw::{Jigsaw, JigsawTile};
 
 use super::*;
 
-const SNAP_DISTANCE: f32 = 0.2;
-const FOV_MIN: f32 = 2.0;
-const FOV_MAX: f32 = 20.0;
+/// Tiles further than this from the server state get corrected
+const SYNC_DISTANCE: f32 = 0.01;
+const FEED_DURATION: f32 = 5.0;
+const FEED_FADE_TIME: f32 = 1.0;
+const FEED_MAX_ENTRIES: usize = 5;
+const BANNER_DURATION: f32 = 10.0;
+/// Seconds without moving after which a player is listed as idle
+const IDLE_TIME: f32 = 30.0;
+/// Seconds to admire the finished puzzle before the timelapse and the victory screen
+const VICTORY_DELAY: f32 = 5.0;
+/// Seconds between snapshots of tile positions for the timelapse
+const TIMELAPSE_INTERVAL: f32 = 2.0;
+/// Snapshots kept, every other one is dropped when there are more
+const TIMELAPSE_MAX_FRAMES: usize = 512;
+/// Seconds the timelapse takes to play back
+const TIMELAPSE_DURATION: f32 = 8.0;
+/// Number of confetti pieces thrown on completion
+const CONFETTI_COUNT: usize = 300;
+/// Number of tiles in a row of the tray
+const TRAY_COLUMNS: usize = 3;
+/// Width of the tray relative to the screen
+const TRAY_WIDTH: f32 = 0.2;
+/// Maximum number of seconds between reconnection attempts
+const RECONNECT_DELAY_MAX: f32 = 30.0;
+/// Seconds a ping marker stays on the board
+const PING_DURATION: f32 = 2.0;
+/// Exponential slowdown of flung groups per second
+const PHYSICS_FRICTION: f32 = 2.0;
+/// Fraction of speed kept when bouncing off the edge of the board
+const PHYSICS_RESTITUTION: f32 = 0.5;
+/// How hard overlapping groups push a flung one away
+const PHYSICS_PUSH: f32 = 30.0;
+/// Speed in tiles per second below which a flung group stops
+const PHYSICS_STOP_SPEED: f32 = 0.3;
+/// Seconds between sending positions of flung groups to the server
+const PHYSICS_SYNC_INTERVAL: f32 = 0.1;
+/// Height in pixels of the board screenshot, unless the board is too wide for it
+const SCREENSHOT_HEIGHT: usize = 2048;
+/// Translation keys of the names and colors of the teams in a race
+const TEAMS: [(&str, Rgba<f32>); RoomConfig::TEAMS] = [
+    (
+        "game.team_red",
+        Rgba {
+            r: 0.9,
+            g: 0.3,
+            b: 0.3,
+            a: 1.0,
+        },
+    ),
+    (
+        "game.team_blue",
+        Rgba {
+            r: 0.3,
+            g: 0.5,
+            b: 0.9,
+            a: 1.0,
+        },
+    ),
+];
+
+/// Collects outgoing messages to send them once per frame
+struct BatchedConnection {
+    inner: Connection,
+    outgoing: Vec<ClientMessage>,
+    /// Becomes `false` once the server closes the connection or sends something broken
+    connected: bool,
+}
+
+impl BatchedConnection {
+    fn new(inner: Connection) -> Self {
+        Self {
+            inner,
+            outgoing: Vec::new(),
+            connected: true,
+        }
+    }
+    fn send(&mut self, message: ClientMessage) {
+        // Only the latest cursor position and viewport matter
+        if matches!(
+            message,
+            ClientMessage::UpdatePos(_) | ClientMessage::UpdateViewport(..)
+        ) {
+            let kind = std::mem::discriminant(&message);
+            self.outgoing
+                .retain(|other| std::mem::discriminant(other) != kind);
+        }
+        self.outgoing.push(message);
+    }
+    fn flush(&mut self) {
+        if !self.connected {
+            self.outgoing.clear();
+            return;
+        }
+        let message = match self.outgoing.len() {
+            0 => return,
+            1 => self.outgoing.pop().unwrap(),
+            _ => ClientMessage::Batch(std::mem::take(&mut self.outgoing)),
+        };
+        self.inner.send(ClientMessage::Packed(pack(&message)));
+    }
+    fn try_recv(&mut self) -> Option<ServerMessage> {
+        if !self.connected {
+            return None;
+        }
+        match self.inner.next().now_or_never() {
+            Some(Some(message)) => Some(message),
+            Some(None) => {
+                self.connected = false;
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[derive(ugli::Vertex)]
+struct TileVertex {
+    a_pos: Vec3<f32>,
+    a_uv: Vec2<f32>,
+    a_bevel: f32,
+    a_normal: Vec2<f32>,
+}
+
+#[derive(ugli::Vertex)]
+struct OutlineVertex {
+    a_pos: Vec3<f32>,
+    a_color: Rgba<f32>,
+}
+
+/// Attempts to restore a dropped connection
+struct Reconnect {
+    attempts: u32,
+    /// Time of the next attempt
+    next_attempt: f32,
+    /// New connection with the messages received up to the room snapshot,
+    /// `None` if the attempt failed
+    task: Option<util::Task<Option<(Connection, Vec<ServerMessage>)>>>,
+}
 
 #[derive(HasId)]
 struct Player {
     id: Id,
     name: String,
     color: Rgba<f32>,
+    cursor: CursorSkin,
     interpolation: Interpolated<Vec2<f32>>,
     tile_grabbed: Option<(usize, Vec2<f32>)>,
+    /// Other groups held along with [Self::tile_grabbed] after a box selection
+    also_grabbed: Vec<(usize, Vec2<f32>)>,
+    /// Connections made in this room, as counted by the server
+    pieces: usize,
+    /// Round trip time to the server in milliseconds
+    ping: Option<u32>,
+    /// When the last cursor position arrived from the server, and that position
+    last_update: Option<(f32, Vec2<f32>)>,
+    /// When the player last moved the cursor or a piece
+    last_active: f32,
+}
+
+/// What a player in the list is up to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Presence {
+    Online,
+    Idle,
+    Holding,
+}
+
+/// Position, rotation and whether the tile is in the tray, for every tile
+type TimelapseFrame = Vec<(Vec2<f32>, u8, bool)>;
+
+/// Where a race stands, from the point of view of this player's team
+struct RaceState {
+    team: usize,
+    /// Largest group of the other team relative to the whole puzzle
+    rival_progress: f32,
+    winner: Option<usize>,
+}
+
+/// Tile positions recorded over the session, played back once the puzzle is completed
+struct Timelapse {
+    frames: Vec<TimelapseFrame>,
+    /// Seconds between frames, grows as frames get thinned out
+    interval: f32,
+    next_frame: f32,
+    playing: bool,
+}
+
+/// Group sliding on after being released in a physics room
+struct Flung {
+    /// Any tile of the group
+    tile: usize,
+    /// Position of that tile, kept here so server updates do not drag it back
+    pos: Vec2<f32>,
+    vel: Vec2<f32>,
+}
+
+/// Celebration particle falling over the board
+struct Confetti {
+    pos: Vec2<f32>,
+    vel: Vec2<f32>,
+    rotation: f32,
+    spin: f32,
+    color: Rgba<f32>,
 }
 
 struct Game {
     geng: Geng,
     room_config: RoomConfig,
+    /// Texture of an uploaded or downloaded image, `None` for builtin ones
+    custom_image: Option<Rc<ugli::Texture>>,
+    /// Smaller copies of the image for zoomed out views
+    image_levels: image_levels::ImageLevels,
     assets: Rc<Assets>,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    assets_watcher: hot_reload::AssetsWatcher,
     id: Id,
-    connection: Connection,
+    connection: BatchedConnection,
     players: Collection<Player>,
     camera: Camera2d,
     framebuffer_size: Vec2<usize>,
     jigsaw: Jigsaw,
+    /// Reused every frame to draw all tiles at once
+    tile_vertices: ugli::VertexBuffer<TileVertex>,
+    /// Same as [Self::tile_vertices], for tiles in the tray
+    tray_vertices: ugli::VertexBuffer<TileVertex>,
+    /// Reused every frame to draw flat colored shapes in one batch: group tints, shadows and confetti
+    outline_vertices: ugli::VertexBuffer<OutlineVertex>,
+    /// Reused every frame to draw outlines of all groups at once
+    line_vertices: ugli::VertexBuffer<lines::LineVertex>,
+    show_tray: bool,
+    /// Hide all tiles not on the border of the puzzle
+    edges_only: bool,
+    /// Number of tray rows scrolled past
+    tray_scroll: f32,
     bounds: AABB<f32>,
+    /// Zoom limits, from a couple of tiles to the whole board
+    fov_range: RangeInclusive<f32>,
+    /// Where the assembled puzzle belongs
+    frame: AABB<f32>,
+    /// Last viewport reported to the server
+    sent_viewport: Option<(CompactPos, CompactPos)>,
     dragging: Option<Dragging>,
     play_connect_sound: bool,
     // intro_time: f32,
     time: f32,
     hovered_tile: Option<usize>,
+    /// One tile of each group picked with a box selection
+    selection: Vec<usize>,
+    /// Tiles this player grabbed recently, oldest first
+    touched: Vec<usize>,
+    /// `None` once the tutorial is done
+    tutorial: Option<tutorial::Tutorial>,
     customize: bool,
+    /// Whether the menu opened with Escape is shown over the board
+    paused: bool,
+    /// Asking whether to leave while holding a piece
+    confirm_leave: bool,
+    /// When the invite was last copied to the clipboard
+    invite_copied: Option<f32>,
     name_typing: bool,
-    show_names: bool,
+    /// Whether the target image is shown enlarged over the board
+    show_reference: bool,
+    settings: Settings,
+    music: geng::SoundEffect,
+    /// Activity feed entries with the time they were added
+    feed: Vec<(f32, String)>,
+    /// Last server announcement with the time it was received
+    banner: Option<(f32, String)>,
+    /// Ping markers with the time they were placed, where and in whose color
+    pings: Vec<(f32, Vec2<f32>, Rgba<f32>)>,
     finish_time: Option<f32>,
+    /// When the server reported completion, and the final stats
+    completed: Option<(f32, RoomStats)>,
+    confetti: Vec<Confetti>,
+    /// Groups this player released still sliding, only in physics rooms
+    flung: Vec<Flung>,
+    /// When positions of flung groups are sent next
+    physics_sync: f32,
+    /// Smoothed velocity of the cursor on the board, for flinging groups
+    cursor_vel: Vec2<f32>,
+    /// Cursor position on the board as of the previous frame
+    last_cursor_world: Vec2<f32>,
+    timelapse: Timelapse,
+    /// `None` unless the room is a team board in a race
+    race: Option<RaceState>,
+    /// Local time when the room was created
+    room_start_time: f32,
+    host: Option<Id>,
+    addr: String,
+    room: String,
+    password: Option<String>,
+    client_config: ClientConfig,
+    reconnect: Option<Reconnect>,
+    transition: Option<geng::Transition>,
     cursor_pos: Vec2<f64>,
     cursor_world: Vec2<f32>,
     touch: Option<Vec<geng::TouchPoint>>,
@@ -50,48 +311,124 @@ struct Dragging {
 
 #[derive(Debug, Clone)]
 enum DragTarget {
-    Camera { initial_camera_pos: Vec2<f32> },
+    Camera {
+        initial_camera_pos: Vec2<f32>,
+    },
+    /// Box selection started at given world position
+    Select {
+        start: Vec2<f32>,
+    },
+}
+
+/// World area covered by a box selection
+fn select_area(a: Vec2<f32>, b: Vec2<f32>) -> AABB<f32> {
+    AABB {
+        x_min: a.x.min(b.x),
+        x_max: a.x.max(b.x),
+        y_min: a.y.min(b.y),
+        y_max: a.y.max(b.y),
+    }
 }
 
 impl Game {
     pub fn new(
         geng: &Geng,
+        addr: &str,
+        room: &str,
+        password: Option<String>,
         assets: &Rc<Assets>,
         id: Id,
         name: Option<String>,
+        config: &ClientConfig,
         room_config: RoomConfig,
+        custom_image: Option<Rc<ugli::Texture>>,
         tiles: Vec<TileState>,
-        mut connection: Connection,
+        connection: Connection,
     ) -> Self {
-        assets.sounds.music.play();
-        let image = &assets.images[room_config.image];
+        let mut connection = BatchedConnection::new(connection);
+        let settings = Settings::load(config);
+        let mut music = assets.sounds.music.effect();
+        music.set_volume(settings.audio.music_volume());
+        music.play();
+        let image = match room_config.image {
+            ImageSource::Builtin(index) => &assets.images[index],
+            ImageSource::Uploaded(_) | ImageSource::Url(_) => {
+                custom_image.as_deref().expect("Image was not loaded")
+            }
+        };
+        let image_levels = image_levels::ImageLevels::new(geng, image);
         let size = image.size().map(|x| x as f32);
-        let size = size * 5.0 / size.y;
+        // Same size the server checks connections with, even if the image changed since
+        let aspect = room_config.image_aspect.unwrap_or(size.x / size.y);
+        let size = vec2(aspect * PUZZLE_HEIGHT, PUZZLE_HEIGHT);
         let seed = room_config.seed;
-        let mut jigsaw = Jigsaw::generate(geng.ugli(), seed, size, room_config.size);
-        let bounds = AABB::ZERO.extend_symmetric(size / 2.0).extend_uniform(3.0);
+        let mut jigsaw = Jigsaw::generate(
+            geng.ugli(),
+            seed,
+            size,
+            room_config.size,
+            room_config.tiling,
+            room_config.edge_style,
+        );
+        let frame = AABB::ZERO.extend_symmetric(size / 2.0);
+        let bounds = frame.extend_uniform(3.0);
+        let fov_range = partial_min(jigsaw.tile_size.x, jigsaw.tile_size.y) * 2.0
+            ..=partial_max(bounds.width(), bounds.height()) * 1.5;
+        for (i, state) in tiles.iter().enumerate() {
+            for &other in &state.connections {
+                jigsaw.connect(i, other);
+            }
+        }
         for (tile, state) in jigsaw.tiles.iter_mut().zip(tiles) {
             tile.grabbed_by = state.grabbed_by;
-            tile.connected_to = state.connections;
+            tile.rotation = state.rotation;
+            tile.in_tray = state.in_tray;
+            tile.locked = state.locked;
             // tile.interpolated
             //     .teleport(tile.interpolated.get() - size / 2.0, Vec2::ZERO);
             // tile.interpolated.server_update(state.pos, Vec2::ZERO);
             tile.interpolated.teleport(state.pos, Vec2::ZERO);
         }
+        let locked: Vec<usize> = (0..jigsaw.tiles.len())
+            .filter(|&tile| jigsaw.tiles[tile].locked)
+            .collect();
+        for tile in locked {
+            jigsaw.lock_group(tile);
+        }
         let my_player = Player {
             id,
-            name: name.unwrap_or_else(|| batbox::preferences::load("name").unwrap_or_default()),
-            color: batbox::preferences::load("color").unwrap_or(Rgba::WHITE),
+            name: name.unwrap_or_else(|| settings.name.clone()),
+            color: settings.color.unwrap_or(Rgba::WHITE),
+            cursor: settings.cursor,
             interpolation: Interpolated::new(Vec2::ZERO, Vec2::ZERO),
             tile_grabbed: None,
+            also_grabbed: Vec::new(),
+            pieces: 0,
+            ping: None,
+            last_update: None,
+            last_active: 0.0,
         };
         connection.send(ClientMessage::UpdateName(my_player.name.clone()));
+        if let Some(color) = settings.color {
+            connection.send(ClientMessage::UpdateColor(color));
+        }
+        connection.send(ClientMessage::UpdateCursor(settings.cursor));
         Self {
-            show_names: batbox::preferences::load("show_names").unwrap_or(true),
+            settings,
+            music,
+            feed: Vec::new(),
+            banner: None,
+            pings: Vec::new(),
             name_typing: false,
             customize: false,
+            paused: false,
+            confirm_leave: false,
+            invite_copied: None,
+            show_reference: false,
             geng: geng.clone(),
             assets: assets.clone(),
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            assets_watcher: hot_reload::AssetsWatcher::new(geng),
             id,
             connection,
             players: {
@@ -102,340 +439,2106 @@ impl Game {
             camera: Camera2d {
                 center: Vec2::ZERO,
                 rotation: 0.0,
-                fov: 10.0,
+                fov: 10.0_f32.clamp(*fov_range.start(), *fov_range.end()),
             },
             framebuffer_size: vec2(1, 1),
             dragging: None,
             hovered_tile: None,
+            selection: Vec::new(),
+            touched: Vec::new(),
+            tutorial: (!settings.tutorial_done).then(tutorial::Tutorial::new),
             play_connect_sound: false,
             bounds,
+            fov_range,
+            frame,
+            sent_viewport: None,
             jigsaw,
+            tile_vertices: ugli::VertexBuffer::new_dynamic(geng.ugli(), Vec::new()),
+            tray_vertices: ugli::VertexBuffer::new_dynamic(geng.ugli(), Vec::new()),
+            outline_vertices: ugli::VertexBuffer::new_dynamic(geng.ugli(), Vec::new()),
+            line_vertices: ugli::VertexBuffer::new_dynamic(geng.ugli(), Vec::new()),
+            show_tray: true,
+            edges_only: false,
+            tray_scroll: 0.0,
             room_config,
+            custom_image,
+            image_levels,
             // intro_time: 1.0,
             time: 0.0,
             finish_time: None,
+            completed: None,
+            confetti: Vec::new(),
+            flung: Vec::new(),
+            physics_sync: 0.0,
+            cursor_vel: Vec2::ZERO,
+            last_cursor_world: Vec2::ZERO,
+            timelapse: Timelapse {
+                frames: Vec::new(),
+                interval: TIMELAPSE_INTERVAL,
+                next_frame: 0.0,
+                playing: false,
+            },
+            race: None,
+            room_start_time: 0.0,
+            host: None,
+            addr: addr.to_owned(),
+            room: room.to_owned(),
+            password,
+            client_config: config.clone(),
+            reconnect: None,
+            transition: None,
             cursor_pos: Vec2::ZERO,
             cursor_world: Vec2::ZERO,
             touch: None,
         }
     }
+    fn image(&self) -> &ugli::Texture {
+        match self.room_config.image {
+            ImageSource::Builtin(index) => &self.assets.images[index],
+            ImageSource::Uploaded(_) | ImageSource::Url(_) => self.custom_image.as_deref().unwrap(),
+        }
+    }
+    /// Image to draw the board from at the current zoom
+    fn board_image(&self) -> &ugli::Texture {
+        let image = self.image();
+        let texels_per_unit = image.size().y as f32 / PUZZLE_HEIGHT;
+        let pixels_per_unit = self.framebuffer_size.y as f32 / self.camera.fov;
+        self.image_levels
+            .get(image, pixels_per_unit / texels_per_unit)
+    }
+    fn play_sound(&self, sound: &geng::Sound) {
+        let mut effect = sound.effect();
+        effect.set_volume(self.settings.audio.effect_volume());
+        effect.play();
+    }
+    /// Plays a random variant with slightly different pitch and volume,
+    /// so that sounds heard hundreds of times are less repetitive
+    fn play_variant(&self, sounds: &[geng::Sound]) {
+        let mut rng = thread_rng();
+        let mut effect = sounds[rng.gen_range(0..sounds.len())].effect();
+        effect.set_volume(self.settings.audio.effect_volume() * rng.gen_range(0.8..=1.0));
+        effect.set_speed(rng.gen_range(0.95..=1.05));
+        effect.play();
+    }
+    /// Players normally arrive with [ServerMessage::PlayerJoined],
+    /// this also covers messages about someone not known yet
     fn get_player(&mut self, id: Id) -> &mut Player {
+        let time = self.time;
         if self.players.get(&id).is_none() {
             self.players.insert(Player {
                 id,
                 name: "".to_owned(),
                 color: Rgba::WHITE,
+                cursor: default(),
                 interpolation: Interpolated::new(Vec2::ZERO, Vec2::ZERO),
                 tile_grabbed: None,
+                also_grabbed: Vec::new(),
+                pieces: 0,
+                ping: None,
+                last_update: None,
+                last_active: time,
             });
         }
         self.players.get_mut(&id).unwrap()
     }
+    fn presence(&self, player: &Player) -> Presence {
+        if player.tile_grabbed.is_some() {
+            Presence::Holding
+        } else if player.id != self.id && self.time - player.last_active > IDLE_TIME {
+            Presence::Idle
+        } else {
+            Presence::Online
+        }
+    }
+    /// Name for the player list, with what the player is up to unless just online
+    fn list_name(&self, player: &Player) -> String {
+        let name = if player.name.is_empty() {
+            tr("game.someone")
+        } else {
+            player.name.clone()
+        };
+        let status = match self.presence(player) {
+            Presence::Online => return name,
+            Presence::Idle => tr("players.idle"),
+            Presence::Holding => tr("players.holding"),
+        };
+        tr_with("players.status", &[("name", &name), ("status", &status)])
+    }
+    /// Moves the cursor of another player towards the position from the server
+    fn update_remote_pos(&mut self, id: Id, pos: Vec2<f32>) {
+        let time = self.time;
+        let prediction = self.settings.network.prediction;
+        let player = self.get_player(id);
+        // Estimate velocity from the previous update to move the cursor ahead of it
+        let vel = match player.last_update {
+            Some((last_time, last_pos)) if time > last_time && time - last_time < 1.0 => {
+                (pos - last_pos) / (time - last_time)
+            }
+            _ => Vec2::ZERO,
+        };
+        if player
+            .last_update
+            .map_or(true, |(_, last_pos)| last_pos != pos)
+        {
+            player.last_active = time;
+        }
+        player.last_update = Some((time, pos));
+        player.interpolation.server_update(pos, vel * prediction);
+    }
     fn handle_connection(&mut self) {
         while let Some(message) = self.connection.try_recv() {
-            match message {
-                ServerMessage::SetupId { .. } => unreachable!(),
-                ServerMessage::RoomNotFound => unreachable!(),
-                ServerMessage::RoomCreated(..) => unreachable!(),
-                ServerMessage::UpdatePlayerName(id, name) => {
-                    self.get_player(id).name = name;
-                }
-                ServerMessage::UpdatePos(id, pos) => {
-                    self.get_player(id)
-                        .interpolation
-                        .server_update(pos, Vec2::ZERO);
-                }
-                ServerMessage::PlayerDisconnected(id) => {
-                    self.players.remove(&id);
-                }
-                ServerMessage::TileGrabbed {
-                    player,
-                    tile,
-                    offset,
-                } => {
-                    self.players.get_mut(&player).unwrap().tile_grabbed = Some((tile, offset));
-                    self.jigsaw.tiles[tile].grabbed_by = Some(player);
-                    for tile in self.jigsaw.get_all_connected(tile) {
-                        self.jigsaw.tiles[tile].last_interaction_time = self.time;
+            match message.unpack() {
+                Ok(messages) => {
+                    for message in messages {
+                        self.handle_message(message);
                     }
                 }
-                ServerMessage::TileReleased { player, tile, pos } => {
-                    let player = self.get_player(player);
-                    let offset = player
-                        .tile_grabbed
-                        .take()
-                        .map_or(Vec2::ZERO, |(_, offset)| offset);
-                    let vel = Some(player.interpolation.get_derivative());
-                    self.jigsaw.tiles[tile].grabbed_by = None;
-                    self.move_tile(tile, self.jigsaw.tiles[tile].interpolated.get(), vel, true);
-                    self.move_tile(tile, pos /*+ offset*/, None, false);
-                }
-                ServerMessage::ConnectTiles(a, b) => {
-                    self.jigsaw.tiles[a].connected_to.push(b);
-                    self.jigsaw.tiles[b].connected_to.push(a);
-                    let delta = self.jigsaw.tiles[a].puzzle_pos.map(|x| x as i32)
-                        - self.jigsaw.tiles[b].puzzle_pos.map(|x| x as i32);
-                    let pos = if delta.x == 0 && delta.y.abs() == 1 {
-                        // Tile is adjacent vertically
-                        self.jigsaw.tiles[b].interpolated.get()
-                            + vec2(0.0, self.jigsaw.tile_size.y * delta.y.signum() as f32)
-                    } else if delta.y == 0 && delta.x.abs() == 1 {
-                        // Tile is adjacent horizontally
-                        self.jigsaw.tiles[b].interpolated.get()
-                            + vec2(self.jigsaw.tile_size.x * delta.x.signum() as f32, 0.0)
-                    } else {
-                        unreachable!()
-                    };
-                    self.move_tile(a, pos, None, true);
-                    self.play_connect_sound = true;
+                // Whatever follows can not be trusted either, start over with a new connection
+                Err(e) => {
+                    error!("Failed to unpack message from server: {}", e);
+                    self.connection.connected = false;
+                    break;
                 }
             }
         }
     }
-    fn hovered_tile(&self, pos: Vec2<f32>) -> Option<usize> {
-        self.jigsaw
-            .tiles
-            .iter()
-            .enumerate()
-            .filter(|(_, tile)| tile.contains(pos))
-            .max_by_key(|(_, tile)| r32(tile.last_interaction_time))
-            .map(|(i, _)| i)
-    }
-    fn click(&mut self, screen_pos: Vec2<f64>) {
-        self.update_cursor(screen_pos);
-        let pos = self.camera.screen_to_world(
-            self.framebuffer_size.map(|x| x as f32),
-            screen_pos.map(|x| x as f32),
-        );
-        if let Some(i) = self
-            .hovered_tile(pos)
-            .filter(|_| self.finish_time.is_none())
-        {
-            let tile = self.jigsaw.tiles.get_mut(i).unwrap();
-            let player = self.players.get_mut(&self.id).unwrap();
-            let offset = tile.interpolated.get() - pos;
-            player.tile_grabbed = Some((i, offset));
-            tile.grabbed_by = Some(self.id);
-            for tile in self.jigsaw.get_all_connected(i) {
-                self.jigsaw.tiles[tile].last_interaction_time = self.time;
-            }
-            self.assets.sounds.grab.play();
-            self.connection
-                .send(ClientMessage::GrabTile { tile: i, offset });
-        } else {
-            self.start_drag(Dragging {
-                initial_screen_pos: screen_pos,
-                target: DragTarget::Camera {
-                    initial_camera_pos: self.camera.center,
-                },
-            });
-        }
-    }
-    fn release(&mut self) {
-        self.stop_drag();
-        let player = self.players.get_mut(&self.id).unwrap();
-        if let Some((tile_id, _)) = player.tile_grabbed.take() {
-            self.assets.sounds.grab.play();
-            let connected = self.jigsaw.get_all_connected(tile_id);
-            let tile = self.jigsaw.tiles.get_mut(tile_id).unwrap();
-            tile.grabbed_by = None;
-
-            // Try to connect
-            let mut moves = Vec::new();
-            for &tile_id in &connected {
-                let tile = self.jigsaw.tiles.get(tile_id).unwrap();
-                let pos = tile.interpolated.get();
-                let puzzle_pos = tile.puzzle_pos;
-                for (i, other) in self.jigsaw.tiles.iter().enumerate() {
-                    if tile.connected_to.contains(&i) {
-                        continue;
-                    }
-                    let delta = puzzle_pos.map(|x| x as i32) - other.puzzle_pos.map(|x| x as i32);
-                    let delta = if delta.x == 0 && delta.y.abs() == 1 {
-                        // Tile is adjacent vertically
-                        Some(
-                            pos - other.interpolated.get()
-                                - vec2(0.0, self.jigsaw.tile_size.y * delta.y.signum() as f32),
-                        )
-                    } else if delta.y == 0 && delta.x.abs() == 1 {
-                        // Tile is adjacent horizontally
-                        Some(
-                            pos - other.interpolated.get()
-                                - vec2(self.jigsaw.tile_size.x * delta.x.signum() as f32, 0.0),
-                        )
-                    } else {
+    fn handle_message(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::SetupId { .. } => unreachable!(),
+            ServerMessage::RoomNotFound
+            | ServerMessage::WrongPassword
+            | ServerMessage::RoomFull => unreachable!(),
+            ServerMessage::RoomList(..) => unreachable!(),
+            ServerMessage::RoomCreated(..) | ServerMessage::QuickPlayRoom(..) => unreachable!(),
+            ServerMessage::RoomRejected(..) => unreachable!(),
+            ServerMessage::ImageUploaded(..) | ServerMessage::ImageData(..) => unreachable!(),
+            ServerMessage::PlayerJoined {
+                id,
+                name,
+                color,
+                cursor,
+            } => {
+                let player = self.get_player(id);
+                player.name = name;
+                player.color = color;
+                player.cursor = cursor;
+            }
+            ServerMessage::UpdatePlayerName(id, name) => {
+                self.get_player(id).name = name;
+            }
+            ServerMessage::UpdatePlayerColor(id, color) => {
+                // Color picked by the player overrides the one assigned by the server
+                if id != self.id || self.settings.color.is_none() {
+                    self.get_player(id).color = color;
+                }
+            }
+            ServerMessage::UpdatePlayerCursor(id, cursor) => {
+                self.get_player(id).cursor = cursor;
+            }
+            ServerMessage::PlayerStats(stats) => {
+                for status in stats {
+                    let player = self.get_player(status.id);
+                    player.pieces = status.pieces;
+                    player.ping = status.ping;
+                }
+            }
+            ServerMessage::LatencyProbe => {
+                self.connection.send(ClientMessage::LatencyReply);
+            }
+            ServerMessage::UpdatePos(id, pos) => {
+                self.update_remote_pos(id, pos.into());
+            }
+            ServerMessage::PlayerDisconnected(id) => {
+                self.players.remove(&id);
+            }
+            ServerMessage::TileGrabbed {
+                player,
+                tile,
+                offset,
+            } => {
+                let holder = self.players.get_mut(&player).unwrap();
+                holder.tile_grabbed = Some((tile, offset));
+                holder.also_grabbed.clear();
+                holder.last_active = self.time;
+                self.jigsaw.tiles[tile].grabbed_by = Some(player);
+                self.jigsaw.set_interaction_time(tile, self.time);
+            }
+            ServerMessage::TilesGrabbed { player, tiles } => {
+                for &(tile, _) in &tiles {
+                    self.jigsaw.tiles[tile].grabbed_by = Some(player);
+                    self.jigsaw.set_interaction_time(tile, self.time);
+                }
+                let holder = self.players.get_mut(&player).unwrap();
+                holder.tile_grabbed = tiles.first().copied();
+                holder.also_grabbed = tiles.into_iter().skip(1).collect();
+                holder.last_active = self.time;
+            }
+            ServerMessage::GrabDenied(tile) => {
+                let player = self.players.get_mut(&self.id).unwrap();
+                if player.tile_grabbed.map(|(held, _)| held) == Some(tile) {
+                    // The rest of the selection is still ours
+                    player.tile_grabbed = if player.also_grabbed.is_empty() {
                         None
+                    } else {
+                        Some(player.also_grabbed.remove(0))
                     };
-                    if let Some(delta) = delta {
-                        // Delta to the snap position
-                        if delta.len() <= SNAP_DISTANCE {
-                            self.connection
-                                .send(ClientMessage::ConnectTiles(tile_id, i));
-                            let pos = pos - delta;
-                            moves.push((tile_id, pos));
-                        }
+                } else {
+                    player.also_grabbed.retain(|&(held, _)| held != tile);
+                }
+                // Unless the winner was already announced
+                if self.jigsaw.tiles[tile].grabbed_by == Some(self.id) {
+                    self.jigsaw.tiles[tile].grabbed_by = None;
+                }
+            }
+            ServerMessage::Updates { positions, tiles } => {
+                for (id, pos) in positions {
+                    self.update_remote_pos(id, pos.into());
+                }
+                for (tile, state) in tiles {
+                    self.sync_tile(tile, state);
+                }
+            }
+            ServerMessage::Keyframe(tiles) => {
+                for (tile, state) in tiles.into_iter().enumerate() {
+                    self.sync_tile(tile, state);
+                }
+            }
+            ServerMessage::Activity(activity) => {
+                fn display_name(name: &str) -> String {
+                    if name.is_empty() {
+                        tr("game.someone")
+                    } else {
+                        name.to_owned()
+                    }
+                }
+                let text = match activity {
+                    Activity::PlayerJoined { name } => {
+                        tr_with("feed.joined", &[("name", &display_name(&name))])
+                    }
+                    Activity::PlayerLeft { name } => {
+                        tr_with("feed.left", &[("name", &display_name(&name))])
+                    }
+                    Activity::PiecesConnected { name, pieces } => tr_with(
+                        if pieces == 1 {
+                            "feed.connected_one"
+                        } else {
+                            "feed.connected"
+                        },
+                        &[
+                            ("name", &display_name(&name)),
+                            ("pieces", &pieces.to_string()),
+                        ],
+                    ),
+                    Activity::LargestGroup(size) => {
+                        tr_with("feed.largest_group", &[("pieces", &size.to_string())])
                     }
+                };
+                self.feed.push((self.time, text));
+                if self.feed.len() > FEED_MAX_ENTRIES {
+                    self.feed.remove(0);
                 }
             }
-            for (tile, pos) in moves {
-                self.move_tile(tile, pos, None, true);
+            ServerMessage::Announcement(text) => {
+                self.banner = Some((self.time, text));
             }
-            self.connection.send(ClientMessage::ReleaseTile(
-                connected
-                    .into_iter()
-                    .map(|tile| (tile, self.jigsaw.tiles[tile].interpolated.get()))
-                    .collect(),
-            ));
+            ServerMessage::Kicked(reason) => {
+                warn!("{}", reason);
+                self.transition = Some(geng::Transition::Switch(Box::new(
+                    message_screen::MessageScreen::new(
+                        &self.geng,
+                        &self.addr,
+                        self.client_config.clone(),
+                        &reason,
+                    ),
+                )));
+            }
+            ServerMessage::Ping { player, pos } => {
+                let color = self.get_player(player).color;
+                self.pings.push((self.time, pos, color));
+                self.play_sound(&self.assets.sounds.ping);
+            }
+            ServerMessage::Elapsed(elapsed) => {
+                self.room_start_time = self.time - elapsed as f32;
+            }
+            ServerMessage::RoomHost(host) => {
+                self.host = host;
+            }
+            ServerMessage::PuzzleCompleted(stats) => {
+                if self.completed.is_none() {
+                    self.celebrate();
+                }
+                self.completed = Some((self.time, stats));
+            }
+            ServerMessage::RaceTeam(team) => {
+                self.race = Some(RaceState {
+                    team,
+                    rival_progress: 0.0,
+                    winner: None,
+                });
+            }
+            ServerMessage::RivalProgress(progress) => {
+                if let Some(race) = &mut self.race {
+                    race.rival_progress = progress;
+                }
+            }
+            ServerMessage::RaceWon(winner) => {
+                if let Some(race) = &mut self.race {
+                    if race.winner.is_none() {
+                        let (name, _) = TEAMS[winner];
+                        let text = tr_with(
+                            if winner == race.team {
+                                "game.race_won"
+                            } else {
+                                "game.race_lost"
+                            },
+                            &[("team", &tr(name))],
+                        );
+                        self.banner = Some((self.time, text));
+                    }
+                    race.winner = Some(winner);
+                }
+            }
+            ServerMessage::Packed(..)
+            | ServerMessage::Compressed(..)
+            | ServerMessage::Batch(..) => unreachable!(),
         }
     }
-    fn move_tile(&mut self, tile: usize, pos: Vec2<f32>, vel: Option<Vec2<f32>>, snap: bool) {
-        let vel = vel.unwrap_or(Vec2::ZERO);
-        let tiles = self.jigsaw.get_all_connected(tile);
-        let start_pos = self.jigsaw.tiles[tile].puzzle_pos.map(|x| x as i32);
-        for tile in tiles {
-            let delta = self.jigsaw.tiles[tile].puzzle_pos.map(|x| x as i32) - start_pos;
-            if snap {
-                self.jigsaw.tiles[tile]
-                    .interpolated
-                    .teleport(pos + delta.map(|x| x as f32) * self.jigsaw.tile_size, vel);
-            } else {
-                self.jigsaw.tiles[tile]
-                    .interpolated
-                    .server_update(pos + delta.map(|x| x as f32) * self.jigsaw.tile_size, vel);
+    /// Connects again and selects the same room, keeping the progress of the current player
+    fn connect_again(&self) -> util::Task<Option<(Connection, Vec<ServerMessage>)>> {
+        let connection = geng::net::client::connect(&self.addr);
+        let name = self.players.get(&self.id).unwrap().name.clone();
+        let previous_id = self.id;
+        let room = self.room.clone();
+        let password = self.password.clone();
+        util::Task::new(async move {
+            let mut connection: Connection = connection.await;
+            connection.send(ClientMessage::Handshake {
+                compression: true,
+                name,
+                token: Some(player_token()),
+            });
+            connection.send(ClientMessage::Resume(previous_id));
+            connection.send(ClientMessage::SelectRoom { room, password });
+            let mut messages = Vec::new();
+            while !messages.iter().any(|message| {
+                matches!(
+                    message,
+                    ServerMessage::SetupId { .. }
+                        | ServerMessage::RoomNotFound
+                        | ServerMessage::WrongPassword
+                        | ServerMessage::RoomFull
+                )
+            }) {
+                match connection.next().await?.unpack() {
+                    Ok(unpacked) => messages.extend(unpacked),
+                    Err(e) => {
+                        error!("Failed to unpack message from server: {}", e);
+                        return None;
+                    }
+                }
             }
-        }
+            Some((connection, messages))
+        })
     }
-    fn start_drag(&mut self, drag: Dragging) {
-        self.stop_drag();
-        self.dragging = Some(drag);
+    fn update_reconnect(&mut self) {
+        if !self.connection.connected && self.reconnect.is_none() && self.transition.is_none() {
+            warn!("Disconnected from server");
+            self.reconnect = Some(Reconnect {
+                attempts: 0,
+                next_attempt: self.time,
+                task: None,
+            });
+        }
+        let reconnect = match &mut self.reconnect {
+            Some(reconnect) => reconnect,
+            None => return,
+        };
+        match &mut reconnect.task {
+            Some(task) => match task.poll() {
+                Some(Some((connection, messages))) => {
+                    info!("Reconnected after {} attempts", reconnect.attempts + 1);
+                    self.reconnect = None;
+                    self.resume(connection, messages);
+                }
+                Some(None) => {
+                    reconnect.attempts += 1;
+                    let delay = 2f32
+                        .powi(reconnect.attempts as i32)
+                        .min(RECONNECT_DELAY_MAX);
+                    warn!("Failed to reconnect, next attempt in {} seconds", delay);
+                    reconnect.next_attempt = self.time + delay;
+                    reconnect.task = None;
+                }
+                None => {}
+            },
+            None => {
+                if self.time >= reconnect.next_attempt {
+                    let task = self.connect_again();
+                    self.reconnect.as_mut().unwrap().task = Some(task);
+                }
+            }
+        }
     }
-    fn update_cursor(&mut self, screen_pos: Vec2<f64>) {
-        self.cursor_pos = screen_pos;
-        let cursor_pos = self.camera.screen_to_world(
-            self.framebuffer_size.map(|x| x as f32),
-            screen_pos.map(|x| x as f32),
-        );
-        self.cursor_world = cursor_pos;
-        let clamped_pos = cursor_pos.clamp_aabb(self.bounds);
-        self.connection.send(ClientMessage::UpdatePos(clamped_pos));
-        let me = self.get_player(self.id);
-        me.interpolation.teleport(clamped_pos, Vec2::ZERO);
+    /// Switches to a new connection and applies the room snapshot received through it
+    fn resume(&mut self, connection: Connection, mut messages: Vec<ServerMessage>) {
+        let index = messages
+            .iter()
+            .position(|message| {
+                matches!(
+                    message,
+                    ServerMessage::SetupId { .. }
+                        | ServerMessage::RoomNotFound
+                        | ServerMessage::WrongPassword
+                        | ServerMessage::RoomFull
+                )
+            })
+            .unwrap();
+        let (player_id, tiles) = match messages.remove(index) {
+            ServerMessage::SetupId {
+                player_id, tiles, ..
+            } => (player_id, tiles),
+            _ => {
+                warn!("Room {:?} is gone", self.room);
+                self.transition = Some(geng::Transition::Switch(Box::new(main_menu::run(
+                    &self.geng,
+                    &self.addr,
+                    &self.client_config,
+                ))));
+                return;
+            }
+        };
+        self.connection = BatchedConnection::new(connection);
+        self.sent_viewport = None;
 
-        if let Some(dragging) = &mut self.dragging {
-            self.hovered_tile = None;
-            match dragging.target {
-                DragTarget::Camera { initial_camera_pos } => {
-                    let from = self.camera.screen_to_world(
-                        self.framebuffer_size.map(|x| x as f32),
-                        dragging.initial_screen_pos.map(|x| x as f32),
-                    );
-                    let target = initial_camera_pos + from - cursor_pos;
-                    self.camera.center = target.clamp_aabb(self.bounds);
-                }
+        // Others are sent again by the server
+        let mut me = self.players.remove(&self.id).unwrap();
+        me.id = player_id;
+        me.tile_grabbed = None;
+        me.also_grabbed.clear();
+        self.id = player_id;
+        self.connection
+            .send(ClientMessage::UpdateName(me.name.clone()));
+        if self.settings.color.is_some() {
+            self.connection.send(ClientMessage::UpdateColor(me.color));
+        }
+        self.connection
+            .send(ClientMessage::UpdateCursor(self.settings.cursor));
+        self.players = Collection::new();
+        self.players.insert(me);
+
+        for (i, state) in tiles.into_iter().enumerate() {
+            for &other in &state.connections {
+                self.jigsaw.connect(i, other);
             }
-        } else if let Some(hovered) = self.hovered_tile(clamped_pos) {
-            if Some(hovered) != self.hovered_tile {
-                self.hovered_tile = Some(hovered);
-                // self.assets.sounds.hover.play();
+            let tile = &mut self.jigsaw.tiles[i];
+            tile.grabbed_by = state.grabbed_by;
+            tile.rotation = state.rotation;
+            tile.in_tray = state.in_tray;
+            tile.locked = state.locked;
+            tile.interpolated.teleport(state.pos, Vec2::ZERO);
+        }
+        let locked: Vec<usize> = (0..self.jigsaw.tiles.len())
+            .filter(|&tile| self.jigsaw.tiles[tile].locked)
+            .collect();
+        for tile in locked {
+            self.jigsaw.lock_group(tile);
+        }
+        for message in messages {
+            if !matches!(message, ServerMessage::ImageData(..)) {
+                self.handle_message(message);
             }
-        } else {
-            self.hovered_tile = None;
         }
     }
-    fn stop_drag(&mut self) {
-        if let Some(_dragging) = self.dragging.take() {}
+    /// Reconciles local tile with the state from the server
+    /// Snaps the group of `a` to `b` once the server has connected them
+    fn connect_tiles(&mut self, a: usize, b: usize) {
+        let rotation = self.jigsaw.tiles[b].rotation;
+        self.jigsaw.connect(a, b);
+        self.jigsaw.rotate_group(a, rotation);
+        let pos = self.jigsaw.tiles[b].interpolated.get() + self.jigsaw.snap_offset(a, b).unwrap();
+        self.move_tile(a, pos, None, true);
+        self.play_connect_sound = true;
+        if self.tutorial.is_some()
+            && self
+                .touched
+                .iter()
+                .any(|&tile| self.jigsaw.get_all_connected(tile).contains(&a))
+        {
+            self.tutorial_progress(tutorial::TutorialStep::Snap);
+        }
     }
-    fn touch(&mut self, touches: Vec<geng::TouchPoint>) {
-        match &touches[..] {
-            [p] => self.click(p.position),
-            [a, b] => {
-                self.start_drag(Dragging {
-                    initial_screen_pos: (a.position + b.position) / 2.0,
-                    target: DragTarget::Camera {
-                        initial_camera_pos: self.camera.center,
-                    },
-                });
-                self.touch = Some(touches);
+    /// Applies the state of a tile from [ServerMessage::Updates] or [ServerMessage::Keyframe],
+    /// the only way changes made by others reach the tiles besides grabs
+    fn sync_tile(&mut self, tile_id: usize, state: TileState) {
+        for &other in &state.connections {
+            if !self.jigsaw.tiles[tile_id].connected_to.contains(&other) {
+                self.connect_tiles(tile_id, other);
             }
-            _ => {}
         }
-    }
-    fn update_touches(&mut self, touches: Vec<geng::TouchPoint>) {
-        match &touches[..] {
-            [p] => self.update_cursor(p.position),
-            [a, b] => {
-                if let Some([a0, b0]) = self.touch.as_deref() {
-                    // Zoom
-                    let d0 = (b0.position - a0.position).len() as f32;
-                    let d = (b.position - a.position).len() as f32;
-                    self.camera.fov = (self.camera.fov / (d / d0)).clamp(FOV_MIN, FOV_MAX);
-
-                    // Move
-                    if let Some(drag) = &self.dragging {
-                        let DragTarget::Camera { initial_camera_pos } = drag.target;
-                        {
-                            let p0 = drag.initial_screen_pos.map(|x| x as f32);
-                            let p1 = (a.position + b.position).map(|x| x as f32) / 2.0;
-                            let from = self
-                                .camera
-                                .screen_to_world(self.framebuffer_size.map(|x| x as f32), p0);
-                            let to = self
-                                .camera
-                                .screen_to_world(self.framebuffer_size.map(|x| x as f32), p1);
-                            let target = initial_camera_pos + from - to;
-                            self.camera.center = target.clamp_aabb(self.bounds);
-                        }
-                    }
-                }
-                self.touch = Some(touches);
+        if state.locked && !self.jigsaw.tiles[tile_id].locked {
+            self.jigsaw.lock_group(tile_id);
+        }
+        // Let go by someone else, keeps flying a bit with the speed of their cursor
+        if let (Some(holder), None) = (self.jigsaw.tiles[tile_id].grabbed_by, state.grabbed_by) {
+            if holder != self.id {
+                let vel = self
+                    .players
+                    .get(&holder)
+                    .map(|player| player.interpolation.get_derivative());
+                self.jigsaw.tiles[tile_id].grabbed_by = None;
+                let pos = self.jigsaw.tiles[tile_id].interpolated.get();
+                self.move_tile(tile_id, pos, vel, true);
+                self.move_tile(tile_id, state.pos, None, false);
             }
-            _ => {}
         }
-    }
-}
-
-impl geng::State for Game {
-    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
-        use geng::ui::*;
-        self.name_typing = false;
-        if self.customize {
-            let save_button = Button::new(cx, "save");
-            if save_button.was_clicked() {
-                self.customize = false;
-                batbox::preferences::save("name", &self.players.get(&self.id).unwrap().name);
-                batbox::preferences::save("show_names", &self.show_names);
-                self.connection.send(ClientMessage::UpdateName(
-                    self.players.get(&self.id).unwrap().name.clone(),
-                ));
+        let tile = &self.jigsaw.tiles[tile_id];
+        if tile.grabbed_by != Some(self.id) {
+            // Others turn groups while holding them
+            if tile.rotation != state.rotation {
+                self.jigsaw.rotate_group(tile_id, state.rotation);
             }
-            let name_input =
-                TextInput::new(cx, &mut self.players.get_mut(&self.id).unwrap().name, 15);
-            self.name_typing = *name_input.capture;
-            let show_names = Button::new(
+            self.jigsaw.tiles[tile_id].in_tray = state.in_tray;
+        }
+        let tile = &mut self.jigsaw.tiles[tile_id];
+        if tile.grabbed_by.is_none()
+            && state.grabbed_by.is_none()
+            && (tile.interpolated.get() - state.pos).len() > SYNC_DISTANCE
+        {
+            tile.interpolated.server_update(state.pos, Vec2::ZERO);
+        }
+    }
+    fn hovered_tile(&self, pos: Vec2<f32>) -> Option<usize> {
+        self.jigsaw
+            .tiles_at(pos)
+            .filter(|&i| {
+                let tile = &self.jigsaw.tiles[i];
+                !tile.in_tray && !tile.locked && !self.is_hidden(tile)
+            })
+            .max_by_key(|&i| (r32(self.jigsaw.tiles[i].last_interaction_time), i))
+    }
+    /// Tray area on the screen, to the right below the timer
+    fn tray_area(&self) -> AABB<f32> {
+        let framebuffer_size = self.framebuffer_size.map(|x| x as f32);
+        AABB::point(vec2(framebuffer_size.x, 0.0))
+            .extend_left(framebuffer_size.x * TRAY_WIDTH)
+            .extend_up(framebuffer_size.y * 0.9)
+    }
+    /// Screen area of the target image thumbnail, if it is shown
+    fn thumbnail_area(&self) -> Option<AABB<f32>> {
+        let corner = self.settings.thumbnail.corner?;
+        if self.show_reference || self.room_config.hard_mode {
+            return None;
+        }
+        let framebuffer_size = self.framebuffer_size.map(|x| x as f32);
+        let image_size = self.image().size().map(|x| x as f32);
+        let height = framebuffer_size.y * self.settings.thumbnail.size;
+        let size = vec2(height * image_size.x / image_size.y, height);
+        let margin = framebuffer_size.y * 0.02;
+        Some(match corner {
+            Corner::BottomRight => {
+                let right = if self.show_tray {
+                    self.tray_area().x_min
+                } else {
+                    framebuffer_size.x
+                };
+                AABB::point(vec2(right - margin, margin))
+                    .extend_left(size.x)
+                    .extend_up(size.y)
+            }
+            Corner::BottomLeft => AABB::point(vec2(margin, margin))
+                .extend_right(size.x)
+                .extend_up(size.y),
+        })
+    }
+    fn over_thumbnail(&self, screen_pos: Vec2<f64>) -> bool {
+        self.thumbnail_area()
+            .map_or(false, |area| area.contains(screen_pos.map(|x| x as f32)))
+    }
+    fn over_tray(&self, screen_pos: Vec2<f64>) -> bool {
+        self.show_tray && self.tray_area().contains(screen_pos.map(|x| x as f32))
+    }
+    /// Tiles in the tray along with the screen areas of their cells
+    fn tray_cells(&self) -> impl Iterator<Item = (usize, AABB<f32>)> + '_ {
+        let area = self.tray_area();
+        let cell_size = area.width() / TRAY_COLUMNS as f32;
+        self.jigsaw
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.in_tray && !self.is_hidden(tile))
+            .enumerate()
+            .map(move |(index, (tile, _))| {
+                let row = (index / TRAY_COLUMNS) as f32 - self.tray_scroll;
+                let column = (index % TRAY_COLUMNS) as f32;
+                let cell = AABB::point(vec2(
+                    area.x_min + column * cell_size,
+                    area.y_max - row * cell_size,
+                ))
+                .extend_right(cell_size)
+                .extend_down(cell_size);
+                (tile, cell)
+            })
+    }
+    /// Colors every connected group with its own hue on top of the image
+    fn draw_group_tints(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        // Hue comes from the smallest tile of the group, so it stays the same as the group grows
+        let mut hues: HashMap<usize, f32> = HashMap::new();
+        self.outline_vertices.clear();
+        for &tile in self.jigsaw.render_order() {
+            let group_tiles = self.jigsaw.get_all_connected(tile);
+            let tile_ref = &self.jigsaw.tiles[tile];
+            if group_tiles.len() < 2 || tile_ref.in_tray || self.is_hidden(tile_ref) {
+                continue;
+            }
+            let hue = *hues.entry(self.jigsaw.group(tile)).or_insert_with(|| {
+                let first = group_tiles.iter().copied().min().unwrap();
+                (first as f32 * 0.618_034).fract()
+            });
+            let color: Rgba<f32> = Hsva::new(hue, 0.8, 1.0, 0.25).into();
+            let matrix = self.jigsaw.render_matrix(tile);
+            let depth = self.jigsaw.depth(tile);
+            self.outline_vertices
+                .extend(self.jigsaw.tile_mesh(tile).iter().map(|v| {
+                    let pos = matrix * v.a_pos.extend(1.0);
+                    OutlineVertex {
+                        a_pos: (pos.xy() / pos.z).extend(depth),
+                        a_color: color,
+                    }
+                }));
+        }
+        ugli::draw(
+            framebuffer,
+            &self.assets.shaders.outline_batch,
+            ugli::DrawMode::Triangles,
+            &self.outline_vertices,
+            geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
+            ugli::DrawParameters {
+                blend_mode: Some(ugli::BlendMode::default()),
+                depth_func: Some(ugli::DepthFunc::LessOrEqual),
+                ..Default::default()
+            },
+        );
+    }
+    /// Soft shadows under held groups, so that lifted pieces look above the table.
+    /// Each tile's shadow is just below it in depth, so it falls on everything lower
+    fn draw_shadows(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        // Offsets of the blurred layers, relative to the tile size
+        const LAYERS: [f32; 3] = [0.06, 0.1, 0.14];
+        const LAYER_ALPHA: f32 = 0.15;
+        let offset =
+            vec2(0.5, -1.0) * partial_min(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y);
+        self.outline_vertices.clear();
+        for &tile in self.jigsaw.render_order() {
+            let tile_ref = &self.jigsaw.tiles[tile];
+            if self.jigsaw.held_tile(self.jigsaw.group(tile)).is_none()
+                || tile_ref.in_tray
+                || self.is_hidden(tile_ref)
+            {
+                continue;
+            }
+            let depth = self.jigsaw.depth(tile) + 0.5 / self.jigsaw.tiles.len() as f32;
+            for layer in LAYERS {
+                let matrix = Mat3::translate(offset * layer) * self.jigsaw.render_matrix(tile);
+                self.outline_vertices
+                    .extend(self.jigsaw.tile_mesh(tile).iter().map(|v| {
+                        let pos = matrix * v.a_pos.extend(1.0);
+                        OutlineVertex {
+                            a_pos: (pos.xy() / pos.z).extend(depth),
+                            a_color: Rgba::new(0.0, 0.0, 0.0, LAYER_ALPHA),
+                        }
+                    }));
+            }
+        }
+        ugli::draw(
+            framebuffer,
+            &self.assets.shaders.outline_batch,
+            ugli::DrawMode::Triangles,
+            &self.outline_vertices,
+            geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
+            ugli::DrawParameters {
+                blend_mode: Some(ugli::BlendMode::default()),
+                // Layers overlap at the center, making edges of the shadow softer
+                depth_func: Some(ugli::DepthFunc::LessOrEqual),
+                ..Default::default()
+            },
+        );
+    }
+    /// Everything lying on the table, drawn with the current camera
+    fn draw_board(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        self.draw_table(framebuffer);
+        self.geng.draw_2d(
+            framebuffer,
+            &self.camera,
+            &draw_2d::Quad::new(self.bounds, Rgba::new(0.1, 0.1, 0.1, 0.1)),
+        );
+        if let Some(race) = &self.race {
+            // Team colored border so nobody confuses the boards
+            let (_, color) = TEAMS[race.team];
+            let width = self.camera.fov * 0.01;
+            let outer = self.bounds.extend_uniform(width);
+            for side in [
+                AABB {
+                    x_max: self.bounds.x_min,
+                    ..outer
+                },
+                AABB {
+                    x_min: self.bounds.x_max,
+                    ..outer
+                },
+                AABB {
+                    y_max: self.bounds.y_min,
+                    ..outer
+                },
+                AABB {
+                    y_min: self.bounds.y_max,
+                    ..outer
+                },
+            ] {
+                self.geng
+                    .draw_2d(framebuffer, &self.camera, &draw_2d::Quad::new(side, color));
+            }
+        }
+        if self.room_config.lock_placed && !self.room_config.hard_mode {
+            // Frame guide showing where the puzzle goes
+            self.geng.draw_2d(
+                framebuffer,
+                &self.camera,
+                &draw_2d::Quad::new(self.frame, Rgba::new(0.0, 0.0, 0.0, 0.2)),
+            );
+            let width = self.camera.fov * 0.003;
+            let outer = self.frame.extend_uniform(width);
+            for side in [
+                AABB {
+                    x_max: outer.x_min + width,
+                    ..outer
+                },
+                AABB {
+                    x_min: outer.x_max - width,
+                    ..outer
+                },
+                AABB {
+                    y_max: outer.y_min + width,
+                    ..outer
+                },
+                AABB {
+                    y_min: outer.y_max - width,
+                    ..outer
+                },
+            ] {
+                self.geng.draw_2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw_2d::Quad::new(side, Rgba::new(1.0, 1.0, 1.0, 0.5)),
+                );
+            }
+        }
+
+        ugli::clear(framebuffer, None, Some(1.0), None);
+
+        self.jigsaw.prepare_render();
+
+        // Combine all meshes into 1
+        self.tile_vertices.clear();
+        for &tile in self.jigsaw.render_order() {
+            if self.jigsaw.tiles[tile].in_tray || self.is_hidden(&self.jigsaw.tiles[tile]) {
+                continue;
+            }
+            let matrix = self.jigsaw.render_matrix(tile);
+            let depth = self.jigsaw.depth(tile);
+            self.tile_vertices
+                .extend(self.jigsaw.tile_mesh(tile).iter().map(|v| {
+                    let pos = matrix * v.a_pos.extend(1.0);
+                    TileVertex {
+                        a_pos: (pos.xy() / pos.z).extend(depth),
+                        a_uv: v.a_uv,
+                        a_bevel: v.a_bevel,
+                        a_normal: (matrix * v.a_normal.extend(0.0)).xy(),
+                    }
+                }));
+        }
+        ugli::draw(
+            framebuffer,
+            &self.assets.shaders.jigsaw,
+            ugli::DrawMode::Triangles,
+            &self.tile_vertices,
+            (
+                ugli::uniforms! {
+                    u_model_matrix: Mat3::identity(),
+                    u_texture: self.board_image(),
+                    u_alpha: 1.0,
+                },
+                geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
+            ),
+            ugli::DrawParameters {
+                // blend_mode: Some(ugli::BlendMode::default()),
+                depth_func: Some(ugli::DepthFunc::Less),
+                ..Default::default()
+            },
+        );
+
+        if self.settings.tint_groups {
+            self.draw_group_tints(framebuffer);
+        }
+
+        // Translucent ghost where the held group would snap, just below the group itself
+        let snap_preview = if self.room_config.hard_mode {
+            None
+        } else {
+            self.snap_preview()
+        };
+        if let Some((held, delta)) = snap_preview {
+            let group_tiles = self.jigsaw.get_all_connected(held);
+            let depth = group_tiles
+                .iter()
+                .map(|&tile| self.jigsaw.depth(tile))
+                .fold(-1.0, f32::max)
+                + 1.0 / self.jigsaw.tiles.len() as f32;
+            self.tile_vertices.clear();
+            for &tile in group_tiles {
+                let matrix = Mat3::translate(-delta) * self.jigsaw.tiles[tile].matrix();
+                self.tile_vertices
+                    .extend(self.jigsaw.tile_mesh(tile).iter().map(|v| {
+                        let pos = matrix * v.a_pos.extend(1.0);
+                        TileVertex {
+                            a_pos: (pos.xy() / pos.z).extend(depth),
+                            a_uv: v.a_uv,
+                            a_bevel: v.a_bevel,
+                            a_normal: (matrix * v.a_normal.extend(0.0)).xy(),
+                        }
+                    }));
+            }
+            ugli::draw(
+                framebuffer,
+                &self.assets.shaders.jigsaw,
+                ugli::DrawMode::Triangles,
+                &self.tile_vertices,
+                (
+                    ugli::uniforms! {
+                        u_model_matrix: Mat3::identity(),
+                        u_texture: self.board_image(),
+                        u_alpha: 0.4,
+                    },
+                    geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
+                ),
+                ugli::DrawParameters {
+                    blend_mode: Some(ugli::BlendMode::default()),
+                    depth_func: Some(ugli::DepthFunc::Less),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let hovered_group = self
+            .hovered_tile
+            .filter(|_| !self.room_config.hard_mode)
+            .map(|tile| self.jigsaw.group(tile));
+        let selected_groups: HashSet<usize> = self
+            .selection
+            .iter()
+            .map(|&tile| self.jigsaw.group(tile))
+            .collect();
+
+        self.line_vertices.clear();
+        for (group_tiles, outline) in self.jigsaw.group_outlines() {
+            let anchor = group_tiles[0];
+            if self.jigsaw.tiles[anchor].in_tray
+                || group_tiles
+                    .iter()
+                    .all(|&tile| self.is_hidden(&self.jigsaw.tiles[tile]))
+            {
+                continue;
+            }
+            let matrix = self.jigsaw.render_matrix(anchor);
+            let holder = self
+                .jigsaw
+                .held_tile(self.jigsaw.group(anchor))
+                .and_then(|tile| self.jigsaw.tiles[tile].grabbed_by)
+                .and_then(|id| self.players.get(&id));
+            let highlighted = holder.is_some()
+                || hovered_group == Some(self.jigsaw.group(anchor))
+                || selected_groups.contains(&self.jigsaw.group(anchor));
+            let snapping = snap_preview.map_or(false, |(held, _)| {
+                self.jigsaw.group(held) == self.jigsaw.group(anchor)
+            });
+            let mut outline_color = if snapping {
+                Rgba::GREEN
+            } else if let Some(holder) = holder {
+                holder.color
+            } else if highlighted {
+                self.settings.highlight.color.color()
+            } else {
+                Rgba::BLACK
+            };
+            outline_color.a = self
+                .finish_time
+                .map_or(1.0, |finish| 1.0 - (self.time - finish));
+            // Draw at the depth of the topmost tile of the group
+            let depth = group_tiles
+                .iter()
+                .map(|&tile| self.jigsaw.depth(tile))
+                .fold(1.0, f32::min)
+                .clamp_abs(1.0);
+            let (width, color) = if highlighted {
+                let glow = self.settings.highlight.glow(self.time);
+                (
+                    self.settings.highlight.thickness as f32,
+                    Rgba::new(
+                        outline_color.r + (1.0 - outline_color.r) * glow,
+                        outline_color.g + (1.0 - outline_color.g) * glow,
+                        outline_color.b + (1.0 - outline_color.b) * glow,
+                        outline_color.a,
+                    ),
+                )
+            } else {
+                (1.0, outline_color)
+            };
+            // The outline is a list of separate segments
+            for segment in outline.chunks(2) {
+                let [a, b] = [segment[0], segment[1]].map(|v| {
+                    let pos = matrix * v.a_pos.extend(1.0);
+                    pos.xy() / pos.z
+                });
+                lines::push_line(&mut self.line_vertices, a, b, depth, width, color);
+            }
+        }
+        ugli::draw(
+            framebuffer,
+            &self.assets.shaders.outline,
+            ugli::DrawMode::Triangles,
+            &self.line_vertices,
+            (
+                ugli::uniforms! {
+                    u_framebuffer_size: framebuffer.size().map(|x| x as f32),
+                },
+                geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
+            ),
+            lines::draw_parameters(),
+        );
+
+        self.draw_shadows(framebuffer);
+    }
+    /// Renders the whole board, not only the visible part, and saves it as a png
+    fn screenshot(&mut self) {
+        let aspect = self.bounds.width() / self.bounds.height();
+        // Wide rooms are shot at a lower height to fit into a texture
+        let max_size = self.client_config.max_texture_size() as f32;
+        let height = (SCREENSHOT_HEIGHT as f32)
+            .min(max_size)
+            .min(max_size / aspect);
+        let size = vec2((height * aspect).round(), height.round()).map(|x| (x as usize).max(1));
+        let mut texture = ugli::Texture::new_uninitialized(self.geng.ugli(), size);
+        let mut renderbuffer = ugli::Renderbuffer::new(self.geng.ugli(), size);
+        let camera = Camera2d {
+            center: self.bounds.center(),
+            rotation: 0.0,
+            fov: self.bounds.height(),
+        };
+        let camera = std::mem::replace(&mut self.camera, camera);
+        let mut framebuffer = ugli::Framebuffer::new(
+            self.geng.ugli(),
+            ugli::ColorAttachment::Texture(&mut texture),
+            ugli::DepthAttachment::Renderbuffer(&mut renderbuffer),
+        );
+        ugli::clear(
+            &mut framebuffer,
+            Some(Rgba::try_from("#322214").unwrap()),
+            None,
+            None,
+        );
+        self.draw_board(&mut framebuffer);
+        self.camera = camera;
+
+        let data = framebuffer.read_color();
+        // Framebuffer rows go bottom to top
+        let image = image::RgbaImage::from_fn(size.x as u32, size.y as u32, |x, y| {
+            let color = data.get(x as usize, size.y - 1 - y as usize);
+            image::Rgba([color.r, color.g, color.b, color.a])
+        });
+        let mut png = Vec::new();
+        if let Err(e) = image.write_to(
+            &mut std::io::Cursor::new(&mut png),
+            image::ImageOutputFormat::Png,
+        ) {
+            warn!("Failed to encode screenshot: {}", e);
+            return;
+        }
+        util::save_file(&format!("jigsaw-{}.png", self.room), &png);
+    }
+    /// Surface under the board, as chosen in [Settings::table]
+    fn draw_table(&self, framebuffer: &mut ugli::Framebuffer) {
+        // Cover everything visible when zoomed out all the way
+        let area = self.bounds.extend_uniform(*self.fov_range.end());
+        match self.settings.table {
+            TableTheme::Wood => {
+                let texture = &self.assets.sprites.table;
+                let size = texture.size().map(|x| x as f32);
+                let ratio = (self.bounds.height() / size.y).max(self.bounds.width() / size.x) * 1.5;
+                let size = size * ratio;
+                self.geng.draw_2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw_2d::TexturedQuad::new(AABB::ZERO.extend_symmetric(size * 0.5), texture),
+                );
+            }
+            TableTheme::Felt => {
+                // Repeat the texture every couple of units
+                const TEXTURE_SIZE: f32 = 2.0;
+                let vertices = [
+                    area.bottom_left(),
+                    area.bottom_right(),
+                    area.top_right(),
+                    area.top_left(),
+                ]
+                .into_iter()
+                .map(|pos| draw_2d::TexturedVertex {
+                    a_pos: pos,
+                    a_color: Rgba::WHITE,
+                    a_vt: pos / TEXTURE_SIZE,
+                })
+                .collect();
+                self.geng.draw_2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw_2d::TexturedPolygon::new(vertices, &self.assets.sprites.felt),
+                );
+            }
+            TableTheme::Dark => {
+                ugli::clear(framebuffer, Some(Rgba::new(0.1, 0.1, 0.1, 1.0)), None, None)
+            }
+            TableTheme::Light => ugli::clear(
+                framebuffer,
+                Some(Rgba::new(0.8, 0.78, 0.72, 1.0)),
+                None,
+                None,
+            ),
+        }
+    }
+    fn draw_tray(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let framebuffer_size = framebuffer.size().map(|x| x as f32);
+        let area = self.tray_area();
+        self.geng.draw_2d(
+            framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::Quad::new(area, Rgba::new(0.0, 0.0, 0.0, 0.5)),
+        );
+
+        // Fit tiles with their tabs into the cells
+        let scale = area.width() / TRAY_COLUMNS as f32 * 0.6
+            / partial_max(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y);
+        let visible: Vec<(usize, AABB<f32>)> = self
+            .tray_cells()
+            .filter(|(_, cell)| cell.y_min >= area.y_min && cell.y_max <= area.y_max)
+            .collect();
+        self.tray_vertices.clear();
+        for (tile, cell) in visible {
+            let matrix = Mat3::translate(cell.center())
+                * Mat3::scale_uniform(scale)
+                * Mat3::rotate(
+                    self.jigsaw.tiles[tile].rotation as f32 * std::f32::consts::FRAC_PI_2,
+                );
+            self.tray_vertices
+                .extend(self.jigsaw.tile_mesh(tile).iter().map(|v| {
+                    let pos = matrix * v.a_pos.extend(1.0);
+                    TileVertex {
+                        a_pos: (pos.xy() / pos.z).extend(0.0),
+                        a_uv: v.a_uv,
+                        a_bevel: v.a_bevel,
+                        a_normal: (matrix * v.a_normal.extend(0.0)).xy(),
+                    }
+                }));
+        }
+        // Camera with one unit per pixel
+        let camera = Camera2d {
+            center: framebuffer_size / 2.0,
+            rotation: 0.0,
+            fov: framebuffer_size.y,
+        };
+        ugli::draw(
+            framebuffer,
+            &self.assets.shaders.jigsaw,
+            ugli::DrawMode::Triangles,
+            &self.tray_vertices,
+            (
+                ugli::uniforms! {
+                    u_model_matrix: Mat3::identity(),
+                    u_texture: self.image(),
+                    u_alpha: 1.0,
+                },
+                geng::camera2d_uniforms(&camera, framebuffer_size),
+            ),
+            ugli::DrawParameters::default(),
+        );
+    }
+    fn scroll_tray(&mut self, rows: f32) {
+        let tiles = self.tray_cells().count();
+        let max_scroll = (tiles / TRAY_COLUMNS) as f32;
+        self.tray_scroll = (self.tray_scroll + rows).clamp(0.0, max_scroll);
+    }
+    /// Advances the tutorial if the player just did what it asks
+    fn tutorial_progress(&mut self, step: tutorial::TutorialStep) {
+        if let Some(tutorial) = &mut self.tutorial {
+            if tutorial.complete(step) {
+                self.skip_tutorial();
+            }
+        }
+    }
+    fn skip_tutorial(&mut self) {
+        self.tutorial = None;
+        self.settings.tutorial_done = true;
+        self.settings.save();
+    }
+    /// Whether the tile is filtered out by the edges only mode
+    fn is_hidden(&self, tile: &JigsawTile) -> bool {
+        self.edges_only && !tile.is_edge
+    }
+    fn grab(&mut self, i: usize, pos: Vec2<f32>) {
+        let tile = self.jigsaw.tiles.get_mut(i).unwrap();
+        let player = self.players.get_mut(&self.id).unwrap();
+        let offset = tile.interpolated.get() - pos;
+        player.tile_grabbed = Some((i, offset));
+        tile.grabbed_by = Some(self.id);
+        self.jigsaw.set_interaction_time(i, self.time);
+        self.play_variant(&self.assets.sounds.grab);
+        // The board as shown lags behind the server by about the latency
+        let time = (self.time - self.room_start_time) as f64;
+        self.connection.send(ClientMessage::GrabTile {
+            tile: i,
+            offset,
+            time,
+        });
+        self.remember_grabbed(i);
+        self.tutorial_progress(tutorial::TutorialStep::Grab);
+    }
+    /// Remembers the tile for [Self::gather]
+    fn remember_grabbed(&mut self, tile: usize) {
+        const MAX_TOUCHED: usize = 50;
+        self.touched.retain(|&other| other != tile);
+        self.touched.push(tile);
+        if self.touched.len() > MAX_TOUCHED {
+            self.touched.remove(0);
+        }
+    }
+    /// Lays out loose tiles this player recently grabbed, or the selected ones,
+    /// in a grid around the camera center
+    fn gather(&mut self) {
+        let candidates = if self.selection.is_empty() {
+            &self.touched
+        } else {
+            &self.selection
+        };
+        let tiles: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&tile| {
+                let state = &self.jigsaw.tiles[tile];
+                state.connected_to.is_empty()
+                    && state.grabbed_by.is_none()
+                    && !state.in_tray
+                    && !state.locked
+            })
+            .collect();
+        if tiles.is_empty() {
+            return;
+        }
+        let columns = (tiles.len() as f32).sqrt().ceil() as usize;
+        let rows = (tiles.len() + columns - 1) / columns;
+        let spacing = partial_max(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y) * 1.5;
+        let origin =
+            self.camera.center - vec2(columns - 1, rows - 1).map(|x| x as f32) * spacing / 2.0;
+        let moves: Vec<(usize, Vec2<f32>)> = tiles
+            .into_iter()
+            .enumerate()
+            .map(|(i, tile)| {
+                let cell = vec2(i % columns, rows - 1 - i / columns).map(|x| x as f32);
+                (tile, (origin + cell * spacing).clamp_aabb(self.bounds))
+            })
+            .collect();
+        for &(tile, pos) in &moves {
+            self.move_tile(tile, pos, None, false);
+            self.jigsaw.set_interaction_time(tile, self.time);
+        }
+        self.connection.send(ClientMessage::MoveTiles(moves));
+    }
+    /// Grabs the clicked tile along with the rest of the selected groups
+    fn grab_selection(&mut self, i: usize, pos: Vec2<f32>) {
+        let mut groups = HashSet::new();
+        groups.insert(self.jigsaw.group(i));
+        let mut tiles = vec![i];
+        for &tile in &self.selection {
+            let state = &self.jigsaw.tiles[tile];
+            if state.grabbed_by.is_none()
+                && !state.in_tray
+                && !state.locked
+                && groups.insert(self.jigsaw.group(tile))
+            {
+                tiles.push(tile);
+            }
+        }
+        let grabbed: Vec<(usize, Vec2<f32>)> = tiles
+            .into_iter()
+            .map(|tile| {
+                let state = &mut self.jigsaw.tiles[tile];
+                state.grabbed_by = Some(self.id);
+                (tile, state.interpolated.get() - pos)
+            })
+            .collect();
+        for &(tile, _) in &grabbed {
+            self.jigsaw.set_interaction_time(tile, self.time);
+            self.remember_grabbed(tile);
+        }
+        let player = self.players.get_mut(&self.id).unwrap();
+        player.tile_grabbed = Some(grabbed[0]);
+        player.also_grabbed = grabbed[1..].to_vec();
+        self.play_variant(&self.assets.sounds.grab);
+        self.connection.send(ClientMessage::GrabTiles(grabbed));
+        self.tutorial_progress(tutorial::TutorialStep::Grab);
+    }
+    fn click(&mut self, screen_pos: Vec2<f64>) {
+        self.update_cursor(screen_pos);
+        let pos = self.camera.screen_to_world(
+            self.framebuffer_size.map(|x| x as f32),
+            screen_pos.map(|x| x as f32),
+        );
+        if self.over_tray(screen_pos) {
+            let clicked = self
+                .tray_cells()
+                .find(|(_, cell)| cell.contains(screen_pos.map(|x| x as f32)));
+            if let Some((i, _)) = clicked {
+                // Take the tile out onto the board under the cursor
+                let pos = pos.clamp_aabb(self.bounds);
+                let tile = &mut self.jigsaw.tiles[i];
+                tile.in_tray = false;
+                tile.interpolated.teleport(pos, Vec2::ZERO);
+                self.connection.send(ClientMessage::SetInTray {
+                    tile: i,
+                    in_tray: false,
+                });
+                self.grab(i, pos);
+            }
+        } else if self.geng.window().is_key_pressed(geng::Key::LShift)
+            || self.geng.window().is_key_pressed(geng::Key::RShift)
+        {
+            self.selection.clear();
+            self.start_drag(Dragging {
+                initial_screen_pos: screen_pos,
+                target: DragTarget::Select { start: pos },
+            });
+        } else if let Some(i) = self
+            .hovered_tile(pos)
+            .filter(|_| self.finish_time.is_none())
+        {
+            let group = self.jigsaw.group(i);
+            if self
+                .selection
+                .iter()
+                .any(|&tile| self.jigsaw.group(tile) == group)
+            {
+                self.grab_selection(i, pos);
+            } else {
+                self.selection.clear();
+                self.grab(i, pos);
+            }
+        } else {
+            self.selection.clear();
+            self.start_pan(screen_pos);
+        }
+    }
+    fn release(&mut self) {
+        self.stop_drag();
+        let to_tray = self.over_tray(self.cursor_pos);
+        let player = self.players.get_mut(&self.id).unwrap();
+        let also_grabbed = std::mem::take(&mut player.also_grabbed);
+        if let Some((tile_id, _)) = player.tile_grabbed.take() {
+            self.play_variant(&self.assets.sounds.grab);
+            let connected = self.jigsaw.get_all_connected(tile_id).to_vec();
+            let tile = self.jigsaw.tiles.get_mut(tile_id).unwrap();
+            tile.grabbed_by = None;
+
+            if to_tray && connected.len() == 1 && also_grabbed.is_empty() {
+                tile.in_tray = true;
+                let pos = tile.interpolated.get();
+                self.connection
+                    .send(ClientMessage::ReleaseTile(vec![(tile_id, pos)]));
+                self.connection.send(ClientMessage::SetInTray {
+                    tile: tile_id,
+                    in_tray: true,
+                });
+                return;
+            }
+
+            let mut groups = vec![connected];
+            for (other, _) in also_grabbed {
+                self.jigsaw.tiles[other].grabbed_by = None;
+                groups.push(self.jigsaw.get_all_connected(other).to_vec());
+            }
+
+            // Try to connect
+            let mut moves = Vec::new();
+            let mut connections = Vec::new();
+            for group in &groups {
+                for (tile_id, other, delta) in self.snaps(group) {
+                    connections.push((tile_id, other));
+                    moves.push((
+                        tile_id,
+                        self.jigsaw.tiles[tile_id].interpolated.get() - delta,
+                    ));
+                }
+            }
+            for (tile, pos) in moves {
+                self.move_tile(tile, pos, None, true);
+            }
+
+            // Lock groups released at their spot
+            let mut placed = Vec::new();
+            if self.room_config.lock_placed {
+                for group in &groups {
+                    let tile = group[0];
+                    let target = self.placed_pos(tile);
+                    if self.jigsaw.tiles[tile].rotation == 0
+                        && (self.jigsaw.tiles[tile].interpolated.get() - target).len()
+                            <= self.snap_distance()
+                    {
+                        self.move_tile(tile, target, None, true);
+                        placed.push(tile);
+                    }
+                }
+            }
+
+            // Groups that did not end up anywhere keep the momentum of the cursor
+            let tile_speed = self.cursor_vel.len()
+                / partial_min(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y);
+            if self.room_config.physics && tile_speed > PHYSICS_STOP_SPEED {
+                for group in &groups {
+                    let snapped = connections.iter().any(|(tile, _)| group.contains(tile));
+                    if !snapped && !placed.contains(&group[0]) {
+                        self.flung.push(Flung {
+                            tile: group[0],
+                            pos: self.jigsaw.tiles[group[0]].interpolated.get(),
+                            vel: self.cursor_vel,
+                        });
+                    }
+                }
+            }
+
+            self.connection.send(ClientMessage::ReleaseTile(
+                groups
+                    .into_iter()
+                    .flatten()
+                    .map(|tile| (tile, self.jigsaw.tiles[tile].interpolated.get()))
+                    .collect(),
+            ));
+            // After the release, so that the server checks connections against final positions
+            for (tile_id, other) in connections {
+                self.connection
+                    .send(ClientMessage::ConnectTiles(tile_id, other));
+            }
+            for tile in placed {
+                self.jigsaw.lock_group(tile);
+                self.connection.send(ClientMessage::LockGroup(tile));
+            }
+        }
+    }
+    /// Moves flung groups along, slowing them down and bouncing them off obstacles
+    fn update_physics(&mut self, delta_time: f32) {
+        let smoothing = 1.0 - (-delta_time / 0.05).exp();
+        let cursor_vel = (self.cursor_world - self.last_cursor_world) / delta_time.max(1e-3);
+        self.cursor_vel += (cursor_vel - self.cursor_vel) * smoothing;
+        self.last_cursor_world = self.cursor_world;
+
+        if self.flung.is_empty() {
+            return;
+        }
+        let tile_size = partial_min(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y);
+        let reach = partial_max(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y);
+        let mut flung = std::mem::take(&mut self.flung);
+        let mut moved = Vec::new();
+        let mut stopped = false;
+        flung.retain_mut(|flung| {
+            let jigsaw = &self.jigsaw;
+            let group = jigsaw.get_all_connected(flung.tile);
+            if group.iter().any(|&tile| {
+                let tile = &jigsaw.tiles[tile];
+                tile.grabbed_by.is_some() || tile.in_tray || tile.locked
+            }) {
+                // Somebody caught it
+                return false;
+            }
+
+            // Soft collisions, pushed away from every overlapping tile of other groups
+            let anchor = jigsaw.tiles[flung.tile].home;
+            let rotation = jigsaw.tiles[flung.tile].rotation;
+            let offset =
+                |tile: usize| util::rotate_quarters(jigsaw.tiles[tile].home - anchor, rotation);
+            for &tile in group {
+                let pos = flung.pos + offset(tile);
+                for other in jigsaw.tiles_near(pos, reach) {
+                    if jigsaw.group(other) == jigsaw.group(flung.tile) {
+                        continue;
+                    }
+                    let delta = pos - jigsaw.tiles[other].interpolated.get();
+                    let distance = delta.len();
+                    if distance > 1e-5 && distance < tile_size {
+                        flung.vel +=
+                            delta / distance * (tile_size - distance) * PHYSICS_PUSH * delta_time;
+                    }
+                }
+            }
+
+            flung.vel = flung.vel * (-PHYSICS_FRICTION * delta_time).exp();
+            flung.pos += flung.vel * delta_time;
+
+            // Bounce off the edges of the board
+            let mut extent = AABB::point(flung.pos);
+            for &tile in group {
+                let pos = flung.pos + offset(tile);
+                extent = AABB {
+                    x_min: partial_min(extent.x_min, pos.x),
+                    x_max: partial_max(extent.x_max, pos.x),
+                    y_min: partial_min(extent.y_min, pos.y),
+                    y_max: partial_max(extent.y_max, pos.y),
+                };
+            }
+            let bounds = self.bounds;
+            if extent.x_min < bounds.x_min {
+                flung.pos.x += bounds.x_min - extent.x_min;
+                flung.vel.x = flung.vel.x.abs() * PHYSICS_RESTITUTION;
+            } else if extent.x_max > bounds.x_max {
+                flung.pos.x -= extent.x_max - bounds.x_max;
+                flung.vel.x = -flung.vel.x.abs() * PHYSICS_RESTITUTION;
+            }
+            if extent.y_min < bounds.y_min {
+                flung.pos.y += bounds.y_min - extent.y_min;
+                flung.vel.y = flung.vel.y.abs() * PHYSICS_RESTITUTION;
+            } else if extent.y_max > bounds.y_max {
+                flung.pos.y -= extent.y_max - bounds.y_max;
+                flung.vel.y = -flung.vel.y.abs() * PHYSICS_RESTITUTION;
+            }
+
+            moved.push((flung.tile, flung.pos));
+            let moving = flung.vel.len() / tile_size > PHYSICS_STOP_SPEED;
+            stopped |= !moving;
+            moving
+        });
+        self.flung = flung;
+
+        for &(tile, pos) in &moved {
+            self.move_tile(tile, pos, None, true);
+        }
+        self.physics_sync -= delta_time;
+        if self.physics_sync <= 0.0 || stopped {
+            self.physics_sync = PHYSICS_SYNC_INTERVAL;
+            let positions = moved
+                .into_iter()
+                .flat_map(|(tile, _)| self.jigsaw.get_all_connected(tile).to_vec())
+                .map(|tile| (tile, self.jigsaw.tiles[tile].interpolated.get()))
+                .collect();
+            self.connection.send(ClientMessage::MoveTiles(positions));
+        }
+    }
+    /// Throws confetti over the finished puzzle and plays the fanfare
+    fn celebrate(&mut self) {
+        self.play_sound(&self.assets.sounds.fanfare);
+        let mut rng = thread_rng();
+        let colors: Vec<Rgba<f32>> = player_colors().collect();
+        for _ in 0..CONFETTI_COUNT {
+            self.confetti.push(Confetti {
+                pos: vec2(
+                    rng.gen_range(self.frame.x_min..=self.frame.x_max),
+                    self.frame.y_max + rng.gen_range(0.0..=self.frame.height()),
+                ),
+                vel: vec2(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=0.5)),
+                rotation: rng.gen_range(0.0..=std::f32::consts::TAU),
+                spin: rng.gen_range(-10.0..=10.0),
+                color: colors[rng.gen_range(0..colors.len())],
+            });
+        }
+    }
+    fn timelapse_frame(&self) -> TimelapseFrame {
+        self.jigsaw
+            .tiles
+            .iter()
+            .map(|tile| (tile.interpolated.get(), tile.rotation, tile.in_tray))
+            .collect()
+    }
+    /// Takes a snapshot of tile positions every now and then while the puzzle is being solved
+    fn record_timelapse(&mut self, delta_time: f32) {
+        self.timelapse.next_frame -= delta_time;
+        if self.timelapse.next_frame > 0.0 {
+            return;
+        }
+        self.timelapse.next_frame = self.timelapse.interval;
+        let frame = self.timelapse_frame();
+        if self.timelapse.frames.last() == Some(&frame) {
+            return;
+        }
+        self.timelapse.frames.push(frame);
+        if self.timelapse.frames.len() > TIMELAPSE_MAX_FRAMES {
+            let mut index = 0;
+            self.timelapse.frames.retain(|_| {
+                index += 1;
+                index % 2 == 1
+            });
+            self.timelapse.interval *= 2.0;
+        }
+    }
+    /// Moves tiles to where they were at the given point of the session, from 0 to 1
+    fn play_timelapse(&mut self, progress: f32) {
+        let frames = &self.timelapse.frames;
+        let position = progress.clamp(0.0, 1.0) * (frames.len() - 1) as f32;
+        let index = (position as usize).min(frames.len() - 2);
+        let t = position - index as f32;
+        for (i, tile) in self.jigsaw.tiles.iter_mut().enumerate() {
+            let (from, rotation, in_tray) = frames[index][i];
+            let (to, next_rotation, next_in_tray) = frames[index + 1][i];
+            tile.interpolated
+                .teleport(from + (to - from) * t, Vec2::ZERO);
+            (tile.rotation, tile.in_tray) = if t < 0.5 {
+                (rotation, in_tray)
+            } else {
+                (next_rotation, next_in_tray)
+            };
+        }
+    }
+    fn update_celebration(&mut self, delta_time: f32) {
+        const GRAVITY: f32 = 2.0;
+        const DRAG: f32 = 1.5;
+        for piece in &mut self.confetti {
+            piece.vel.y -= GRAVITY * delta_time;
+            piece.vel -= piece.vel * (DRAG * delta_time).min(1.0);
+            piece.pos += piece.vel * delta_time;
+            piece.rotation += piece.spin * delta_time;
+        }
+        let bottom = self.bounds.y_min;
+        self.confetti.retain(|piece| piece.pos.y > bottom);
+
+        // Slowly frame the finished image, or the whole board during the timelapse
+        if self.completed.is_some() {
+            let target = if self.timelapse.playing {
+                self.bounds
+            } else {
+                self.frame
+            };
+            let fov = (target.height() * 1.3).clamp(*self.fov_range.start(), *self.fov_range.end());
+            let k = (delta_time * 1.5).min(1.0);
+            self.camera.center += (target.center() - self.camera.center) * k;
+            self.camera.fov += (fov - self.camera.fov) * k;
+        }
+    }
+    /// Everyone in the room with their colors, connected pieces and ping, shown while Tab is held
+    fn draw_scoreboard(&self, framebuffer: &mut ugli::Framebuffer) {
+        let framebuffer_size = framebuffer.size().map(|x| x as f32);
+        let font_size = framebuffer_size.y * 0.035;
+        let row_height = font_size * 1.4;
+        let mut players: Vec<&Player> = self.players.iter().collect();
+        players.sort_by(|a, b| b.pieces.cmp(&a.pieces));
+        let panel = AABB::point(framebuffer_size / 2.0).extend_symmetric(vec2(
+            framebuffer_size.x * 0.25,
+            row_height * (players.len() + 1) as f32 / 2.0 + font_size,
+        ));
+        self.geng.draw_2d(
+            framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::Quad::new(panel, Rgba::new(0.0, 0.0, 0.0, 0.7)),
+        );
+        let name_x = panel.x_min + font_size * 2.0;
+        let pieces_x = panel.x_min + panel.width() * 0.75;
+        let ping_x = panel.x_max - font_size;
+        let rows = std::iter::once((
+            None,
+            tr("players.player"),
+            tr("players.pieces"),
+            tr("players.ping"),
+        ))
+        .chain(players.into_iter().map(|player| {
+            let name = self.list_name(player);
+            let ping = player.ping.map_or("?".to_owned(), |ping| {
+                tr_with("players.ping_ms", &[("ping", &ping.to_string())])
+            });
+            (Some(player.color), name, player.pieces.to_string(), ping)
+        }));
+        for (i, (color, name, pieces, ping)) in rows.enumerate() {
+            let y = panel.y_max - font_size * 1.5 - i as f32 * row_height;
+            if let Some(color) = color {
+                self.geng.draw_2d(
+                    framebuffer,
+                    &geng::PixelPerfectCamera,
+                    &draw_2d::Quad::new(
+                        AABB::point(vec2(panel.x_min + font_size, y + font_size * 0.35))
+                            .extend_uniform(font_size * 0.3),
+                        color,
+                    ),
+                );
+            }
+            let text_color = if color.is_some() {
+                Rgba::WHITE
+            } else {
+                Rgba::new(0.7, 0.7, 0.7, 1.0)
+            };
+            for (text, x, align) in [
+                (&name, name_x, geng::TextAlign::LEFT),
+                (&pieces, pieces_x, geng::TextAlign::RIGHT),
+                (&ping, ping_x, geng::TextAlign::RIGHT),
+            ] {
+                self.geng.default_font().draw_with_outline(
+                    framebuffer,
+                    &geng::PixelPerfectCamera,
+                    text,
+                    vec2(x, y),
+                    align,
+                    font_size,
+                    text_color,
+                    font_size * 0.05,
+                    Rgba::BLACK,
+                );
+            }
+        }
+    }
+    fn draw_confetti(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        if self.confetti.is_empty() {
+            return;
+        }
+        let size = partial_min(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y) * 0.1;
+        self.outline_vertices.clear();
+        for piece in &self.confetti {
+            let matrix = Mat3::translate(piece.pos)
+                * Mat3::rotate(piece.rotation)
+                * Mat3::scale(vec2(size, size * 0.5));
+            let corners = [
+                vec2(-1.0, -1.0),
+                vec2(1.0, -1.0),
+                vec2(1.0, 1.0),
+                vec2(-1.0, 1.0),
+            ]
+            .map(|corner| (matrix * corner.extend(1.0)).xy());
+            for index in [0, 1, 2, 0, 2, 3] {
+                self.outline_vertices.push(OutlineVertex {
+                    a_pos: corners[index].extend(0.0),
+                    a_color: piece.color,
+                });
+            }
+        }
+        ugli::draw(
+            framebuffer,
+            &self.assets.shaders.outline_batch,
+            ugli::DrawMode::Triangles,
+            &self.outline_vertices,
+            geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
+            ugli::DrawParameters::default(),
+        );
+    }
+    /// Marks a spot on the board for everyone in the room
+    fn ping(&mut self, screen_pos: Vec2<f64>) {
+        let pos = self
+            .camera
+            .screen_to_world(
+                self.framebuffer_size.map(|x| x as f32),
+                screen_pos.map(|x| x as f32),
+            )
+            .clamp_aabb(self.bounds);
+        let color = self.players.get(&self.id).unwrap().color;
+        self.pings.push((self.time, pos, color));
+        self.play_sound(&self.assets.sounds.ping);
+        self.connection.send(ClientMessage::Ping(pos));
+    }
+    /// Goes back to the main menu, the server keeps the progress
+    fn leave(&mut self) {
+        self.settings.save();
+        // Reloading the page should not join the room again
+        util::set_room_url(None);
+        self.transition = Some(geng::Transition::Switch(Box::new(main_menu::run(
+            &self.geng,
+            &self.addr,
+            &self.client_config,
+        ))));
+    }
+    /// Copies the room link, or just its code on native, for inviting others
+    fn copy_invite(&mut self) {
+        util::copy_to_clipboard(&util::invite_text(&self.room));
+        self.invite_copied = Some(self.time);
+    }
+    /// Tells the server which part of the board is visible, if it changed
+    fn report_viewport(&mut self) {
+        let framebuffer_size = self.framebuffer_size.map(|x| x as f32);
+        let half = vec2(framebuffer_size.x / framebuffer_size.y, 1.0) * self.camera.fov / 2.0;
+        let viewport = (
+            CompactPos::from(self.camera.center - half),
+            CompactPos::from(self.camera.center + half),
+        );
+        if self.sent_viewport != Some(viewport) {
+            self.sent_viewport = Some(viewport);
+            self.connection
+                .send(ClientMessage::UpdateViewport(viewport.0, viewport.1));
+        }
+    }
+    /// How close tiles need to be to snap, scaled with the tile size
+    fn snap_distance(&self) -> f32 {
+        self.room_config.effective_snap_distance()
+            * partial_min(self.jigsaw.tile_size.x, self.jigsaw.tile_size.y)
+    }
+    /// Position of the tile in the assembled puzzle inside the frame
+    fn placed_pos(&self, tile: usize) -> Vec2<f32> {
+        self.frame.bottom_left() + self.jigsaw.tiles[tile].home
+    }
+    /// Tiles of the group close enough to snap to another tile,
+    /// as `(tile, other, delta)` where `delta` is how far the group is from the snap position
+    fn snaps(&self, group: &[usize]) -> Vec<(usize, usize, Vec2<f32>)> {
+        let mut snaps = Vec::new();
+        for &tile_id in group {
+            let tile = &self.jigsaw.tiles[tile_id];
+            let pos = tile.interpolated.get();
+            for i in self.jigsaw.neighbors_near(tile_id, self.snap_distance()) {
+                let other = &self.jigsaw.tiles[i];
+                if tile.connected_to.contains(&i)
+                    || other.rotation != tile.rotation
+                    || other.in_tray
+                    || self.is_hidden(other)
+                {
+                    continue;
+                }
+                if let Some(offset) = self.jigsaw.snap_offset(tile_id, i) {
+                    let delta = pos - other.interpolated.get() - offset;
+                    if delta.len() <= self.snap_distance() {
+                        snaps.push((tile_id, i, delta));
+                    }
+                }
+            }
+        }
+        snaps
+    }
+    /// Tile held by this player and how far its group is from where it would snap if released now
+    fn snap_preview(&self) -> Option<(usize, Vec2<f32>)> {
+        let (tile, _) = self.players.get(&self.id).unwrap().tile_grabbed?;
+        let (_, _, delta) = self
+            .snaps(self.jigsaw.get_all_connected(tile))
+            .into_iter()
+            .next()?;
+        Some((tile, delta))
+    }
+    /// Rotates the group of the grabbed tile by given number of allowed steps,
+    /// see [Rotation::step]
+    fn rotate_grabbed(&mut self, steps: u8) {
+        let step = match self.room_config.rotation.step() {
+            Some(step) => step,
+            None => return,
+        };
+        let player = self.players.get(&self.id).unwrap();
+        if let Some((tile, _)) = player.tile_grabbed {
+            // Groups of a box selection would end up overlapping
+            if !player.also_grabbed.is_empty() {
+                return;
+            }
+            let rotation = (self.jigsaw.tiles[tile].rotation + steps * step) % 4;
+            self.jigsaw.rotate_group(tile, rotation);
+            self.connection
+                .send(ClientMessage::RotateTile { tile, rotation });
+        }
+    }
+    fn move_tile(&mut self, tile: usize, pos: Vec2<f32>, vel: Option<Vec2<f32>>, snap: bool) {
+        self.jigsaw
+            .move_group(tile, pos, vel.unwrap_or(Vec2::ZERO), snap);
+    }
+    fn start_drag(&mut self, drag: Dragging) {
+        self.stop_drag();
+        self.dragging = Some(drag);
+    }
+    fn start_pan(&mut self, screen_pos: Vec2<f64>) {
+        self.start_drag(Dragging {
+            initial_screen_pos: screen_pos,
+            target: DragTarget::Camera {
+                initial_camera_pos: self.camera.center,
+            },
+        });
+    }
+    fn update_cursor(&mut self, screen_pos: Vec2<f64>) {
+        self.cursor_pos = screen_pos;
+        let cursor_pos = self.camera.screen_to_world(
+            self.framebuffer_size.map(|x| x as f32),
+            screen_pos.map(|x| x as f32),
+        );
+        self.cursor_world = cursor_pos;
+        let clamped_pos = cursor_pos.clamp_aabb(self.bounds);
+        self.connection
+            .send(ClientMessage::UpdatePos(clamped_pos.into()));
+        let me = self.get_player(self.id);
+        me.interpolation.teleport(clamped_pos, Vec2::ZERO);
+
+        if let Some(dragging) = &mut self.dragging {
+            self.hovered_tile = None;
+            match dragging.target {
+                DragTarget::Camera { initial_camera_pos } => {
+                    let from = self.camera.screen_to_world(
+                        self.framebuffer_size.map(|x| x as f32),
+                        dragging.initial_screen_pos.map(|x| x as f32),
+                    );
+                    let target = initial_camera_pos + from - cursor_pos;
+                    self.camera.center = target.clamp_aabb(self.bounds);
+                }
+                DragTarget::Select { .. } => {}
+            }
+        } else if self.over_tray(screen_pos) {
+            self.hovered_tile = None;
+        } else if let Some(hovered) = self.hovered_tile(clamped_pos) {
+            if Some(hovered) != self.hovered_tile {
+                self.hovered_tile = Some(hovered);
+                // self.assets.sounds.hover.play();
+            }
+        } else {
+            self.hovered_tile = None;
+        }
+    }
+    fn stop_drag(&mut self) {
+        if let Some(dragging) = self.dragging.take() {
+            if let DragTarget::Camera { initial_camera_pos } = dragging.target {
+                if self.camera.center != initial_camera_pos {
+                    self.tutorial_progress(tutorial::TutorialStep::Pan);
+                }
+            }
+            if let DragTarget::Select { start } = dragging.target {
+                let area = select_area(start, self.cursor_world);
+                // One tile per group
+                let mut groups = HashSet::new();
+                let selection = (0..self.jigsaw.tiles.len())
+                    .filter(|&i| {
+                        let tile = &self.jigsaw.tiles[i];
+                        !tile.in_tray
+                            && !tile.locked
+                            && tile.grabbed_by.is_none()
+                            && !self.is_hidden(tile)
+                            && area.contains(tile.interpolated.get())
+                            && groups.insert(self.jigsaw.group(i))
+                    })
+                    .collect();
+                self.selection = selection;
+            }
+        }
+    }
+    fn touch(&mut self, touches: Vec<geng::TouchPoint>) {
+        match &touches[..] {
+            [p] => self.click(p.position),
+            [_, _] => {
+                // Second finger turns the gesture into panning, so drop whatever was grabbed
+                self.release();
+                self.hovered_tile = None;
+                self.touch = Some(touches);
+            }
+            _ => {}
+        }
+    }
+    fn update_touches(&mut self, touches: Vec<geng::TouchPoint>) {
+        match &touches[..] {
+            [p] => self.update_cursor(p.position),
+            [a, b] => {
+                if let Some([a0, b0]) = self.touch.as_deref() {
+                    let framebuffer_size = self.framebuffer_size.map(|x| x as f32);
+                    let center0 = ((a0.position + b0.position) / 2.0).map(|x| x as f32);
+                    let center = ((a.position + b.position) / 2.0).map(|x| x as f32);
+                    // Keep the world point between the fingers under them
+                    let anchor = self.camera.screen_to_world(framebuffer_size, center0);
+
+                    // Zoom
+                    let d0 = (b0.position - a0.position).len() as f32;
+                    let d = (b.position - a.position).len() as f32;
+                    if d0 > 0.0 && d > 0.0 {
+                        self.camera.fov = (self.camera.fov * d0 / d)
+                            .clamp(*self.fov_range.start(), *self.fov_range.end());
+                        if d0 != d {
+                            self.tutorial_progress(tutorial::TutorialStep::Zoom);
+                        }
+                    }
+
+                    // Move
+                    let moved = self.camera.screen_to_world(framebuffer_size, center);
+                    self.camera.center =
+                        (self.camera.center + anchor - moved).clamp_aabb(self.bounds);
+                    if center != center0 {
+                        self.tutorial_progress(tutorial::TutorialStep::Pan);
+                    }
+                }
+                self.touch = Some(touches);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.connection.send(ClientMessage::Leave);
+        self.connection.flush();
+    }
+}
+
+impl geng::State for Game {
+    fn transition(&mut self) -> Option<geng::Transition> {
+        self.transition.take()
+    }
+    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
+        use geng::ui::*;
+        self.name_typing = false;
+        let theme = self.settings.ui_theme;
+        if self.customize {
+            let save_button = button::Button::new(cx, theme, &tr("game.save"));
+            if save_button.was_clicked() {
+                self.customize = false;
+                self.settings.name = self.players.get(&self.id).unwrap().name.clone();
+                self.settings.save();
+                if let Some(color) = self.settings.color {
+                    self.connection.send(ClientMessage::UpdateColor(color));
+                }
+                self.players.get_mut(&self.id).unwrap().cursor = self.settings.cursor;
+                self.connection
+                    .send(ClientMessage::UpdateCursor(self.settings.cursor));
+                self.connection.send(ClientMessage::UpdateName(
+                    self.players.get(&self.id).unwrap().name.clone(),
+                ));
+            }
+            // Server allows anyone to scatter once the host has left
+            let can_scatter = self.host.map_or(true, |host| {
+                host == self.id || self.players.get(&host).is_none()
+            });
+            let scatter_button = button::Button::new(
+                cx,
+                theme,
+                &tr(if can_scatter {
+                    "game.scatter"
+                } else {
+                    "game.scatter_host_only"
+                }),
+            );
+            if scatter_button.was_clicked() && can_scatter {
+                self.connection.send(ClientMessage::Scatter);
+                self.customize = false;
+            }
+            let name_input =
+                TextInput::new(cx, &mut self.players.get_mut(&self.id).unwrap().name, 15);
+            self.name_typing = *name_input.capture;
+            let color_button = button::Button::new(cx, theme, &tr("game.change_color"));
+            if color_button.was_clicked() {
+                let player = self.players.get_mut(&self.id).unwrap();
+                let colors: Vec<Rgba<f32>> = player_colors().collect();
+                let index = colors.iter().position(|&color| color == player.color);
+                player.color = colors[index.map_or(0, |index| (index + 1) % colors.len())];
+                self.settings.color = Some(player.color);
+            }
+            let settings = self.settings.ui(&self.geng, cx);
+            self.music.set_volume(self.settings.audio.music_volume());
+            (
+                name_input.center(),
+                color_button.center(),
+                settings.center(),
+                scatter_button.center(),
+                save_button.center(),
+            )
+                .column()
+                .center()
+                .boxed()
+        } else if self.paused && self.confirm_leave {
+            let question = Text::new(
+                tr("game.leave_holding"),
+                self.geng.default_font().clone(),
+                cx.theme().text_size,
+                Rgba::WHITE,
+            );
+            let leave_button = button::Button::new(cx, theme, &tr("game.leave_confirm"));
+            if leave_button.was_clicked() {
+                self.leave();
+            }
+            let cancel_button = button::Button::new(cx, theme, &tr("game.cancel"));
+            if cancel_button.was_clicked() {
+                self.confirm_leave = false;
+            }
+            (
+                question.center(),
+                leave_button.center(),
+                cancel_button.center(),
+            )
+                .column()
+                .center()
+                .boxed()
+        } else if self.paused {
+            let resume_button = button::Button::new(cx, theme, &tr("game.resume"));
+            if resume_button.was_clicked() {
+                self.paused = false;
+            }
+            let settings_button = button::Button::new(cx, theme, &tr("game.settings"));
+            if settings_button.was_clicked() {
+                self.paused = false;
+                self.customize = true;
+            }
+            let copied = self
+                .invite_copied
+                .map_or(false, |time| self.time - time < 2.0);
+            let invite_button = button::Button::new(
                 cx,
-                if self.show_names {
-                    "Show names: YES"
+                theme,
+                &tr(if copied {
+                    "game.invite_copied"
                 } else {
-                    "Show names: NO"
-                },
+                    "game.copy_invite"
+                }),
             );
-            if show_names.was_clicked() {
-                self.show_names = !self.show_names;
+            if invite_button.was_clicked() {
+                self.copy_invite();
+            }
+            let leave_button = button::Button::new(cx, theme, &tr("game.leave"));
+            if leave_button.was_clicked() {
+                if self.players.get(&self.id).unwrap().tile_grabbed.is_some() {
+                    self.confirm_leave = true;
+                } else {
+                    self.leave();
+                }
             }
             (
-                name_input.center(),
-                show_names.center(),
-                save_button.center(),
+                resume_button.center(),
+                settings_button.center(),
+                invite_button.center(),
+                leave_button.center(),
             )
                 .column()
                 .center()
                 .boxed()
         } else {
-            let customize_button = Button::new(cx, "customize");
+            let customize_button = button::Button::new(cx, theme, &tr("game.customize"));
             if customize_button.was_clicked() {
                 self.customize = true;
             }
-            (customize_button.align(vec2(0.0, 1.0)),).stack().boxed()
+            let gather_button = button::Button::new(cx, theme, &tr("game.gather"));
+            if gather_button.was_clicked() {
+                self.gather();
+            }
+            let screenshot_button = button::Button::new(cx, theme, &tr("game.screenshot"));
+            if screenshot_button.was_clicked() {
+                self.screenshot();
+            }
+            let copied = self
+                .invite_copied
+                .map_or(false, |time| self.time - time < 2.0);
+            let invite_button = button::Button::new(
+                cx,
+                theme,
+                &if copied {
+                    tr("game.invite_copied")
+                } else {
+                    tr_with("game.invite", &[("room", &self.room)])
+                },
+            );
+            if invite_button.was_clicked() {
+                self.copy_invite();
+            }
+            let mut buttons = vec![
+                customize_button.boxed(),
+                gather_button.boxed(),
+                screenshot_button.boxed(),
+                invite_button.boxed(),
+            ];
+            if self.tutorial.is_some() {
+                let skip_button = button::Button::new(cx, theme, &tr("game.skip_tutorial"));
+                if skip_button.was_clicked() {
+                    self.skip_tutorial();
+                }
+                buttons.push(skip_button.boxed());
+            }
+            (row(buttons).align(vec2(0.0, 1.0)),).stack().boxed()
         }
     }
     fn update(&mut self, delta_time: f64) {
@@ -448,14 +2551,79 @@ impl geng::State for Game {
             self.finish_time = Some(self.time);
         }
 
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        if let Some(assets) = self.assets_watcher.update(delta_time) {
+            translations::set_translations(assets.translations.clone());
+            self.assets = Rc::new(assets);
+            self.image_levels = image_levels::ImageLevels::new(&self.geng, self.image());
+        }
+
         self.handle_connection();
+        self.update_reconnect();
+        self.report_viewport();
+        self.update_celebration(delta_time);
+        self.update_physics(delta_time);
+
+        if self.completed.is_none() {
+            self.record_timelapse(delta_time);
+        } else if !self.timelapse.frames.is_empty() {
+            let since = self.time - self.completed.as_ref().unwrap().0 - VICTORY_DELAY;
+            if since > 0.0 {
+                if !self.timelapse.playing {
+                    // Everything has settled by now
+                    let frame = self.timelapse_frame();
+                    self.timelapse.frames.push(frame);
+                    self.timelapse.playing = true;
+                }
+                self.play_timelapse(since / TIMELAPSE_DURATION);
+            }
+        }
+
+        if let Some((received, stats)) = &self.completed {
+            let timelapse = if self.timelapse.frames.is_empty() {
+                0.0
+            } else {
+                TIMELAPSE_DURATION + 1.0
+            };
+            if self.transition.is_none() && self.time - received > VICTORY_DELAY + timelapse {
+                let image = match &self.custom_image {
+                    Some(texture) => victory::ImageTexture::Custom(texture.clone()),
+                    None => match self.room_config.image {
+                        ImageSource::Builtin(index) => {
+                            victory::ImageTexture::Builtin(self.assets.clone(), index)
+                        }
+                        _ => unreachable!(),
+                    },
+                };
+                self.transition = Some(geng::Transition::Switch(Box::new(victory::Victory::new(
+                    &self.geng,
+                    &self.addr,
+                    self.client_config.clone(),
+                    image,
+                    stats.clone(),
+                ))));
+            }
+        }
+
+        let time = self.time;
+        self.feed
+            .retain(|&(added, _)| time - added < FEED_DURATION + FEED_FADE_TIME);
+        if let Some((added, _)) = self.banner {
+            if time - added > BANNER_DURATION + FEED_FADE_TIME {
+                self.banner = None;
+            }
+        }
+        self.pings
+            .retain(|&(added, _, _)| time - added < PING_DURATION);
 
         if std::mem::take(&mut self.play_connect_sound) {
-            self.assets.sounds.connect_piece.play();
+            self.play_variant(&self.assets.sounds.connect_piece);
         }
 
         let mut moves = Vec::new();
+        let smoothing = self.settings.network.smoothing;
         for player in &mut self.players {
+            player.interpolation.set_time(smoothing);
             player.interpolation.update(delta_time);
 
             // Update grabbed tile
@@ -468,6 +2636,15 @@ impl geng::State for Game {
                     }
                 }
             }
+            let tiles = &self.jigsaw.tiles;
+            player.also_grabbed.retain(|&(tile, _)| {
+                tiles
+                    .get(tile)
+                    .map_or(false, |tile| tile.grabbed_by == Some(player.id))
+            });
+            for &(tile, offset) in &player.also_grabbed {
+                moves.push((tile, player.interpolation.get() + offset));
+            }
         }
         for (tile, pos) in moves {
             self.move_tile(tile, pos, None, true);
@@ -477,9 +2654,13 @@ impl geng::State for Game {
         // self.intro_time -= delta_time;
         // } else {
         for tile in &mut self.jigsaw.tiles {
+            tile.interpolated.set_time(smoothing);
             tile.interpolated.update(delta_time);
         }
         // }
+        self.jigsaw.update_spatial_index();
+
+        self.connection.flush();
     }
     fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
         self.framebuffer_size = framebuffer.size();
@@ -496,11 +2677,15 @@ impl geng::State for Game {
                 .set_cursor_type(geng::CursorType::Default);
             return;
         } else {
+            let mouse_pos = self.geng.window().mouse_pos();
             self.geng.window().set_cursor_type(
-                if self.bounds.contains(self.camera.screen_to_world(
-                    self.framebuffer_size.map(|x| x as f32),
-                    self.geng.window().mouse_pos().map(|x| x as f32),
-                )) {
+                if !self.paused
+                    && !self.over_tray(mouse_pos)
+                    && self.bounds.contains(self.camera.screen_to_world(
+                        self.framebuffer_size.map(|x| x as f32),
+                        mouse_pos.map(|x| x as f32),
+                    ))
+                {
                     geng::CursorType::None
                 } else {
                     geng::CursorType::Default
@@ -508,148 +2693,74 @@ impl geng::State for Game {
             );
         }
 
-        let texture = &self.assets.sprites.table;
-        let size = texture.size().map(|x| x as f32);
-        let ratio = (self.bounds.height() / size.y).max(self.bounds.width() / size.x) * 1.5;
-        let size = size * ratio;
-        self.geng.draw_2d(
-            framebuffer,
-            &self.camera,
-            &draw_2d::TexturedQuad::new(AABB::ZERO.extend_symmetric(size * 0.5), texture),
-        );
-        self.geng.draw_2d(
-            framebuffer,
-            &self.camera,
-            &draw_2d::Quad::new(self.bounds, Rgba::new(0.1, 0.1, 0.1, 0.1)),
-        );
-
-        ugli::clear(framebuffer, None, Some(1.0), None);
-
-        let mut tiles: Vec<_> = self.jigsaw.tiles.iter().enumerate().collect();
-        tiles.sort_by_key(|(_, tile)| r32(tile.last_interaction_time));
-        let mut grabbed_tiles = HashMap::new();
-        for (i, tile) in self.jigsaw.tiles.iter().enumerate() {
-            if tile.grabbed_by.is_some() {
-                for other in self.jigsaw.get_all_connected(i) {
-                    grabbed_tiles.insert(other, tile);
-                }
-            }
-        }
+        self.draw_board(framebuffer);
 
-        // Combine all meshes into 1
+        if let Some(Dragging {
+            target: DragTarget::Select { start },
+            ..
+        }) = self.dragging
         {
-            #[derive(ugli::Vertex)]
-            struct Vertex {
-                a_pos: Vec3<f32>,
-                a_uv: Vec2<f32>,
-            }
-            let mesh: Vec<Vertex> = tiles
-                .iter()
-                .enumerate()
-                .flat_map(|(depth_i, (i, tile))| {
-                    let mut matrix = tile.matrix();
-                    if let Some(connected_to) = grabbed_tiles.get(i) {
-                        let delta = (tile.puzzle_pos.map(|x| x as f32)
-                            - connected_to.puzzle_pos.map(|x| x as f32))
-                            * self.jigsaw.tile_size;
-                        matrix = connected_to.matrix()
-                            * Mat3::scale_uniform(1.05)
-                            * Mat3::translate(delta);
-                    }
-                    let depth = 1.0 - 2.0 * (depth_i as f32 + 0.5) / tiles.len() as f32;
-                    tile.mesh.iter().map(move |v| {
-                        let pos = matrix * v.a_pos.extend(1.0);
-                        let a_pos = (pos.xy() / pos.z).extend(depth);
-                        Vertex {
-                            a_pos,
-                            a_uv: v.a_uv,
-                        }
-                    })
-                })
-                .collect();
-            let mesh = ugli::VertexBuffer::new_dynamic(self.geng.ugli(), mesh);
-            ugli::draw(
+            let mut color = self.settings.highlight.color.color();
+            color.a = 0.2;
+            self.geng.draw_2d(
                 framebuffer,
-                &self.assets.shaders.jigsaw,
-                ugli::DrawMode::Triangles,
-                &mesh,
-                (
-                    ugli::uniforms! {
-                        u_model_matrix: Mat3::identity(),
-                        u_texture: &self.assets.images[self.room_config.image],
-                    },
-                    geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
-                ),
-                ugli::DrawParameters {
-                    // blend_mode: Some(ugli::BlendMode::default()),
-                    depth_func: Some(ugli::DepthFunc::Less),
-                    ..Default::default()
-                },
+                &self.camera,
+                &draw_2d::Quad::new(select_area(start, self.cursor_world), color),
             );
         }
 
-        let hovered = self
-            .hovered_tile
-            .map(|tile| self.jigsaw.get_all_connected(tile))
-            .unwrap_or_default();
-
-        for (depth_i, (i, tile)) in tiles.iter().enumerate() {
-            let mut matrix = tile.matrix();
-            if let Some(connected_to) = grabbed_tiles.get(i) {
-                let delta = (tile.puzzle_pos.map(|x| x as f32)
-                    - connected_to.puzzle_pos.map(|x| x as f32))
-                    * self.jigsaw.tile_size;
-                matrix = connected_to.matrix() * Mat3::scale_uniform(1.05) * Mat3::translate(delta);
-            }
-            let mut outline_color = if hovered.contains(i) {
-                Rgba::WHITE
-            } else {
-                Rgba::BLACK
-            };
-            outline_color.a = self
-                .finish_time
-                .map_or(1.0, |finish| 1.0 - (self.time - finish));
-            let depth = (1.0 - 2.0 * (depth_i as f32 + 0.5) / tiles.len() as f32).clamp_abs(1.0);
-            ugli::draw(
-                framebuffer,
-                &self.assets.shaders.outline,
-                ugli::DrawMode::LineLoop { line_width: 1.0 },
-                &tile.outline,
-                (
-                    ugli::uniforms! {
-                        u_model_matrix: matrix,
-                        u_color: outline_color,
-                        u_depth: depth,
-                    },
-                    geng::camera2d_uniforms(&self.camera, framebuffer.size().map(|x| x as f32)),
-                ),
-                ugli::DrawParameters {
-                    blend_mode: Some(ugli::BlendMode::default()),
-                    depth_func: Some(ugli::DepthFunc::LessOrEqual),
-                    ..Default::default()
-                },
-            );
+        self.draw_confetti(framebuffer);
+
+        for &(added, pos, color) in &self.pings {
+            // A couple of rings expanding from the spot one after another
+            let age = (self.time - added) / PING_DURATION;
+            for ring in [age, age - 0.25] {
+                if ring < 0.0 {
+                    continue;
+                }
+                let radius = self.camera.fov * (0.01 + ring * 0.06);
+                let width = self.camera.fov * 0.004;
+                self.geng.draw_2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw_2d::Ellipse::circle_with_cut(
+                        pos,
+                        radius - width,
+                        radius,
+                        Rgba {
+                            a: 1.0 - ring,
+                            ..color
+                        },
+                    ),
+                );
+            }
         }
 
         for player in &self.players {
             let size = self.camera.fov * 0.01;
+            // Own cursor follows the settings right away, before they are saved
+            let sprites = self.assets.cursor(if player.id == self.id {
+                self.settings.cursor
+            } else {
+                player.cursor
+            });
             let texture = if player.tile_grabbed.is_some()
                 || player.id == self.id && self.dragging.is_some()
             {
-                &self.assets.hand.grab
+                &sprites.grab
             } else if self.finish_time.is_some() {
-                &self.assets.hand.thumb
+                &sprites.thumb
             } else {
-                &self.assets.hand.regular
+                &sprites.regular
             };
             self.geng.draw_2d(
                 framebuffer,
                 &self.camera,
-                &draw_2d::TexturedQuad::unit(texture)
+                &draw_2d::TexturedQuad::unit_colored(texture, player.color)
                     .scale_uniform(size)
                     .translate(player.interpolation.get()),
             );
-            if self.show_names {
+            if self.settings.show_names {
                 self.geng.default_font().draw_with_outline(
                     framebuffer,
                     &self.camera,
@@ -663,6 +2774,302 @@ impl geng::State for Game {
                 );
             }
         }
+
+        if self.settings.show_feed {
+            let font_size = framebuffer.size().y as f32 * 0.03;
+            for (i, (added, text)) in self.feed.iter().rev().enumerate() {
+                let age = self.time - added;
+                let alpha = (1.0 - (age - FEED_DURATION) / FEED_FADE_TIME).clamp(0.0, 1.0);
+                self.geng.default_font().draw_with_outline(
+                    framebuffer,
+                    &geng::PixelPerfectCamera,
+                    text,
+                    vec2(font_size, font_size * (1.0 + i as f32 * 1.2)),
+                    geng::TextAlign::LEFT,
+                    font_size,
+                    Rgba::new(1.0, 1.0, 1.0, alpha),
+                    font_size * 0.05,
+                    Rgba::new(0.0, 0.0, 0.0, alpha),
+                );
+            }
+        }
+
+        {
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            let font_size = framebuffer_size.y * 0.04;
+            let time = match &self.completed {
+                Some((_, stats)) => stats.time,
+                None => (self.time - self.room_start_time) as f64,
+            };
+            self.geng.default_font().draw_with_outline(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &util::format_duration(time),
+                framebuffer_size - vec2(font_size, font_size * 1.5),
+                geng::TextAlign::RIGHT,
+                font_size,
+                Rgba::WHITE,
+                font_size * 0.05,
+                Rgba::BLACK,
+            );
+
+            if self.settings.show_players {
+                // Who is here, what they are doing and how many pieces they connected, under the timer
+                let top = framebuffer_size.y - font_size * 2.5;
+                let font_size = framebuffer_size.y * 0.025;
+                let mut players: Vec<&Player> = self.players.iter().collect();
+                players.sort_by(|a, b| b.pieces.cmp(&a.pieces));
+                self.geng.default_font().draw_with_outline(
+                    framebuffer,
+                    &geng::PixelPerfectCamera,
+                    &tr_with("players.online", &[("count", &players.len().to_string())]),
+                    vec2(framebuffer_size.x - font_size * 1.6, top),
+                    geng::TextAlign::RIGHT,
+                    font_size,
+                    Rgba::WHITE,
+                    font_size * 0.05,
+                    Rgba::BLACK,
+                );
+                for (i, player) in players.into_iter().enumerate() {
+                    let color = match self.presence(player) {
+                        Presence::Idle => Rgba {
+                            a: 0.5,
+                            ..player.color
+                        },
+                        Presence::Online | Presence::Holding => player.color,
+                    };
+                    self.geng.default_font().draw_with_outline(
+                        framebuffer,
+                        &geng::PixelPerfectCamera,
+                        &format!("{}: {}", self.list_name(player), player.pieces),
+                        vec2(
+                            framebuffer_size.x - font_size * 1.6,
+                            top - (i + 1) as f32 * font_size * 1.2,
+                        ),
+                        geng::TextAlign::RIGHT,
+                        font_size,
+                        color,
+                        font_size * 0.05,
+                        Rgba::BLACK,
+                    );
+                }
+            }
+        }
+
+        {
+            // Progress bar at the top
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            let height = framebuffer_size.y * 0.015;
+            let bar = AABB::point(vec2(framebuffer_size.x / 2.0, framebuffer_size.y - height))
+                .extend_symmetric(vec2(framebuffer_size.x * 0.15, 0.0))
+                .extend_down(height);
+            let progress = self.jigsaw.progress();
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(bar.extend_uniform(2.0), Rgba::new(0.0, 0.0, 0.0, 0.5)),
+            );
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(
+                    AABB {
+                        x_max: bar.x_min + bar.width() * progress,
+                        ..bar
+                    },
+                    Rgba::new(0.4, 0.8, 0.3, 1.0),
+                ),
+            );
+            self.geng.default_font().draw_with_outline(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &format!("{}%", (progress * 100.0).floor()),
+                vec2(bar.x_max + height, bar.y_min),
+                geng::TextAlign::LEFT,
+                height * 1.5,
+                Rgba::WHITE,
+                height * 0.1,
+                Rgba::BLACK,
+            );
+            if let Some(race) = &self.race {
+                // The other team's progress right below
+                let rival = (race.team + 1) % TEAMS.len();
+                let (name, color) = TEAMS[rival];
+                let bar = AABB {
+                    y_min: bar.y_min - height * 1.5,
+                    y_max: bar.y_max - height * 1.5,
+                    ..bar
+                };
+                self.geng.draw_2d(
+                    framebuffer,
+                    &geng::PixelPerfectCamera,
+                    &draw_2d::Quad::new(bar.extend_uniform(2.0), Rgba::new(0.0, 0.0, 0.0, 0.5)),
+                );
+                self.geng.draw_2d(
+                    framebuffer,
+                    &geng::PixelPerfectCamera,
+                    &draw_2d::Quad::new(
+                        AABB {
+                            x_max: bar.x_min + bar.width() * race.rival_progress,
+                            ..bar
+                        },
+                        color,
+                    ),
+                );
+                self.geng.default_font().draw_with_outline(
+                    framebuffer,
+                    &geng::PixelPerfectCamera,
+                    &format!("{} {}%", tr(name), (race.rival_progress * 100.0).floor()),
+                    vec2(bar.x_max + height, bar.y_min),
+                    geng::TextAlign::LEFT,
+                    height * 1.5,
+                    color,
+                    height * 0.1,
+                    Rgba::BLACK,
+                );
+            }
+        }
+
+        if let Some((added, text)) = &self.banner {
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            let font_size = framebuffer_size.y * 0.04;
+            let age = self.time - added;
+            let alpha = (1.0 - (age - BANNER_DURATION) / FEED_FADE_TIME).clamp(0.0, 1.0);
+            self.geng.default_font().draw_with_outline(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                text,
+                vec2(
+                    framebuffer_size.x / 2.0,
+                    framebuffer_size.y - font_size * 2.0,
+                ),
+                geng::TextAlign::CENTER,
+                font_size,
+                Rgba::new(1.0, 0.9, 0.5, alpha),
+                font_size * 0.05,
+                Rgba::new(0.0, 0.0, 0.0, alpha),
+            );
+        }
+
+        if let Some(tutorial) = &self.tutorial {
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            let font_size = framebuffer_size.y * 0.035;
+            let pos = vec2(
+                framebuffer_size.x / 2.0,
+                framebuffer_size.y - font_size * 4.0,
+            );
+            let text = tutorial.text(&self.settings.controls);
+            // Text width is not known without measuring, so the backdrop is generous
+            let half_width = text.chars().count() as f32 * font_size * 0.3 + font_size;
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(
+                    AABB::point(pos + vec2(0.0, font_size * 0.3))
+                        .extend_symmetric(vec2(half_width, font_size * 0.8)),
+                    Rgba::new(0.0, 0.0, 0.0, 0.6),
+                ),
+            );
+            self.geng.default_font().draw(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &text,
+                pos,
+                geng::TextAlign::CENTER,
+                font_size,
+                Rgba::WHITE,
+            );
+        }
+
+        if self.show_tray {
+            self.draw_tray(framebuffer);
+        }
+
+        if let Some(thumbnail) = self.thumbnail_area() {
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(thumbnail.extend_uniform(2.0), Rgba::BLACK),
+            );
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::TexturedQuad::colored(
+                    thumbnail,
+                    self.image_levels.get(
+                        self.image(),
+                        thumbnail.height() / self.image().size().y as f32,
+                    ),
+                    Rgba::new(1.0, 1.0, 1.0, 0.9),
+                ),
+            );
+        }
+
+        if self.show_reference {
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(
+                    AABB::ZERO.extend_positive(framebuffer_size),
+                    Rgba::new(0.0, 0.0, 0.0, 0.5),
+                ),
+            );
+            let image_size = self.image().size().map(|x| x as f32);
+            let max_size = framebuffer_size * 0.8;
+            let size = image_size * (max_size.x / image_size.x).min(max_size.y / image_size.y);
+            let reference = AABB::point(framebuffer_size / 2.0).extend_symmetric(size / 2.0);
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(reference.extend_uniform(2.0), Rgba::BLACK),
+            );
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::TexturedQuad::new(reference, self.image()),
+            );
+        }
+
+        if !self.name_typing && self.geng.window().is_key_pressed(geng::Key::Tab) {
+            self.draw_scoreboard(framebuffer);
+        }
+
+        if self.paused {
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(
+                    AABB::ZERO.extend_positive(framebuffer_size),
+                    Rgba::new(0.0, 0.0, 0.0, 0.5),
+                ),
+            );
+        }
+
+        if self.reconnect.is_some() {
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(
+                    AABB::ZERO.extend_positive(framebuffer_size),
+                    Rgba::new(0.0, 0.0, 0.0, 0.5),
+                ),
+            );
+            let font_size = framebuffer_size.y * 0.05;
+            self.geng.default_font().draw_with_outline(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &tr("game.reconnecting"),
+                framebuffer_size / 2.0,
+                geng::TextAlign::CENTER,
+                font_size,
+                Rgba::WHITE,
+                font_size * 0.05,
+                Rgba::BLACK,
+            );
+        }
     }
     fn handle_event(&mut self, event: geng::Event) {
         if self.name_typing {
@@ -681,42 +3088,121 @@ impl geng::State for Game {
             }
         }
         match event {
+            geng::Event::KeyDown {
+                key: geng::Key::Escape,
+            } => {
+                if self.customize {
+                    self.customize = false;
+                } else {
+                    self.paused = !self.paused;
+                    self.confirm_leave = false;
+                    self.stop_drag();
+                }
+            }
+            geng::Event::KeyDown {
+                key: geng::Key::F11,
+            } => {
+                self.settings.window.toggle_fullscreen(&self.geng);
+                self.settings.save();
+            }
+            // The board is out of reach behind the menu
+            _ if self.paused => {}
+            geng::Event::KeyDown { key: geng::Key::R } if !self.name_typing => {
+                self.rotate_grabbed(1);
+            }
+            geng::Event::KeyDown {
+                key: geng::Key::F12,
+            } => {
+                self.screenshot();
+            }
+            geng::Event::KeyDown { key: geng::Key::G } if !self.name_typing => {
+                self.gather();
+            }
+            geng::Event::KeyDown { key: geng::Key::C } if !self.name_typing => {
+                self.copy_invite();
+            }
+            geng::Event::KeyDown { key: geng::Key::P }
+                if !self.name_typing && !self.room_config.hard_mode =>
+            {
+                self.show_reference = !self.show_reference;
+            }
+            geng::Event::KeyDown { key: geng::Key::T } if !self.name_typing => {
+                self.show_tray = !self.show_tray;
+            }
+            geng::Event::KeyDown { key: geng::Key::E } if !self.name_typing => {
+                self.edges_only = !self.edges_only;
+                self.hovered_tile = None;
+            }
+            geng::Event::MouseDown { .. } if self.show_reference => {
+                self.show_reference = false;
+            }
+            geng::Event::MouseDown { position, .. } if self.over_thumbnail(position) => {
+                self.show_reference = true;
+            }
+            geng::Event::Wheel { delta } if self.over_thumbnail(self.cursor_pos) => {
+                let thumbnail = &mut self.settings.thumbnail;
+                thumbnail.size = (thumbnail.size + delta as f32 * 0.0002).clamp(
+                    *ThumbnailSettings::SIZE_RANGE.start(),
+                    *ThumbnailSettings::SIZE_RANGE.end(),
+                );
+                self.settings.save();
+            }
+            geng::Event::Wheel { delta } if self.over_tray(self.cursor_pos) => {
+                self.scroll_tray(-delta as f32 * 0.01);
+            }
+            // Zooms as usual in rooms without rotation
+            geng::Event::Wheel { delta }
+                if self.players.get(&self.id).unwrap().tile_grabbed.is_some()
+                    && self.room_config.rotation.step().is_some() =>
+            {
+                self.rotate_grabbed(if delta > 0.0 { 1 } else { 3 });
+            }
             geng::Event::Wheel { delta } => {
                 const SENSITIVITY: f32 = 0.02;
                 let cursor_pos = self.geng.window().mouse_pos().map(|x| x as f32);
                 let old_world_pos = self
                     .camera
                     .screen_to_world(self.framebuffer_size.map(|x| x as f32), cursor_pos);
-                self.camera.fov =
-                    (self.camera.fov - delta as f32 * SENSITIVITY).clamp(FOV_MIN, FOV_MAX);
+                self.camera.fov = (self.camera.fov - delta as f32 * SENSITIVITY)
+                    .clamp(*self.fov_range.start(), *self.fov_range.end());
                 let new_world_pos = self
                     .camera
                     .screen_to_world(self.framebuffer_size.map(|x| x as f32), cursor_pos);
-                self.camera.center += old_world_pos - new_world_pos;
+                // Keep the point under the cursor in place
+                self.camera.center =
+                    (self.camera.center + old_world_pos - new_world_pos).clamp_aabb(self.bounds);
+                self.tutorial_progress(tutorial::TutorialStep::Zoom);
             }
             geng::Event::MouseMove { position, .. } => {
                 self.update_cursor(position);
             }
-            geng::Event::MouseDown { position, button } => match button {
-                geng::MouseButton::Left => {
+            geng::Event::MouseDown { position, .. }
+                if self.geng.window().is_key_pressed(geng::Key::LAlt)
+                    || self.geng.window().is_key_pressed(geng::Key::RAlt) =>
+            {
+                self.ping(position);
+            }
+            geng::Event::MouseDown { position, button } => {
+                // Like in image editors, holding space turns the left button into a pan
+                let space_pan = button == geng::MouseButton::Left
+                    && !self.name_typing
+                    && self.geng.window().is_key_pressed(geng::Key::Space);
+                if button == self.settings.controls.grab.into() && !space_pan {
                     self.click(position);
+                } else if button == self.settings.controls.pan.into()
+                    || button == geng::MouseButton::Middle
+                    || space_pan
+                {
+                    self.start_pan(position);
                 }
-                geng::MouseButton::Right => {
-                    self.start_drag(Dragging {
-                        initial_screen_pos: position,
-                        target: DragTarget::Camera {
-                            initial_camera_pos: self.camera.center,
-                        },
-                    });
-                }
-                geng::MouseButton::Middle => {}
-            },
+            }
             geng::Event::MouseUp { .. } => {
                 self.release();
             }
             geng::Event::TouchStart { touches } => self.touch(touches),
             geng::Event::TouchMove { touches } => self.update_touches(touches),
             geng::Event::TouchEnd { .. } => {
+                self.touch = None;
                 self.release();
             }
             _ => (),
@@ -724,35 +3210,195 @@ impl geng::State for Game {
     }
 }
 
-pub fn run(geng: &Geng, addr: &str, room: &str, name: Option<String>) -> impl geng::State {
+pub fn run(
+    geng: &Geng,
+    addr: &str,
+    room: &str,
+    name: Option<String>,
+    password: Option<String>,
+    config: &ClientConfig,
+) -> impl geng::State {
+    let loading_screen = loading_screen::LoadingScreen::new(geng, config);
+    let status = loading_screen.status();
     let future = {
         let geng = geng.clone();
+        let config = config.clone();
+        let addr = addr.to_owned();
         let room = room.to_owned();
-        let connection = geng::net::client::connect(addr);
+        let connection = geng::net::client::connect(&addr);
         async move {
             let assets: Rc<Assets> = geng::LoadAsset::load(&geng, &run_dir().join("assets"))
                 .await
                 .expect("Failed to load assets");
+            translations::init(&assets, &config);
+            status.borrow_mut().text = Some(tr_with("loading.connecting", &[("room", &room)]));
             let mut connection: game::Connection = connection.await;
-            connection.send(ClientMessage::SelectRoom(room));
-            match connection.next().await {
-                Some(ServerMessage::SetupId {
+            let name = name.unwrap_or_else(|| Settings::load(&config).name);
+            connection.send(ClientMessage::Handshake {
+                compression: true,
+                name: name.clone(),
+                token: Some(player_token()),
+            });
+            connection.send(ClientMessage::SelectRoom {
+                room: room.clone(),
+                password: password.clone(),
+            });
+            let mut messages = Vec::new();
+            loop {
+                // Latency probes and announcements may come before the answer to joining
+                if let Some(index) = messages.iter().position(|message| {
+                    matches!(
+                        message,
+                        ServerMessage::SetupId { .. }
+                            | ServerMessage::RoomNotFound
+                            | ServerMessage::RoomFull
+                            | ServerMessage::WrongPassword
+                    )
+                }) {
+                    messages.drain(..index);
+                    break;
+                }
+                messages = match connection.next().await.map(ServerMessage::unpack) {
+                    Some(Ok(messages)) => messages,
+                    Some(Err(e)) => {
+                        error!("Failed to unpack message from server: {}", e);
+                        return Box::new(message_screen::MessageScreen::new(
+                            &geng,
+                            &addr,
+                            config,
+                            &tr("message.bad_message"),
+                        )) as Box<dyn geng::State>;
+                    }
+                    None => panic!("Disconnected from server"),
+                };
+            }
+            match messages.remove(0) {
+                ServerMessage::SetupId {
                     player_id,
                     room_config,
                     tiles,
-                }) => game::Game::new(
+                } => {
+                    let mut settings = Settings::load(&config);
+                    settings.last_room = Some(LastRoom {
+                        addr: addr.clone(),
+                        room: room.clone(),
+                    });
+                    settings.save();
+                    if let ImageSource::Builtin(index) = room_config.image {
+                        status.borrow_mut().image =
+                            Some(victory::ImageTexture::Builtin(assets.clone(), index));
+                    } else {
+                        status.borrow_mut().text = Some(tr("loading.image"));
+                    }
+                    let custom_image = match &room_config.image {
+                        ImageSource::Builtin(_) => Ok(None),
+                        ImageSource::Url(url) => util::fetch_bytes(url)
+                            .await
+                            .and_then(|data| {
+                                assets::texture_from_bytes(
+                                    geng.ugli(),
+                                    &data,
+                                    config.max_texture_size(),
+                                )
+                            })
+                            .map(Some),
+                        ImageSource::Uploaded(_) => {
+                            let data = loop {
+                                if let Some(index) = messages.iter().position(|message| {
+                                    matches!(message, ServerMessage::ImageData(..))
+                                }) {
+                                    match messages.remove(index) {
+                                        ServerMessage::ImageData(data) => break Ok(data),
+                                        _ => unreachable!(),
+                                    }
+                                }
+                                match connection.next().await.map(ServerMessage::unpack) {
+                                    Some(Ok(unpacked)) => messages.extend(unpacked),
+                                    Some(Err(e)) => break Err(e.to_string()),
+                                    None => panic!("Disconnected from server"),
+                                }
+                            };
+                            data.and_then(|data| {
+                                assets::texture_from_bytes(
+                                    geng.ugli(),
+                                    &data,
+                                    config.max_texture_size(),
+                                )
+                            })
+                            .map(Some)
+                        }
+                    };
+                    // A dead link or something that is not an image, nothing to play with
+                    let custom_image = match custom_image {
+                        Ok(image) => image.map(Rc::new),
+                        Err(e) => {
+                            warn!("Failed to load room image: {}", e);
+                            return Box::new(message_screen::MessageScreen::new(
+                                &geng,
+                                &addr,
+                                config,
+                                &tr("message.room_image_failed"),
+                            )) as Box<dyn geng::State>;
+                        }
+                    };
+                    if let Some(texture) = &custom_image {
+                        status.borrow_mut().image =
+                            Some(victory::ImageTexture::Custom(texture.clone()));
+                    }
+                    let mut game = game::Game::new(
+                        &geng,
+                        &addr,
+                        &room,
+                        password,
+                        &assets,
+                        player_id,
+                        Some(name),
+                        &config,
+                        room_config,
+                        custom_image,
+                        tiles,
+                        connection,
+                    );
+                    for message in messages {
+                        game.handle_message(message);
+                    }
+                    Box::new(game) as Box<dyn geng::State>
+                }
+                ServerMessage::RoomNotFound => {
+                    // Nothing to continue anymore
+                    let mut settings = Settings::load(&config);
+                    if settings
+                        .last_room
+                        .as_ref()
+                        .map_or(false, |last| last.addr == addr && last.room == room)
+                    {
+                        settings.last_room = None;
+                        settings.save();
+                    }
+                    Box::new(message_screen::MessageScreen::new(
+                        &geng,
+                        &addr,
+                        config,
+                        &tr("message.room_not_found"),
+                    ))
+                }
+                ServerMessage::RoomFull => Box::new(message_screen::MessageScreen::new(
                     &geng,
-                    &assets,
-                    player_id,
-                    name,
-                    room_config,
-                    tiles,
-                    connection,
-                ),
-                Some(ServerMessage::RoomNotFound) => panic!("Room not found"),
+                    &addr,
+                    config,
+                    &tr("message.room_full"),
+                )),
+                ServerMessage::WrongPassword => Box::new(password_prompt::PasswordPrompt::new(
+                    &geng,
+                    &addr,
+                    &room,
+                    Some(name),
+                    config,
+                    password.is_some(),
+                )),
                 _ => unreachable!(),
             }
         }
     };
-    geng::LoadingScreen::new(geng, geng::EmptyLoadingScreen, future, |state| state)
+    geng::LoadingScreen::new(geng, loading_screen, future, |state| state)
 }
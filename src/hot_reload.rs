@@ -0,0 +1,76 @@
+use super::*;
+
+/// Seconds between checks for changed files
+const CHECK_INTERVAL: f32 = 0.5;
+
+/// Watches the assets directory and loads everything again once a file changes,
+/// only built with the `hot-reload` feature
+pub struct AssetsWatcher {
+    geng: Geng,
+    path: std::path::PathBuf,
+    last_modified: Option<std::time::SystemTime>,
+    next_check: f32,
+    task: Option<util::Task<Result<Assets, String>>>,
+}
+
+impl AssetsWatcher {
+    pub fn new(geng: &Geng) -> Self {
+        let path = run_dir().join("assets");
+        Self {
+            geng: geng.clone(),
+            last_modified: last_modified(&path),
+            path,
+            next_check: CHECK_INTERVAL,
+            task: None,
+        }
+    }
+
+    /// Returns freshly loaded assets once they are ready
+    pub fn update(&mut self, delta_time: f32) -> Option<Assets> {
+        if let Some(task) = &mut self.task {
+            match task.poll()? {
+                Ok(assets) => {
+                    info!("Reloaded assets");
+                    self.task = None;
+                    return Some(assets);
+                }
+                Err(e) => {
+                    // Probably saved halfway, the next change tries again
+                    error!("Failed to reload assets: {}", e);
+                    self.task = None;
+                    return None;
+                }
+            }
+        }
+        self.next_check -= delta_time;
+        if self.next_check > 0.0 {
+            return None;
+        }
+        self.next_check = CHECK_INTERVAL;
+        let modified = last_modified(&self.path);
+        if modified != self.last_modified {
+            self.last_modified = modified;
+            let future = <Assets as geng::LoadAsset>::load(&self.geng, &self.path);
+            self.task = Some(util::Task::new(async move {
+                future.await.map_err(|e| e.to_string())
+            }));
+        }
+        None
+    }
+}
+
+/// Latest modification time of any file in the directory
+fn last_modified(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() {
+                last_modified(&entry.path())
+            } else {
+                metadata.modified().ok()
+            }
+        })
+        .max()
+}
@@ -0,0 +1,66 @@
+use super::*;
+
+/// Levels are not made smaller than this many pixels on the shorter side
+const MIN_LEVEL_SIZE: usize = 32;
+
+/// Copies of the puzzle image, each half the size of the previous one, like mipmaps.
+///
+/// WebGL can only generate mipmaps for power of two sizes, which photos rarely have,
+/// so zoomed out tiles are drawn from the level closest to their size on the screen
+/// to avoid shimmering.
+pub struct ImageLevels {
+    /// Halved copies, the original image is not included
+    levels: Vec<ugli::Texture>,
+}
+
+impl ImageLevels {
+    pub fn new(geng: &Geng, image: &ugli::Texture) -> Self {
+        let mut levels: Vec<ugli::Texture> = Vec::new();
+        loop {
+            let source = levels.last().unwrap_or(image);
+            let size = source.size();
+            if size.x.min(size.y) / 2 < MIN_LEVEL_SIZE {
+                break;
+            }
+            let level = halve(geng, source);
+            levels.push(level);
+        }
+        Self { levels }
+    }
+
+    /// Level to draw the image from when a texel of the original
+    /// takes `pixels_per_texel` pixels on the screen
+    pub fn get<'a>(&'a self, image: &'a ugli::Texture, pixels_per_texel: f32) -> &'a ugli::Texture {
+        if pixels_per_texel >= 1.0 || !pixels_per_texel.is_finite() {
+            return image;
+        }
+        // Each level takes twice as many pixels per texel as the previous one
+        let level = ((1.0 / pixels_per_texel).log2().round() as usize).min(self.levels.len());
+        match level {
+            0 => image,
+            level => &self.levels[level - 1],
+        }
+    }
+}
+
+/// Draws the texture into one half its size, linear filtering averages every 2x2 texels
+fn halve(geng: &Geng, texture: &ugli::Texture) -> ugli::Texture {
+    let size = texture.size().map(|x| (x / 2).max(1));
+    let mut result = ugli::Texture::new_uninitialized(geng.ugli(), size);
+    {
+        let framebuffer = &mut ugli::Framebuffer::new_color(
+            geng.ugli(),
+            ugli::ColorAttachment::Texture(&mut result),
+        );
+        ugli::clear(framebuffer, Some(Rgba::new(0.0, 0.0, 0.0, 0.0)), None, None);
+        geng.draw_2d(
+            framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::TexturedQuad::new(
+                AABB::point(Vec2::ZERO).extend_positive(size.map(|x| x as f32)),
+                texture,
+            ),
+        );
+    }
+    result
+}
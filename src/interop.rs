@@ -3,6 +3,31 @@ use super::*;
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Id(pub u64);
 
+/// Fixed point position used in frequent messages
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompactPos(i16, i16);
+
+impl CompactPos {
+    const SCALE: f32 = 100.0;
+}
+
+impl From<Vec2<f32>> for CompactPos {
+    fn from(pos: Vec2<f32>) -> Self {
+        let pos = pos.map(|x| {
+            (x * Self::SCALE)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32)
+        });
+        Self(pos.x as i16, pos.y as i16)
+    }
+}
+
+impl From<CompactPos> for Vec2<f32> {
+    fn from(pos: CompactPos) -> Self {
+        vec2(pos.0, pos.1).map(|x| x as f32 / CompactPos::SCALE)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerMessage {
     SetupId {
@@ -11,39 +36,565 @@ pub enum ServerMessage {
         tiles: Vec<TileState>,
     },
     RoomNotFound,
+    /// Room requires a password that was missing or didn't match
+    WrongPassword,
+    /// Room already has the maximum number of players
+    RoomFull,
+    /// Public rooms that can be joined
+    RoomList(Vec<RoomInfo>),
     RoomCreated(String),
+    /// Room to join picked for [ClientMessage::QuickPlay]
+    QuickPlayRoom(String),
+    RoomRejected(String),
+    /// Id to refer to the uploaded image in [RoomConfig]
+    ImageUploaded(u64),
+    /// Contents of the room's uploaded image, sent right after [ServerMessage::SetupId]
+    ImageData(Vec<u8>),
+    /// Another player is in the room, sent for everyone already there on join
+    /// and to the others once someone joins
+    PlayerJoined {
+        id: Id,
+        name: String,
+        color: Rgba<f32>,
+        cursor: CursorSkin,
+    },
     PlayerDisconnected(Id),
-    UpdatePos(Id, Vec2<f32>),
+    UpdatePos(Id, CompactPos),
     UpdatePlayerName(Id, String),
+    UpdatePlayerColor(Id, Rgba<f32>),
+    UpdatePlayerCursor(Id, CursorSkin),
+    /// Everyone in the room, sent every now and then
+    PlayerStats(Vec<PlayerStatus>),
+    /// Has to be answered with [ClientMessage::LatencyReply] right away to measure the ping
+    LatencyProbe,
+    /// Team the player races for, sent on join to race rooms
+    RaceTeam(usize),
+    /// How far the other team got, as a fraction of the largest group
+    RivalProgress(f32),
+    /// The given team finished first
+    RaceWon(usize),
+    /// Another player pointed to a spot on the board
+    Ping {
+        player: Id,
+        pos: Vec2<f32>,
+    },
     TileGrabbed {
         player: Id,
         tile: usize,
         offset: Vec2<f32>,
     },
-    TileReleased {
+    /// Several groups were grabbed at once, see [ClientMessage::GrabTiles]
+    TilesGrabbed {
         player: Id,
-        tile: usize,
-        pos: Vec2<f32>,
+        tiles: Vec<(usize, Vec2<f32>)>,
     },
-    ConnectTiles(usize, usize),
+    /// Somebody else got to the tile first, the grab has to be undone
+    GrabDenied(usize),
+    /// Everything changed in the room since the last tick, releases, connections,
+    /// rotations and tray moves only reach other players through here
+    Updates {
+        /// Latest cursor positions of players that moved
+        positions: Vec<(Id, CompactPos)>,
+        tiles: Vec<(usize, TileState)>,
+    },
+    /// Full state of all tiles
+    Keyframe(Vec<TileState>),
+    Activity(Activity),
+    /// Server-wide message shown as a banner
+    Announcement(String),
+    /// Connection is cut off for the given reason, the client should not reconnect
+    Kicked(String),
+    /// Seconds since the room was created, sent on join
+    Elapsed(f64),
+    /// Player who created the room, sent on join
+    RoomHost(Option<Id>),
+    /// All tiles got connected
+    PuzzleCompleted(RoomStats),
+    /// Another message in the compact encoding, see [pack]
+    Packed(Vec<u8>),
+    /// Deflated [ServerMessage::Packed]
+    Compressed(Vec<u8>),
+    /// All messages sent during a single tick
+    Batch(Vec<ServerMessage>),
+}
+
+/// Messages smaller than this are not worth compressing
+const COMPRESSION_THRESHOLD: usize = 256;
+/// Largest packed message accepted, so that a bogus length can not exhaust memory
+const MAX_PACKED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Encodes the message with variable length integers, most ids,
+/// indices and lengths take a single byte instead of eight
+pub fn pack<T: Serialize>(message: &T) -> Vec<u8> {
+    use bincode::Options as _;
+    bincode::DefaultOptions::new()
+        .serialize(message)
+        .expect("Failed to serialize message")
+}
+
+/// Reverse of [pack]
+pub fn unpack<T: for<'de> Deserialize<'de>>(data: impl std::io::Read) -> bincode::Result<T> {
+    use bincode::Options as _;
+    bincode::DefaultOptions::new()
+        .with_limit(MAX_PACKED_SIZE)
+        .deserialize_from(data)
+}
+
+impl ServerMessage {
+    /// Packs the message and compresses it if it is big enough
+    pub fn pack(self) -> Self {
+        let data = pack(&self);
+        if data.len() < COMPRESSION_THRESHOLD {
+            return Self::Packed(data);
+        }
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut encoder, &data).unwrap();
+        Self::Compressed(encoder.finish().unwrap())
+    }
+
+    /// Reverse of [Self::pack], fails on data that did not come from it
+    pub fn decompress(self) -> bincode::Result<Self> {
+        match self {
+            Self::Packed(data) => unpack(data.as_slice()),
+            Self::Compressed(data) => {
+                let decoder = flate2::read::DeflateDecoder::new(data.as_slice());
+                unpack(decoder)
+            }
+            message => Ok(message),
+        }
+    }
+
+    /// Decompresses and flattens batches into a list of plain messages
+    pub fn unpack(self) -> bincode::Result<Vec<Self>> {
+        match self.decompress()? {
+            Self::Batch(messages) => {
+                let mut result = Vec::new();
+                for message in messages {
+                    result.extend(message.unpack()?);
+                }
+                Ok(result)
+            }
+            message => Ok(vec![message]),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClientMessage {
+    /// All messages sent during a single frame
+    Batch(#[serde(deserialize_with = "deserialize_batch")] Vec<ClientMessage>),
+    /// Another message in the compact encoding, see [pack]
+    Packed(Vec<u8>),
+    /// Sent before anything else to negotiate connection features
+    Handshake {
+        /// Whether the client understands [ServerMessage::Packed] and [ServerMessage::Compressed]
+        compression: bool,
+        name: String,
+        /// Random string the client keeps between sessions to be recognized when it comes back
+        token: Option<String>,
+    },
+    /// Sent after reconnecting, before selecting the room again, to keep the player's progress,
+    /// only honoured if the id was had by the player with the same token
+    Resume(Id),
     UpdateName(String),
+    UpdateColor(Rgba<f32>),
+    UpdateCursor(CursorSkin),
+    /// Png or jpeg image to use in a room created later
+    UploadImage(Vec<u8>),
     CreateRoom(RoomConfig),
-    SelectRoom(String),
-    UpdatePos(Vec2<f32>),
-    GrabTile { tile: usize, offset: Vec2<f32> },
+    /// Asks for a random public room in progress, one with the given config
+    /// is created when there is none, answered with [ServerMessage::QuickPlayRoom]
+    QuickPlay(RoomConfig),
+    ListRooms,
+    SelectRoom {
+        room: String,
+        password: Option<String>,
+    },
+    UpdatePos(CompactPos),
+    /// Bottom left and top right corners of the area the player sees,
+    /// updates outside of it are delayed until it comes into view
+    UpdateViewport(CompactPos, CompactPos),
+    GrabTile {
+        tile: usize,
+        offset: Vec2<f32>,
+        /// Room time of the board the player saw when grabbing,
+        /// the earlier of two grabs wins even if it arrives later
+        time: f64,
+    },
+    /// Grab several groups at once, by one tile of each with its offset from the cursor
+    GrabTiles(Vec<(usize, Vec2<f32>)>),
     ReleaseTile(Vec<(usize, Vec2<f32>)>),
     ConnectTiles(usize, usize),
+    /// Rotate the group of a grabbed tile
+    RotateTile {
+        tile: usize,
+        rotation: u8,
+    },
+    /// Move a single unconnected tile into the tray, or take it out onto the board
+    SetInTray {
+        tile: usize,
+        in_tray: bool,
+    },
+    /// Spread all loose tiles over the spawn area again, only allowed for the host
+    Scatter,
+    /// Group of the tile was released at its spot inside the frame
+    LockGroup(usize),
+    /// Move several loose tiles at once without grabbing them
+    MoveTiles(Vec<(usize, Vec2<f32>)>),
+    /// Point others to a spot on the board
+    Ping(Vec2<f32>),
+    /// Answer to [ServerMessage::LatencyProbe]
+    LatencyReply,
+    /// Sent before closing the connection, so that others see the player gone right away
+    Leave,
+}
+
+/// Batches are never nested by the client, deeper ones are refused while decoding
+/// so that a small message can not recurse the decoder until the stack overflows
+const MAX_BATCH_DEPTH: usize = 1;
+
+thread_local! {
+    /// Batches being decoded on this thread, counted by [deserialize_batch]
+    static BATCH_DEPTH: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+fn deserialize_batch<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<ClientMessage>, D::Error> {
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            BATCH_DEPTH.with(|depth| depth.set(depth.get() - 1));
+        }
+    }
+    let depth = BATCH_DEPTH.with(|depth| {
+        depth.set(depth.get() + 1);
+        depth.get()
+    });
+    let _guard = Guard;
+    if depth > MAX_BATCH_DEPTH {
+        return Err(serde::de::Error::custom("batches are nested too deep"));
+    }
+    Vec::deserialize(deserializer)
+}
+
+impl ClientMessage {
+    /// Messages in a batch or a packed message, which may hold a single batch,
+    /// fails if anything is nested deeper than that
+    pub fn unpack(self) -> Result<Vec<Self>, String> {
+        let message = match self {
+            Self::Packed(data) => unpack(data.as_slice()).map_err(|e| e.to_string())?,
+            message => message,
+        };
+        let messages = match message {
+            Self::Batch(messages) => messages,
+            message => vec![message],
+        };
+        if messages
+            .iter()
+            .any(|message| matches!(message, Self::Batch(..) | Self::Packed(..)))
+        {
+            return Err("Nested batch or packed message".to_owned());
+        }
+        Ok(messages)
+    }
+}
+
+/// Notable events shown in the activity feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Activity {
+    PlayerJoined { name: String },
+    PlayerLeft { name: String },
+    PiecesConnected { name: String, pieces: usize },
+    LargestGroup(usize),
+}
+
+const PLAYER_COLORS: [&str; 8] = [
+    "#e6194b", "#3cb44b", "#ffe119", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6",
+];
+
+/// Colors players can have, server assigns them in order
+pub fn player_colors() -> impl Iterator<Item = Rgba<f32>> {
+    PLAYER_COLORS
+        .iter()
+        .map(|hex| Rgba::try_from(*hex).unwrap())
+}
+
+/// Live numbers of a player in the room, shown on the scoreboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerStatus {
+    pub id: Id,
+    /// Number of connections made by this player, see [PlayerStats::pieces]
+    pub pieces: usize,
+    /// Round trip time in milliseconds, unknown until measured
+    pub ping: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub name: String,
+    /// Number of connections made by this player
+    pub pieces: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomStats {
+    /// Seconds it took to complete the puzzle
+    pub time: f64,
+    /// Everyone who joined the room, most active first
+    pub players: Vec<PlayerStats>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoomConfig {
     pub seed: u64,
     pub size: Vec2<usize>,
-    pub image: usize,
+    pub image: ImageSource,
+    /// Orientations pieces start in and can be turned to
+    #[serde(default)]
+    pub rotation: Rotation,
+    /// Whether the room is shown in the room browser
+    #[serde(default)]
+    pub public: bool,
+    #[serde(default)]
+    pub edge_style: EdgeStyle,
+    #[serde(default)]
+    pub tiling: Tiling,
+    /// Password required to join, never sent to clients
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Maximum number of players in the room at once
+    #[serde(default)]
+    pub max_players: Option<usize>,
+    /// Whether groups put at their spot inside the frame get locked in place
+    #[serde(default)]
+    pub lock_placed: bool,
+    /// How close pieces snap together, as a fraction of the piece size
+    #[serde(default = "RoomConfig::default_snap_distance")]
+    pub snap_distance: f32,
+    /// Two teams race on their own copies of the puzzle, see [RoomConfig::team_room]
+    #[serde(default)]
+    pub race: bool,
+    /// Released groups keep sliding and bounce off the edges and each other
+    #[serde(default)]
+    pub physics: bool,
+    /// No hover highlight, reference image or placement hints, and a smaller snap distance
+    #[serde(default)]
+    pub hard_mode: bool,
+    /// Name asked for by the creator instead of a generated code,
+    /// cleaned up with [RoomConfig::sanitize_name] and made unique by the server
+    #[serde(default)]
+    pub custom_name: Option<String>,
+    /// Width of the image divided by its height, sent by the creator since the server
+    /// never decodes the image, unknown for rooms created before it was sent
+    #[serde(default)]
+    pub image_aspect: Option<f32>,
+}
+
+/// Height of the assembled puzzle in world units, the width follows the image
+pub const PUZZLE_HEIGHT: f32 = 5.0;
+
+impl RoomConfig {
+    pub const TEAMS: usize = 2;
+    /// Widest (or, inverted, tallest) image aspect ratio a room can have
+    pub const MAX_IMAGE_ASPECT: f32 = 10.0;
+    /// Number of images bundled with the game, same as the range of [Assets::images]
+    pub const BUILTIN_IMAGES: usize = 3;
+    /// Images with more pixels are rejected without decoding them,
+    /// they would take gigabytes of memory
+    pub const MAX_IMAGE_PIXELS: u64 = 64 * 1024 * 1024;
+
+    /// Name of the room holding the board of the given team in a race
+    pub fn team_room(code: &str, team: usize) -> String {
+        format!("{}-{}", code, team + 1)
+    }
+    /// Code shared by both teams and the team, if the room is a team board
+    pub fn race_team(room: &str) -> Option<(&str, usize)> {
+        let (code, team) = room.rsplit_once('-')?;
+        let team = team.parse::<usize>().ok()?.checked_sub(1)?;
+        (team < Self::TEAMS).then_some((code, team))
+    }
+
+    pub const MAX_NAME_LEN: usize = 32;
+
+    /// Room name safe for urls and file names: ascii letters and digits with single dashes between words
+    pub fn sanitize_name(name: &str) -> String {
+        let mut result = String::new();
+        for c in name.chars() {
+            if result.len() >= Self::MAX_NAME_LEN {
+                break;
+            }
+            if c.is_ascii_alphanumeric() {
+                result.push(c);
+            } else if !result.is_empty() && !result.ends_with('-') {
+                result.push('-');
+            }
+        }
+        result.trim_end_matches('-').to_owned()
+    }
+    /// Whether the name of an existing room could have been made by the server,
+    /// names coming from clients are checked with this before touching the disk
+    pub fn is_valid_name(name: &str) -> bool {
+        // Room for the number suffix and the team of a race
+        !name.is_empty()
+            && name.len() <= Self::MAX_NAME_LEN * 2
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    }
+
+    pub const SNAP_DISTANCE_RANGE: RangeInclusive<f32> = 0.05..=0.5;
+
+    pub fn default_snap_distance() -> f32 {
+        0.2
+    }
+    /// Snap distance in effect, shrunk in hard mode
+    pub fn effective_snap_distance(&self) -> f32 {
+        if self.hard_mode {
+            self.snap_distance * 0.5
+        } else {
+            self.snap_distance
+        }
+    }
+    /// Number of tiles the puzzle is cut into
+    pub fn tile_count(&self) -> usize {
+        match self.tiling {
+            Tiling::Grid => self.size.x * self.size.y,
+            // Odd rows have halves at both ends
+            Tiling::Hexagonal => self.size.x * self.size.y + self.size.y / 2,
+        }
+    }
+}
+
+/// How the image is divided into tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tiling {
+    Grid,
+    Hexagonal,
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Self::Grid
+    }
+}
+
+/// Orientations pieces can be turned to, they start in a random one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Rotation {
+    /// Pieces always stay upright
+    Off,
+    /// Upright or upside down
+    Half,
+    /// Any quarter turn
+    Full,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+impl Rotation {
+    /// Quarter turns between allowed orientations, `None` if pieces can not be turned
+    pub fn step(self) -> Option<u8> {
+        match self {
+            Self::Off => None,
+            Self::Half => Some(2),
+            Self::Full => Some(1),
+        }
+    }
+    /// Whether pieces may be turned given number of quarter turns
+    pub fn allows(self, rotation: u8) -> bool {
+        rotation % self.step().unwrap_or(4) == 0
+    }
+}
+
+impl<'de> Deserialize<'de> for Rotation {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        enum Repr {
+            Off,
+            Half,
+            Full,
+        }
+        /// Rooms saved before there were several options only had an on/off switch
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Saved {
+            Enabled(bool),
+            Repr(Repr),
+        }
+        let repr = if deserializer.is_human_readable() {
+            match Saved::deserialize(deserializer)? {
+                Saved::Enabled(false) => Repr::Off,
+                Saved::Enabled(true) => Repr::Full,
+                Saved::Repr(repr) => repr,
+            }
+        } else {
+            Repr::deserialize(deserializer)?
+        };
+        Ok(match repr {
+            Repr::Off => Self::Off,
+            Repr::Half => Self::Half,
+            Repr::Full => Self::Full,
+        })
+    }
+}
+
+/// Shape of the tabs connecting neighbouring pieces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EdgeStyle {
+    /// Round knobs
+    Classic,
+    /// Trapezoid tabs
+    Angular,
+    /// No tabs at all
+    Straight,
+}
+
+impl Default for EdgeStyle {
+    fn default() -> Self {
+        Self::Classic
+    }
+}
+
+/// Sprites used for the cursor, everyone sees the one picked by the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CursorSkin {
+    Hand,
+    Glove,
+    /// Combine harvester, fitting for the harvest theme
+    Harvester,
+}
+
+impl Default for CursorSkin {
+    fn default() -> Self {
+        Self::Hand
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub name: String,
+    pub image: ImageSource,
+    pub pieces: usize,
+    pub players: usize,
+    /// Whether joining requires a password
+    pub locked: bool,
+    pub max_players: Option<usize>,
+    /// Whether [Self::name] was picked by the creator rather than generated
+    pub named: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageSource {
+    /// One of the images bundled with the game
+    Builtin(usize),
+    /// Image uploaded to the server, identified by hash of its contents
+    Uploaded(u64),
+    /// Image downloaded by every client from the given url
+    Url(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,4 +602,100 @@ pub struct TileState {
     pub grabbed_by: Option<Id>,
     pub pos: Vec2<f32>,
     pub connections: Vec<usize>,
+    /// Number of quarter turns counterclockwise
+    #[serde(default)]
+    pub rotation: u8,
+    /// Whether the tile waits in the tray instead of lying on the board
+    #[serde(default)]
+    pub in_tray: bool,
+    /// Placed at its spot and can not be moved anymore, see [RoomConfig::lock_placed]
+    #[serde(default)]
+    pub locked: bool,
+}
+
+#[test]
+fn test_packed_size() {
+    // A tick with a few players moving, cursors make up most of the traffic
+    let tick = ServerMessage::Updates {
+        positions: (0..5)
+            .map(|i| (Id(i), CompactPos::from(vec2(1.5, -2.5))))
+            .collect(),
+        tiles: (0..3)
+            .map(|i| {
+                (
+                    i,
+                    TileState {
+                        grabbed_by: None,
+                        pos: vec2(0.5, 0.5),
+                        connections: vec![i + 1],
+                        rotation: 0,
+                        in_tray: false,
+                        locked: false,
+                    },
+                )
+            })
+            .collect(),
+    };
+    let plain = bincode::serialize(&tick).unwrap().len();
+    let packed = bincode::serialize(&tick.clone().pack()).unwrap().len();
+    assert!(
+        packed * 10 < plain * 6,
+        "{packed} bytes packed, {plain} plain"
+    );
+    assert!(matches!(
+        tick.pack().decompress().unwrap(),
+        ServerMessage::Updates { positions, tiles } if positions.len() == 5 && tiles.len() == 3
+    ));
+}
+
+#[test]
+fn test_nested_client_messages() {
+    let packed = |message: &ClientMessage| ClientMessage::Packed(pack(message));
+    let batch = ClientMessage::Batch(vec![ClientMessage::Scatter, ClientMessage::Leave]);
+    assert_eq!(packed(&batch).unpack().unwrap().len(), 2);
+    assert_eq!(packed(&ClientMessage::Scatter).unpack().unwrap().len(), 1);
+    assert!(packed(&packed(&ClientMessage::Scatter)).unpack().is_err());
+    assert!(packed(&ClientMessage::Batch(vec![packed(&batch)]))
+        .unpack()
+        .is_err());
+    assert!(ClientMessage::Batch(vec![packed(&batch)]).unpack().is_err());
+    // Refused while decoding, before anything gets to look at it
+    let mut deep = ClientMessage::Scatter;
+    for _ in 0..1000 {
+        deep = ClientMessage::Batch(vec![deep]);
+    }
+    assert!(unpack::<ClientMessage>(pack(&deep).as_slice()).is_err());
+    assert!(unpack::<ClientMessage>(pack(&batch).as_slice()).is_ok());
+}
+
+#[test]
+fn test_compact_pos() {
+    let pos = vec2(1.234, -56.789);
+    let compact = CompactPos::from(pos);
+    let decoded: Vec2<f32> = compact.into();
+    assert!((decoded - pos).len() < 1.0 / CompactPos::SCALE);
+}
+
+#[test]
+fn test_sanitize_name() {
+    assert_eq!(
+        RoomConfig::sanitize_name("  Sunday puzzle!! #2 "),
+        "Sunday-puzzle-2"
+    );
+    assert_eq!(RoomConfig::sanitize_name("../../etc/passwd"), "etc-passwd");
+    assert_eq!(RoomConfig::sanitize_name("Привет"), "");
+    assert_eq!(
+        RoomConfig::sanitize_name(&"a".repeat(100)).len(),
+        RoomConfig::MAX_NAME_LEN
+    );
+}
+
+#[test]
+fn test_valid_name() {
+    assert!(RoomConfig::is_valid_name("Sunday-puzzle-2-1"));
+    assert!(RoomConfig::is_valid_name("a8Xk2mQ9rT4vB7nP"));
+    assert!(!RoomConfig::is_valid_name(""));
+    assert!(!RoomConfig::is_valid_name("../../x"));
+    assert!(!RoomConfig::is_valid_name("rooms/x"));
+    assert!(!RoomConfig::is_valid_name(&"a".repeat(100)));
 }
@@ -6,6 +6,8 @@ pub struct Interpolated<T> {
     c: T,
     d: T,
     t: f32,
+    /// Seconds it takes to reach the position from a server update
+    time: f32,
 }
 
 // f(t) = A * t^3 + B * t^2 + C * t + D
@@ -44,7 +46,7 @@ impl Zero for Vec3<f32> {
     };
 }
 
-const INTERPOLATION_TIME: f32 = 0.2;
+pub const INTERPOLATION_TIME: f32 = 0.2;
 
 impl<T: Mul<f32, Output = T> + Add<Output = T> + Sub<Output = T> + Copy + Zero> Interpolated<T> {
     pub fn new(p: T, v: T) -> Self {
@@ -54,33 +56,54 @@ impl<T: Mul<f32, Output = T> + Add<Output = T> + Sub<Output = T> + Copy + Zero>
             c: v * INTERPOLATION_TIME,
             d: p,
             t: 0.0,
+            time: INTERPOLATION_TIME,
+        }
+    }
+    /// Changes how long catching up with server updates takes, keeping the current motion
+    pub fn set_time(&mut self, time: f32) {
+        if time == self.time {
+            return;
+        }
+        let p = self.get();
+        let v = self.get_derivative();
+        let target = self.a + self.b + self.c + self.d;
+        self.time = time;
+        if self.t < 1.0 {
+            // Continue from where it is now towards the same target
+            self.d = p;
+            self.c = v * time;
+            self.b = target * 3.0 - self.c * 2.0 - self.d * 3.0;
+            self.a = target - self.b - self.c - self.d;
+            self.t = 0.0;
+        } else {
+            self.teleport(p, T::ZERO);
         }
     }
     pub fn teleport(&mut self, p: T, v: T) {
         self.a = T::ZERO;
         self.b = T::ZERO;
-        self.c = v * INTERPOLATION_TIME;
+        self.c = v * self.time;
         self.d = p;
         self.t = 0.0;
     }
     pub fn server_update(&mut self, p2: T, v2: T) {
-        let p2 = p2 + v2 * INTERPOLATION_TIME;
+        let p2 = p2 + v2 * self.time;
         let p1 = self.get();
         let v1 = self.get_derivative();
         self.d = p1;
-        self.c = v1 * INTERPOLATION_TIME;
-        self.b = p2 * 3.0 - self.c * 2.0 - self.d * 3.0 - v2 * INTERPOLATION_TIME;
+        self.c = v1 * self.time;
+        self.b = p2 * 3.0 - self.c * 2.0 - self.d * 3.0 - v2 * self.time;
         self.a = p2 - self.b - self.c - self.d;
         self.t = 0.0;
     }
     pub fn update(&mut self, delta_time: f32) {
-        self.t = (self.t + delta_time / INTERPOLATION_TIME).min(1.0);
+        self.t = (self.t + delta_time / self.time).min(1.0);
     }
     pub fn get(&self) -> T {
         self.a * self.t.powi(3) + self.b * self.t.sqr() + self.c * self.t + self.d
     }
     pub fn get_derivative(&self) -> T {
-        (self.a * 3.0 * self.t.sqr() + self.b * 2.0 * self.t + self.c) * (1.0 / INTERPOLATION_TIME)
+        (self.a * 3.0 * self.t.sqr() + self.b * 2.0 * self.t + self.c) * (1.0 / self.time)
     }
 }
 
@@ -99,3 +122,17 @@ fn test_interpolation() {
     assert!(i.get() == 0.5);
     assert!(i.get_derivative() == 1.0);
 }
+
+#[test]
+fn test_set_time() {
+    let mut i = Interpolated::new(0.0, 0.0);
+    i.server_update(1.0, 0.0);
+    i.update(INTERPOLATION_TIME / 2.0);
+    let halfway = i.get();
+    i.set_time(INTERPOLATION_TIME * 2.0);
+    assert!(i.get() == halfway);
+    i.update(INTERPOLATION_TIME * 2.0);
+    assert!((i.get() - 1.0).abs() < 1e-5);
+    i.set_time(INTERPOLATION_TIME);
+    assert!((i.get() - 1.0).abs() < 1e-5);
+}
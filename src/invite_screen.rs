@@ -0,0 +1,100 @@
+use super::*;
+
+/// Shown after creating a room so that the invite can be shared before playing
+pub struct InviteScreen {
+    geng: Geng,
+    addr: String,
+    room: String,
+    name: Option<String>,
+    password: Option<String>,
+    client_config: ClientConfig,
+    theme: UiTheme,
+    copied: bool,
+    transition: Option<geng::Transition>,
+}
+
+impl InviteScreen {
+    pub fn new(
+        geng: &Geng,
+        addr: &str,
+        room: &str,
+        name: Option<String>,
+        password: Option<String>,
+        client_config: &ClientConfig,
+    ) -> Self {
+        Self {
+            geng: geng.clone(),
+            addr: addr.to_owned(),
+            room: room.to_owned(),
+            name,
+            password,
+            theme: Settings::load(client_config).ui_theme,
+            client_config: client_config.clone(),
+            copied: false,
+            transition: None,
+        }
+    }
+}
+
+impl geng::State for InviteScreen {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(
+            framebuffer,
+            Some(self.theme.palette().background),
+            None,
+            None,
+        );
+    }
+    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
+        use geng::ui::*;
+        let invite = util::invite_text(&self.room);
+        let title = Text::new(
+            tr("invite.title"),
+            self.geng.default_font().clone(),
+            cx.theme().text_size,
+            self.theme.palette().text,
+        );
+        let invite_text = Text::new(
+            invite.clone(),
+            self.geng.default_font().clone(),
+            cx.theme().text_size,
+            self.theme.palette().text,
+        );
+        let copy_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(if self.copied {
+                "invite.copied"
+            } else {
+                "invite.copy"
+            }),
+        );
+        if copy_button.was_clicked() {
+            util::copy_to_clipboard(&invite);
+            self.copied = true;
+        }
+        let play_button = button::Button::new(cx, self.theme, &tr("menu.play"));
+        if play_button.was_clicked() {
+            self.transition = Some(geng::Transition::Switch(Box::new(game::run(
+                &self.geng,
+                &self.addr,
+                &self.room,
+                self.name.clone(),
+                self.password.clone(),
+                &self.client_config,
+            ))));
+        }
+        (
+            title.center(),
+            invite_text.center(),
+            copy_button.center(),
+            play_button.center(),
+        )
+            .column()
+            .center()
+            .boxed()
+    }
+    fn transition(&mut self) -> Option<geng::Transition> {
+        self.transition.take()
+    }
+}
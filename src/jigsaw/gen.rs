@@ -2,37 +2,255 @@ use super::*;
 
 type Mesh = Vec<[JigsawVertex; 3]>;
 
+/// Width of the beveled rim along the edges, relative to the smaller side of a tile
+const BEVEL_WIDTH: f32 = 0.025;
+
+pub struct GeneratedTile {
+    pub mesh: Vec<JigsawVertex>,
+    pub outline: ugli::VertexBuffer<JigsawVertex>,
+    /// Center of the tile in the assembled puzzle, mesh is relative to it
+    pub home: Vec2<f32>,
+    pub neighbors: Vec<usize>,
+}
+
 pub fn generate_jigsaw(
     ugli: &Ugli,
     seed: u64,
     size: Vec2<f32>,
     pieces: Vec2<usize>,
-) -> Vec<(JigsawMesh, ugli::VertexBuffer<JigsawVertex>)> {
-    let outlines = outline_vertices(size, pieces, jigsaw(seed, size, pieces));
-    let triangles = triangulate(&outlines);
-    finalize_meshes(ugli, triangles, outlines)
-}
-
-fn finalize_meshes(
-    ugli: &Ugli,
-    triangles: Vec<Mesh>,
-    outlines: Vec<Vec<JigsawVertex>>,
-) -> Vec<(JigsawMesh, ugli::VertexBuffer<JigsawVertex>)> {
-    triangles
+    tiling: Tiling,
+    style: EdgeStyle,
+) -> Vec<GeneratedTile> {
+    let tiles = match tiling {
+        Tiling::Grid => grid_jigsaw(seed, size, pieces, style),
+        Tiling::Hexagonal => hex_jigsaw(seed, size, pieces, style),
+    };
+    let outlines = outline_vertices(size, &tiles);
+    let tile_size = size / pieces.map(|x| x as f32);
+    let bevel_width = partial_min(tile_size.x, tile_size.y) * BEVEL_WIDTH;
+    let meshes: Vec<Mesh> = outlines
+        .iter()
+        .map(|outline| bevel_mesh(outline, bevel_width, size))
+        .collect();
+    meshes
         .into_iter()
         .zip(outlines)
-        .map(|(mesh, outline)| {
-            (
-                ugli::VertexBuffer::new_dynamic(ugli, mesh.into_iter().flatten().collect()),
-                ugli::VertexBuffer::new_dynamic(ugli, outline),
-            )
+        .zip(tiles)
+        .map(|((mesh, outline), tile)| GeneratedTile {
+            mesh: mesh.into_iter().flatten().collect(),
+            outline: ugli::VertexBuffer::new_dynamic(ugli, outline),
+            home: tile.home,
+            neighbors: tile.neighbors,
         })
         .collect()
 }
 
+/// Home positions and neighbors of all tiles, without building any meshes
+pub fn layout(
+    seed: u64,
+    size: Vec2<f32>,
+    pieces: Vec2<usize>,
+    tiling: Tiling,
+    style: EdgeStyle,
+) -> Vec<(Vec2<f32>, Vec<usize>)> {
+    let tiles = match tiling {
+        Tiling::Grid => grid_jigsaw(seed, size, pieces, style),
+        Tiling::Hexagonal => hex_jigsaw(seed, size, pieces, style),
+    };
+    tiles
+        .into_iter()
+        .map(|tile| (tile.home, tile.neighbors))
+        .collect()
+}
+
 type Polygon = Vec<Vec2<f32>>;
 
-fn jigsaw(seed: u64, size: Vec2<f32>, pieces: Vec2<usize>) -> Vec<Polygon> {
+struct TileOutline {
+    polygon: Polygon,
+    home: Vec2<f32>,
+    neighbors: Vec<usize>,
+}
+
+fn grid_jigsaw(
+    seed: u64,
+    size: Vec2<f32>,
+    pieces: Vec2<usize>,
+    style: EdgeStyle,
+) -> Vec<TileOutline> {
+    let tile_size = size / pieces.map(|x| x as f32);
+    jigsaw(seed, size, pieces, style)
+        .into_iter()
+        .enumerate()
+        .map(|(i, polygon)| {
+            let pos = vec2(i % pieces.x, i / pieces.x);
+            let mut neighbors = Vec::new();
+            if pos.x > 0 {
+                neighbors.push(i - 1);
+            }
+            if pos.x + 1 < pieces.x {
+                neighbors.push(i + 1);
+            }
+            if pos.y > 0 {
+                neighbors.push(i - pieces.x);
+            }
+            if pos.y + 1 < pieces.y {
+                neighbors.push(i + pieces.x);
+            }
+            TileOutline {
+                polygon,
+                home: pos.map(|x| x as f32 + 0.5) * tile_size,
+                neighbors,
+            }
+        })
+        .collect()
+}
+
+/// Tab shape along an edge of unit length, starting at the origin
+fn knob(style: EdgeStyle) -> Vec<Vec2<f32>> {
+    match style {
+        EdgeStyle::Classic => {
+            const KNOB_RESOLUTION: usize = 10;
+            const MIN: f32 = 3.95;
+            const MAX: f32 = -0.85;
+            (0..KNOB_RESOLUTION)
+                .map(|i| {
+                    let t = i as f32 / (KNOB_RESOLUTION as f32 - 1.0) * (MAX - MIN) + MIN;
+                    let (sin, cos) = t.sin_cos();
+                    // (x - 0.1)^2 + (y - 0.15)^2 = 0.025
+                    vec2(0.1 + cos * 0.15, 0.15 + sin * 0.15)
+                })
+                .collect()
+        }
+        // Dovetail, wider at the top
+        EdgeStyle::Angular => vec![vec2(-0.03, 0.2), vec2(0.23, 0.2)],
+        EdgeStyle::Straight => vec![],
+    }
+}
+
+/// Pointy-top hexagons in rows, odd rows are shifted by half a hexagon
+/// and have halves at both ends so that the puzzle stays rectangular
+fn hex_jigsaw(
+    seed: u64,
+    size: Vec2<f32>,
+    pieces: Vec2<usize>,
+    style: EdgeStyle,
+) -> Vec<TileOutline> {
+    let mut rng = rand::prelude::StdRng::seed_from_u64(seed);
+    // Corners lie on a lattice of half a hexagon's width by a quarter of its height
+    let max = vec2(2 * pieces.x as i32, 3 * pieces.y as i32 - 1);
+    let unit = size / max.map(|x| x as f32);
+    let to_world = |v: Vec2<i32>| v.map(|x| x as f32) * unit;
+    let mut centers = Vec::new();
+    for row in 0..pieces.y as i32 {
+        let (first, count) = if row % 2 == 0 {
+            (1, pieces.x)
+        } else {
+            (0, pieces.x + 1)
+        };
+        for column in 0..count as i32 {
+            centers.push(vec2(first + 2 * column, 1 + 3 * row));
+        }
+    }
+    const HEXAGON: [(i32, i32); 6] = [(0, -2), (1, -1), (1, 1), (0, 2), (-1, 1), (-1, -1)];
+    let corners: Vec<Vec<Vec2<i32>>> = centers
+        .iter()
+        .map(|&center| {
+            let hexagon = HEXAGON.iter().map(|&(x, y)| center + vec2(x, y)).collect();
+            clip(hexagon, max)
+        })
+        .collect();
+
+    type Edge = ((i32, i32), (i32, i32));
+    let edge_key = |a: Vec2<i32>, b: Vec2<i32>| -> Edge {
+        let (a, b) = ((a.x, a.y), (b.x, b.y));
+        (a.min(b), a.max(b))
+    };
+    let mut edge_tiles: HashMap<Edge, Vec<usize>> = HashMap::new();
+    for (tile, corners) in corners.iter().enumerate() {
+        for (i, &a) in corners.iter().enumerate() {
+            let b = corners[(i + 1) % corners.len()];
+            edge_tiles.entry(edge_key(a, b)).or_default().push(tile);
+        }
+    }
+
+    let knob = knob(style);
+    // Points of every shared edge, going from its smaller corner to the bigger one
+    let mut edge_points: HashMap<Edge, Vec<Vec2<f32>>> = HashMap::new();
+    let mut tiles = Vec::new();
+    for (tile, (corners, &center)) in corners.iter().zip(&centers).enumerate() {
+        let mut polygon = Vec::new();
+        let mut neighbors = Vec::new();
+        for (i, &a) in corners.iter().enumerate() {
+            let b = corners[(i + 1) % corners.len()];
+            polygon.push(to_world(a));
+            let edge = edge_key(a, b);
+            let other = match edge_tiles[&edge].iter().find(|&&other| other != tile) {
+                Some(&other) => other,
+                None => continue,
+            };
+            neighbors.push(other);
+            let points = edge_points.entry(edge).or_insert_with(|| {
+                let from = to_world(vec2(edge.0 .0, edge.0 .1));
+                let delta = to_world(vec2(edge.1 .0, edge.1 .1)) - from;
+                let start = rng.gen_range(0.3..=0.5);
+                let side = if rng.gen() { 1.0 } else { -1.0 };
+                itertools::chain![[vec2(0.0, 0.0)], knob.iter().copied(), [vec2(0.2, 0.0)],]
+                    .map(|v| from + delta * (v.x + start) + delta.rotate_90() * v.y * side)
+                    .collect()
+            });
+            if (a.x, a.y) == edge.0 {
+                polygon.extend(points.iter().copied());
+            } else {
+                polygon.extend(points.iter().rev().copied());
+            }
+        }
+        tiles.push(TileOutline {
+            polygon,
+            home: to_world(center),
+            neighbors,
+        });
+    }
+    tiles
+}
+
+/// Clips a convex polygon to the rectangle from the origin to `max`
+fn clip(polygon: Vec<Vec2<i32>>, max: Vec2<i32>) -> Vec<Vec2<i32>> {
+    // Signed distances to the sides of the rectangle, positive inside
+    let sides: [fn(Vec2<f32>, Vec2<f32>) -> f32; 4] = [
+        |v, _| v.x,
+        |v, _| v.y,
+        |v, max| max.x - v.x,
+        |v, max| max.y - v.y,
+    ];
+    let max = max.map(|x| x as f32);
+    let mut polygon: Vec<Vec2<f32>> = polygon.into_iter().map(|v| v.map(|x| x as f32)).collect();
+    for side in sides {
+        let input = std::mem::take(&mut polygon);
+        for (i, &to) in input.iter().enumerate() {
+            let from = input[(i + input.len() - 1) % input.len()];
+            let (from_distance, to_distance) = (side(from, max), side(to, max));
+            if (from_distance < 0.0) != (to_distance < 0.0) {
+                let t = from_distance / (from_distance - to_distance);
+                polygon.push(from + (to - from) * t);
+            }
+            if to_distance >= 0.0 {
+                polygon.push(to);
+            }
+        }
+    }
+    // Cuts only happen at lattice points
+    let mut result: Vec<Vec2<i32>> = polygon
+        .into_iter()
+        .map(|v| v.map(|x| x.round() as i32))
+        .collect();
+    result.dedup();
+    if result.len() > 1 && result.first() == result.last() {
+        result.pop();
+    }
+    result
+}
+
+fn jigsaw(seed: u64, size: Vec2<f32>, pieces: Vec2<usize>, style: EdgeStyle) -> Vec<Polygon> {
     let mut rng = rand::prelude::StdRng::seed_from_u64(seed);
     let tile_size = size / pieces.map(|x| x as f32);
     let mut vertices: Vec<Vec2<f32>> = (0..=pieces.y)
@@ -68,19 +286,7 @@ fn jigsaw(seed: u64, size: Vec2<f32>, pieces: Vec2<usize>) -> Vec<Polygon> {
 
     let vertical_edges = pieces.y * (pieces.x - 1);
     let edges_count = vertical_edges + pieces.x * (pieces.y - 1);
-    let knob: Vec<Vec2<f32>> = {
-        const KNOB_RESOLUTION: usize = 10;
-        const MIN: f32 = 3.95;
-        const MAX: f32 = -0.85;
-        (0..KNOB_RESOLUTION)
-            .map(|i| {
-                let t = i as f32 / (KNOB_RESOLUTION as f32 - 1.0) * (MAX - MIN) + MIN;
-                let (sin, cos) = t.sin_cos();
-                // (x - 0.1)^2 + (y - 0.15)^2 = 0.025
-                vec2(0.1 + cos * 0.15, 0.15 + sin * 0.15)
-            })
-            .collect()
-    };
+    let knob = knob(style);
     let mut edges: Vec<Vec<Vec2<f32>>> = (0..edges_count)
         .map(|i| {
             let t = i as f32 / (edges_count as f32 - 1.0);
@@ -134,46 +340,91 @@ fn jigsaw(seed: u64, size: Vec2<f32>, pieces: Vec2<usize>) -> Vec<Polygon> {
         .collect()
 }
 
-fn outline_vertices(
-    size: Vec2<f32>,
-    pieces: Vec2<usize>,
-    polygons: Vec<Polygon>,
-) -> Vec<Vec<JigsawVertex>> {
-    polygons
-        .into_iter()
-        .enumerate()
-        .map(|(i, polygon)| {
-            let center = vec2(i % pieces.x, i / pieces.x).map(|x| x as f32 + 0.5)
-                / pieces.map(|x| x as f32)
-                * size;
-            polygon
-                .into_iter()
-                .map(|v| JigsawVertex {
-                    a_pos: v - center,
-                    a_uv: v / size,
+fn outline_vertices(size: Vec2<f32>, tiles: &[TileOutline]) -> Vec<Vec<JigsawVertex>> {
+    tiles
+        .iter()
+        .map(|tile| {
+            let polygon = &tile.polygon;
+            // Outward side depends on the winding
+            let area: f32 = (0..polygon.len())
+                .map(|i| cross(polygon[i], polygon[(i + 1) % polygon.len()]))
+                .sum();
+            let edge_normal = |a: Vec2<f32>, b: Vec2<f32>| {
+                let normal = unit((b - a).rotate_90());
+                if area > 0.0 {
+                    -normal
+                } else {
+                    normal
+                }
+            };
+            (0..polygon.len())
+                .map(|i| {
+                    let prev = polygon[(i + polygon.len() - 1) % polygon.len()];
+                    let v = polygon[i];
+                    let next = polygon[(i + 1) % polygon.len()];
+                    JigsawVertex {
+                        a_pos: v - tile.home,
+                        a_uv: v / size,
+                        a_bevel: 1.0,
+                        a_normal: unit(edge_normal(prev, v) + edge_normal(v, next)),
+                    }
                 })
                 .collect()
         })
         .collect()
 }
 
-fn triangulate(polygons: &[Vec<JigsawVertex>]) -> Vec<Mesh> {
-    polygons
+/// Triangles of the tile with a strip along the outline for the bevel
+fn bevel_mesh(outline: &[JigsawVertex], width: f32, size: Vec2<f32>) -> Mesh {
+    let inner: Vec<JigsawVertex> = (0..outline.len())
+        .map(|i| {
+            let v = outline[i];
+            let next = outline[(i + 1) % outline.len()];
+            // Keep the rim the same width at corners, within reason at sharp ones
+            let sin = cross(unit(next.a_pos - v.a_pos), v.a_normal).abs().max(0.5);
+            let offset = -v.a_normal * width / sin;
+            JigsawVertex {
+                a_pos: v.a_pos + offset,
+                a_uv: v.a_uv + offset / size,
+                a_bevel: 0.0,
+                ..v
+            }
+        })
+        .collect();
+    let mut mesh = triangulate(&inner);
+    for i in 0..outline.len() {
+        let j = (i + 1) % outline.len();
+        mesh.push([outline[i], outline[j], inner[j]]);
+        mesh.push([outline[i], inner[j], inner[i]]);
+    }
+    mesh
+}
+
+fn cross(a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Vector of unit length in the same direction, zero stays zero
+fn unit(v: Vec2<f32>) -> Vec2<f32> {
+    let len = v.len();
+    if len > 0.0 {
+        v / len
+    } else {
+        Vec2::ZERO
+    }
+}
+
+fn triangulate(polygon: &[JigsawVertex]) -> Mesh {
+    let flat_polygon: Vec<f32> = polygon
         .iter()
-        .map(|polygon| {
-            let flat_polygon: Vec<f32> = polygon
-                .iter()
-                .flat_map(|v| [v.a_pos.x, v.a_pos.y])
-                .collect();
-            let triangles =
-                earcutr::earcut(&flat_polygon, &[], 2).expect("Failed to triangulate mesh");
-            triangles
-                .chunks(3)
-                .map(|triangle| {
-                    let triangle = [triangle[0], triangle[1], triangle[2]];
-                    triangle.map(|i| polygon[i])
-                })
-                .collect()
+        .flat_map(|v| [v.a_pos.x, v.a_pos.y])
+        .collect();
+    let triangles = earcutr::earcut(&flat_polygon, &[], 2).expect("Failed to triangulate mesh");
+    triangles
+        .chunks(3)
+        .map(|triangle| {
+            let triangle = [triangle[0], triangle[1], triangle[2]];
+            triangle.map(|i| polygon[i])
         })
         .collect()
 }
@@ -1,6 +1,9 @@
 use super::*;
 
 mod gen;
+mod spatial;
+
+pub use gen::layout;
 
 pub type JigsawMesh = ugli::VertexBuffer<JigsawVertex>;
 
@@ -8,11 +11,35 @@ pub type JigsawMesh = ugli::VertexBuffer<JigsawVertex>;
 pub struct JigsawVertex {
     pub a_pos: Vec2<f32>,
     pub a_uv: Vec2<f32>,
+    /// `1` on the edge of the tile fading to `0` at the inner side of the beveled rim
+    pub a_bevel: f32,
+    /// Direction away from the nearest edge, for shading the bevel
+    pub a_normal: Vec2<f32>,
 }
 
 pub struct Jigsaw {
+    ugli: Ugli,
     pub tile_size: Vec2<f32>,
     pub tiles: Vec<JigsawTile>,
+    /// Triangles of all tiles, see [JigsawTile::mesh_range]
+    pub mesh: JigsawMesh,
+    /// Group index of every tile
+    group_of: Vec<usize>,
+    /// Tiles of every group, groups merged into others are left empty
+    groups: Vec<Vec<usize>>,
+    /// Silhouette of every group as a list of line segments relative to its first tile,
+    /// `None` means it needs to be rebuilt
+    group_outlines: Vec<Option<ugli::VertexBuffer<JigsawVertex>>>,
+    /// Tiles from bottom to top
+    render_order: Vec<usize>,
+    /// Position of every tile in [Self::render_order]
+    render_index: Vec<usize>,
+    /// Tile holding each group, refreshed by [Self::prepare_render]
+    held_by_tile: Vec<Option<usize>>,
+    /// Number of tiles in the biggest group
+    largest_group: usize,
+    /// Tiles on the board by position, refreshed by [Self::update_spatial_index]
+    spatial: spatial::SpatialIndex,
 }
 
 pub struct JigsawTile {
@@ -20,70 +47,352 @@ pub struct JigsawTile {
     pub last_interaction_time: f32,
     pub grabbed_by: Option<Id>,
     pub connected_to: Vec<usize>,
-    pub puzzle_pos: Vec2<usize>,
-    pub mesh: JigsawMesh,
+    /// Position in the assembled puzzle
+    pub home: Vec2<f32>,
+    /// Tiles adjacent in the assembled puzzle
+    pub neighbors: Vec<usize>,
+    /// Number of quarter turns counterclockwise, same for the whole group
+    pub rotation: u8,
+    /// Tiles in the tray are not on the board, only loose tiles can be there
+    pub in_tray: bool,
+    /// Whether the tile is on the border of the puzzle
+    pub is_edge: bool,
+    /// Placed at its spot and can not be grabbed anymore
+    pub locked: bool,
+    /// Range of this tile's vertices in [Jigsaw::mesh]
+    pub mesh_range: std::ops::Range<usize>,
     pub outline: ugli::VertexBuffer<JigsawVertex>,
 }
 
 impl Jigsaw {
-    pub fn generate(ugli: &Ugli, seed: u64, size: Vec2<f32>, pieces: Vec2<usize>) -> Self {
+    /// Grid with at most the given number of pieces where pieces are as close to square
+    /// as possible for an image with the given aspect ratio
+    pub fn grid_size(pieces: usize, aspect: f32) -> Vec2<usize> {
+        (1..=pieces)
+            .map(|x| vec2(x, (pieces / x).max(1)))
+            .min_by_key(|size| {
+                let piece_aspect = aspect * size.y as f32 / size.x as f32;
+                let missing = pieces as f32 / (size.x * size.y) as f32;
+                r32(piece_aspect.ln().abs() + missing.ln().abs())
+            })
+            .unwrap()
+    }
+    pub fn generate(
+        ugli: &Ugli,
+        seed: u64,
+        size: Vec2<f32>,
+        pieces: Vec2<usize>,
+        tiling: Tiling,
+        style: EdgeStyle,
+    ) -> Self {
         let tile_size = size / pieces.map(|x| x as f32);
-        Self {
+        let max_neighbors = match tiling {
+            Tiling::Grid => 4,
+            Tiling::Hexagonal => 6,
+        };
+        let mut mesh = Vec::new();
+        let tiles: Vec<JigsawTile> = gen::generate_jigsaw(ugli, seed, size, pieces, tiling, style)
+            .into_iter()
+            .map(|tile| {
+                let mesh_range = mesh.len()..mesh.len() + tile.mesh.len();
+                mesh.extend(tile.mesh);
+                JigsawTile {
+                    interpolated: Interpolated::new(tile.home, Vec2::ZERO),
+                    last_interaction_time: 0.0,
+                    grabbed_by: None,
+                    connected_to: vec![],
+                    home: tile.home,
+                    is_edge: tile.neighbors.len() < max_neighbors,
+                    neighbors: tile.neighbors,
+                    rotation: 0,
+                    in_tray: false,
+                    locked: false,
+                    mesh_range,
+                    outline: tile.outline,
+                }
+            })
+            .collect();
+        let count = tiles.len();
+        let reach = partial_max(tile_size.x, tile_size.y);
+        let mut jigsaw = Self {
+            ugli: ugli.clone(),
             tile_size,
-            tiles: gen::generate_jigsaw(ugli, seed, size, pieces)
-                .into_iter()
-                .enumerate()
-                .map(|(i, (mesh, outline))| {
-                    let puzzle_pos = vec2(i % pieces.x, i / pieces.x);
-                    JigsawTile {
-                        interpolated: Interpolated::new(
-                            puzzle_pos.map(|x| x as f32 + 0.5) * tile_size,
-                            Vec2::ZERO,
-                        ),
-                        last_interaction_time: 0.0,
-                        grabbed_by: None,
-                        connected_to: vec![],
-                        puzzle_pos,
-                        mesh,
-                        outline,
-                    }
+            tiles,
+            mesh: ugli::VertexBuffer::new_static(ugli, mesh),
+            group_of: (0..count).collect(),
+            groups: (0..count).map(|i| vec![i]).collect(),
+            group_outlines: (0..count).map(|_| None).collect(),
+            render_order: (0..count).collect(),
+            render_index: (0..count).collect(),
+            held_by_tile: vec![None; count],
+            largest_group: 1,
+            spatial: spatial::SpatialIndex::new(count, reach),
+        };
+        jigsaw.update_spatial_index();
+        jigsaw
+    }
+
+    pub fn tile_mesh(&self, tile: usize) -> &[JigsawVertex] {
+        &self.mesh[self.tiles[tile].mesh_range.clone()]
+    }
+
+    pub fn tile_contains(&self, tile: usize, pos: Vec2<f32>) -> bool {
+        let matrix = self.tiles[tile].matrix();
+        for triangle in self.tile_mesh(tile).chunks(3) {
+            let triangle = [triangle[0], triangle[1], triangle[2]].map(|p| {
+                let p = matrix * p.a_pos.extend(1.0);
+                p.xy() / p.z
+            });
+            if util::triangle_contains(triangle, pos) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Farthest a tile's shape reaches from its position, tabs included
+    fn reach(&self) -> f32 {
+        partial_max(self.tile_size.x, self.tile_size.y)
+    }
+
+    /// Puts tiles that moved since the last call into their new cells
+    pub fn update_spatial_index(&mut self) {
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let pos = (!tile.in_tray).then(|| tile.interpolated.get());
+            self.spatial.update(i, pos);
+        }
+    }
+
+    /// Tiles on the board whose position may be within `radius` of `pos`
+    pub fn tiles_near(&self, pos: Vec2<f32>, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        self.spatial.query(pos, radius)
+    }
+
+    /// Tiles on the board with `pos` inside their shape
+    pub fn tiles_at(&self, pos: Vec2<f32>) -> impl Iterator<Item = usize> + '_ {
+        self.tiles_near(pos, self.reach())
+            .filter(move |&tile| self.tile_contains(tile, pos))
+    }
+
+    /// Tiles on the board close enough to snap to the tile, `distance` is on top of the tile size
+    pub fn neighbors_near(&self, tile: usize, distance: f32) -> Vec<usize> {
+        let pos = self.tiles[tile].interpolated.get();
+        let mut near: Vec<usize> = self
+            .tiles_near(pos, self.tile_size.len() + distance)
+            .filter(|other| self.tiles[tile].neighbors.contains(other))
+            .collect();
+        near.sort_unstable();
+        near
+    }
+
+    pub fn group(&self, tile: usize) -> usize {
+        self.group_of[tile]
+    }
+
+    pub fn get_all_connected(&self, tile: usize) -> &[usize] {
+        &self.groups[self.group_of[tile]]
+    }
+
+    pub fn connect(&mut self, a: usize, b: usize) {
+        if !self.tiles[a].connected_to.contains(&b) {
+            self.tiles[a].connected_to.push(b);
+        }
+        if !self.tiles[b].connected_to.contains(&a) {
+            self.tiles[b].connected_to.push(a);
+        }
+        let (a, b) = (self.group_of[a], self.group_of[b]);
+        if a == b {
+            return;
+        }
+        // Merge smaller group into the bigger one
+        let (big, small) = if self.groups[a].len() >= self.groups[b].len() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let moved = std::mem::take(&mut self.groups[small]);
+        for &tile in &moved {
+            self.group_of[tile] = big;
+        }
+        self.groups[big].extend(moved);
+        self.largest_group = self.largest_group.max(self.groups[big].len());
+        self.group_outlines[small] = None;
+        self.group_outlines[big] = None;
+    }
+
+    /// Fraction of the puzzle assembled into a single group
+    pub fn progress(&self) -> f32 {
+        self.largest_group as f32 / self.tiles.len() as f32
+    }
+
+    fn build_outline(&self, group: usize) -> ugli::VertexBuffer<JigsawVertex> {
+        let tiles = &self.groups[group];
+        let anchor = self.tiles[tiles[0]].home;
+        let segments: Vec<(JigsawVertex, JigsawVertex)> = tiles
+            .iter()
+            .flat_map(|&tile| {
+                let tile = &self.tiles[tile];
+                let offset = tile.home - anchor;
+                let outline = &tile.outline;
+                (0..outline.len()).map(move |i| {
+                    let mut a = outline[i];
+                    let mut b = outline[(i + 1) % outline.len()];
+                    a.a_pos += offset;
+                    b.a_pos += offset;
+                    (a, b)
                 })
-                .collect(),
+            })
+            .collect();
+        // Shared edges are generated from the same points, so uvs match exactly
+        let key = |v: &JigsawVertex| (v.a_uv.x.to_bits(), v.a_uv.y.to_bits());
+        let all: HashSet<_> = segments.iter().map(|(a, b)| (key(a), key(b))).collect();
+        let vertices = segments
+            .into_iter()
+            .filter(|(a, b)| !all.contains(&(key(b), key(a))))
+            .flat_map(|(a, b)| [a, b])
+            .collect();
+        ugli::VertexBuffer::new_dynamic(&self.ugli, vertices)
+    }
+
+    /// Rebuilds outlines of groups changed since the last call
+    fn rebuild_outlines(&mut self) {
+        for group in 0..self.groups.len() {
+            if self.group_outlines[group].is_none() && !self.groups[group].is_empty() {
+                self.group_outlines[group] = Some(self.build_outline(group));
+            }
         }
     }
 
-    pub fn get_all_connected(&self, tile: usize) -> HashSet<usize> {
-        fn walk_rec(tiles: &[jigsaw::JigsawTile], tile: usize, checked: &mut HashSet<usize>) {
-            if !checked.insert(tile) {
-                return;
+    /// Outlines of all groups along with their tiles, the outline is relative to the first tile
+    pub fn group_outlines(
+        &self,
+    ) -> impl Iterator<Item = (&[usize], &ugli::VertexBuffer<JigsawVertex>)> {
+        self.groups
+            .iter()
+            .zip(&self.group_outlines)
+            .filter_map(|(tiles, outline)| Some((tiles.as_slice(), outline.as_ref()?)))
+    }
+
+    pub fn set_interaction_time(&mut self, tile: usize, time: f32) {
+        let group = self.group_of[tile];
+        for &tile in &self.groups[group] {
+            self.tiles[tile].last_interaction_time = time;
+        }
+
+        // Bring the group to the top
+        let group_of = &self.group_of;
+        self.render_order.retain(|&tile| group_of[tile] != group);
+        self.render_order.extend_from_slice(&self.groups[group]);
+        for (index, &tile) in self.render_order.iter().enumerate() {
+            self.render_index[tile] = index;
+        }
+    }
+
+    /// Locks the whole group in place and moves it below everything else
+    pub fn lock_group(&mut self, tile: usize) {
+        let group = self.group_of[tile];
+        for &tile in &self.groups[group] {
+            self.tiles[tile].locked = true;
+        }
+        let group_of = &self.group_of;
+        let mut render_order = self.groups[group].clone();
+        render_order.extend(
+            self.render_order
+                .iter()
+                .copied()
+                .filter(|&tile| group_of[tile] != group),
+        );
+        self.render_order = render_order;
+        for (index, &tile) in self.render_order.iter().enumerate() {
+            self.render_index[tile] = index;
+        }
+    }
+
+    pub fn render_order(&self) -> &[usize] {
+        &self.render_order
+    }
+
+    /// Depth value for drawing the tile, top tiles are closer
+    pub fn depth(&self, tile: usize) -> f32 {
+        1.0 - 2.0 * (self.render_index[tile] as f32 + 0.5) / self.tiles.len() as f32
+    }
+
+    /// Updates cached data needed for drawing
+    pub fn prepare_render(&mut self) {
+        self.rebuild_outlines();
+        self.held_by_tile.fill(None);
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if tile.grabbed_by.is_some() {
+                self.held_by_tile[self.group_of[i]] = Some(i);
             }
-            for &tile in &tiles[tile].connected_to {
-                walk_rec(tiles, tile, checked);
+        }
+    }
+
+    /// Tile holding the group, as of the last [Self::prepare_render]
+    pub fn held_tile(&self, group: usize) -> Option<usize> {
+        self.held_by_tile[group]
+    }
+
+    /// Matrix to draw the tile with, held groups are lifted around the holding tile
+    pub fn render_matrix(&self, tile: usize) -> Mat3<f32> {
+        let tile_ref = &self.tiles[tile];
+        match self.held_by_tile[self.group_of[tile]] {
+            Some(held) => {
+                let held = &self.tiles[held];
+                let delta = tile_ref.home - held.home;
+                held.matrix() * Mat3::scale_uniform(1.05) * Mat3::translate(delta)
             }
+            None => tile_ref.matrix(),
+        }
+    }
+
+    /// Position of tile `a` relative to tile `b` when they are snapped together,
+    /// `None` if they are not adjacent in the puzzle
+    pub fn snap_offset(&self, a: usize, b: usize) -> Option<Vec2<f32>> {
+        if !self.tiles[a].neighbors.contains(&b) {
+            return None;
         }
+        Some(util::rotate_quarters(
+            self.tiles[a].home - self.tiles[b].home,
+            self.tiles[b].rotation,
+        ))
+    }
 
-        let mut connected = HashSet::new();
-        walk_rec(&self.tiles, tile, &mut connected);
-        connected
+    /// Sets rotation of the whole group, pivoting around given tile
+    pub fn rotate_group(&mut self, tile: usize, rotation: u8) {
+        for &other in &self.groups[self.group_of[tile]] {
+            self.tiles[other].rotation = rotation % 4;
+        }
+        let pos = self.tiles[tile].interpolated.get();
+        self.move_group(tile, pos, Vec2::ZERO, true);
+    }
+
+    /// Moves the whole group so that given tile ends up at `pos`
+    pub fn move_group(&mut self, tile: usize, pos: Vec2<f32>, vel: Vec2<f32>, snap: bool) {
+        let start_pos = self.tiles[tile].home;
+        for &tile in &self.groups[self.group_of[tile]] {
+            let tile = &mut self.tiles[tile];
+            let pos = pos + util::rotate_quarters(tile.home - start_pos, tile.rotation);
+            if snap {
+                tile.interpolated.teleport(pos, vel);
+            } else {
+                tile.interpolated.server_update(pos, vel);
+            }
+        }
     }
 }
 
 impl JigsawTile {
     pub fn matrix(&self) -> Mat3<f32> {
         Mat3::translate(self.interpolated.get())
+            * Mat3::rotate(self.rotation as f32 * std::f32::consts::FRAC_PI_2)
     }
+}
 
-    pub fn contains(&self, pos: Vec2<f32>) -> bool {
-        let matrix = self.matrix();
-        for triangle in self.mesh.chunks(3) {
-            let triangle = [triangle[0], triangle[1], triangle[2]].map(|p| {
-                let p = matrix * p.a_pos.extend(1.0);
-                p.xy() / p.z
-            });
-            if util::triangle_contains(triangle, pos) {
-                return true;
-            }
-        }
-        false
-    }
+#[test]
+fn test_grid_size() {
+    assert_eq!(Jigsaw::grid_size(12, 4.0 / 3.0), vec2(4, 3));
+    let size = Jigsaw::grid_size(500, 16.0 / 9.0);
+    assert!(size.x * size.y <= 500 && size.x * size.y >= 450);
+    assert!((size.x as f32 / size.y as f32 - 16.0 / 9.0).abs() < 0.1);
 }
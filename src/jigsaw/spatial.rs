@@ -0,0 +1,73 @@
+use super::*;
+
+/// Uniform grid over the board to find tiles around a point without looking at all of them
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// Cell of every tile, `None` for tiles not on the board
+    cell_of: Vec<Option<(i32, i32)>>,
+}
+
+impl SpatialIndex {
+    pub fn new(tiles: usize, cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            cell_of: vec![None; tiles],
+        }
+    }
+
+    fn cell(&self, pos: Vec2<f32>) -> (i32, i32) {
+        let cell = pos.map(|x| (x / self.cell_size).floor() as i32);
+        (cell.x, cell.y)
+    }
+
+    /// Moves the tile to the cell of its new position, `None` takes it off the board
+    pub fn update(&mut self, tile: usize, pos: Option<Vec2<f32>>) {
+        let cell = pos.map(|pos| self.cell(pos));
+        let old = self.cell_of[tile];
+        if cell == old {
+            return;
+        }
+        if let Some(old) = old {
+            if let Some(tiles) = self.cells.get_mut(&old) {
+                tiles.retain(|&other| other != tile);
+                if tiles.is_empty() {
+                    self.cells.remove(&old);
+                }
+            }
+        }
+        if let Some(cell) = cell {
+            self.cells.entry(cell).or_default().push(tile);
+        }
+        self.cell_of[tile] = cell;
+    }
+
+    /// Tiles that may be within `radius` of `pos`, exact distance is up to the caller
+    pub fn query(&self, pos: Vec2<f32>, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let (x_min, y_min) = self.cell(pos - vec2(radius, radius));
+        let (x_max, y_max) = self.cell(pos + vec2(radius, radius));
+        (x_min..=x_max)
+            .flat_map(move |x| (y_min..=y_max).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+#[test]
+fn test_spatial_index() {
+    let mut index = SpatialIndex::new(3, 1.0);
+    index.update(0, Some(vec2(0.5, 0.5)));
+    index.update(1, Some(vec2(-3.5, 2.0)));
+    index.update(2, Some(vec2(0.9, -0.2)));
+    let mut near: Vec<usize> = index.query(vec2(0.7, 0.3), 0.5).collect();
+    near.sort();
+    assert_eq!(near, vec![0, 2]);
+    index.update(2, Some(vec2(-3.2, 2.5)));
+    index.update(0, None);
+    assert_eq!(index.query(vec2(0.7, 0.3), 0.5).count(), 0);
+    let mut near: Vec<usize> = index.query(vec2(-3.0, 2.0), 1.0).collect();
+    near.sort();
+    assert_eq!(near, vec![1, 2]);
+}
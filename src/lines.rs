@@ -0,0 +1,50 @@
+use super::*;
+
+/// Corner of a quad covering a line segment, drawn with the outline shader
+/// which widens it on the screen and fades out its edges
+#[derive(ugli::Vertex, Debug, Clone, Copy)]
+pub struct LineVertex {
+    /// End of the segment with depth
+    a_pos: Vec3<f32>,
+    /// The other end of the segment
+    a_other: Vec2<f32>,
+    /// End of the segment, `-1` for the start, and side of the line, both `-1` or `1`
+    a_corner: Vec2<f32>,
+    /// Width in pixels, independent of zoom
+    a_width: f32,
+    a_color: Rgba<f32>,
+}
+
+/// Adds two triangles for the segment from `a` to `b`
+pub fn push_line(
+    vertices: &mut Vec<LineVertex>,
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    depth: f32,
+    width: f32,
+    color: Rgba<f32>,
+) {
+    let corner = |pos: Vec2<f32>, other: Vec2<f32>, corner: Vec2<f32>| LineVertex {
+        a_pos: pos.extend(depth),
+        a_other: other,
+        a_corner: corner,
+        a_width: width,
+        a_color: color,
+    };
+    let corners = [
+        corner(a, b, vec2(-1.0, -1.0)),
+        corner(a, b, vec2(-1.0, 1.0)),
+        corner(b, a, vec2(1.0, 1.0)),
+        corner(b, a, vec2(1.0, -1.0)),
+    ];
+    vertices.extend([0, 1, 2, 0, 2, 3].map(|index| corners[index]));
+}
+
+/// Parameters for drawing lines on top of the tiles they outline
+pub fn draw_parameters() -> ugli::DrawParameters {
+    ugli::DrawParameters {
+        blend_mode: Some(ugli::BlendMode::default()),
+        depth_func: Some(ugli::DepthFunc::LessOrEqual),
+        ..Default::default()
+    }
+}
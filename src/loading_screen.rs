@@ -0,0 +1,111 @@
+use super::*;
+
+/// Seconds for the puzzle image to fade in once it is known
+const FADE_TIME: f32 = 0.5;
+
+/// What the loading is waiting for, filled in by the loading future as it goes
+#[derive(Default)]
+pub struct LoadingStatus {
+    /// Shown under the progress bar, like the room being connected to
+    pub text: Option<String>,
+    pub image: Option<victory::ImageTexture>,
+}
+
+/// Shown while assets load and the room is joined, with a progress bar for the assets
+pub struct LoadingScreen {
+    geng: Geng,
+    theme: UiTheme,
+    status: Rc<RefCell<LoadingStatus>>,
+    progress: f64,
+    time: f32,
+    /// When the image first appeared in [Self::status]
+    image_time: Option<f32>,
+}
+
+impl LoadingScreen {
+    pub fn new(geng: &Geng, config: &ClientConfig) -> Self {
+        Self {
+            geng: geng.clone(),
+            theme: Settings::load(config).ui_theme,
+            status: default(),
+            progress: 0.0,
+            time: 0.0,
+            image_time: None,
+        }
+    }
+
+    /// Handle for the loading future to report what it is doing
+    pub fn status(&self) -> Rc<RefCell<LoadingStatus>> {
+        self.status.clone()
+    }
+}
+
+impl geng::State for LoadingScreen {
+    fn update(&mut self, delta_time: f64) {
+        self.time += delta_time as f32;
+        if self.image_time.is_none() && self.status.borrow().image.is_some() {
+            self.image_time = Some(self.time);
+        }
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let palette = self.theme.palette();
+        ugli::clear(framebuffer, Some(palette.background), None, None);
+        let framebuffer_size = framebuffer.size().map(|x| x as f32);
+        let status = self.status.borrow();
+        if let (Some(image), Some(image_time)) = (&status.image, self.image_time) {
+            let texture = image.get();
+            let size = texture.size().map(|x| x as f32);
+            let ratio = (framebuffer_size.y * 0.5 / size.y).min(framebuffer_size.x * 0.8 / size.x);
+            let center = vec2(framebuffer_size.x / 2.0, framebuffer_size.y * 0.6);
+            let alpha = ((self.time - image_time) / FADE_TIME).min(1.0);
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::TexturedQuad::colored(
+                    AABB::point(center).extend_symmetric(size * ratio * 0.5),
+                    texture,
+                    Rgba::new(1.0, 1.0, 1.0, alpha),
+                ),
+            );
+        }
+        let font_size = framebuffer_size.y * 0.04;
+        let bar_pos = vec2(framebuffer_size.x / 2.0, framebuffer_size.y * 0.25);
+        if self.progress < 1.0 {
+            let bar = AABB::point(bar_pos)
+                .extend_symmetric(vec2(framebuffer_size.x * 0.25, font_size * 0.25));
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(bar, palette.button),
+            );
+            self.geng.draw_2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw_2d::Quad::new(
+                    AABB {
+                        x_max: bar.x_min + bar.width() * self.progress as f32,
+                        ..bar
+                    },
+                    palette.text,
+                ),
+            );
+        }
+        if let Some(text) = &status.text {
+            self.geng.default_font().draw(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                text,
+                bar_pos - vec2(0.0, font_size * 2.0),
+                geng::TextAlign::CENTER,
+                font_size,
+                palette.text,
+            );
+        }
+    }
+}
+
+impl geng::ProgressScreen for LoadingScreen {
+    fn update_progress(&mut self, progress: f64) {
+        self.progress = progress;
+    }
+}
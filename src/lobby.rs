@@ -0,0 +1,152 @@
+use super::*;
+
+/// Lists public rooms to join
+pub struct Lobby {
+    geng: Geng,
+    addr: String,
+    client_config: ClientConfig,
+    theme: UiTheme,
+    name: Option<String>,
+    rooms: Option<Vec<RoomInfo>>,
+    loading: Option<util::Task<Vec<RoomInfo>>>,
+    transition: Option<geng::Transition>,
+}
+
+impl Lobby {
+    pub fn new(geng: &Geng, addr: &str, client_config: ClientConfig, name: Option<String>) -> Self {
+        let mut lobby = Self {
+            geng: geng.clone(),
+            addr: addr.to_owned(),
+            theme: Settings::load(&client_config).ui_theme,
+            client_config,
+            name,
+            rooms: None,
+            loading: None,
+            transition: None,
+        };
+        lobby.refresh();
+        lobby
+    }
+
+    fn refresh(&mut self) {
+        let addr = self.addr.clone();
+        self.loading = Some(util::Task::new(async move {
+            let mut con: Connection = geng::net::client::connect(&addr).await;
+            con.send(ClientMessage::ListRooms);
+            util::wait_for_reply(&mut con, |message| match message {
+                ServerMessage::RoomList(rooms) => Some(rooms),
+                _ => None,
+            })
+            .await
+            .unwrap_or_default()
+        }));
+    }
+
+    fn join(&mut self, room: &str) {
+        util::set_room_url(Some(room));
+        self.transition = Some(geng::Transition::Switch(Box::new(game::run(
+            &self.geng,
+            &self.addr,
+            room,
+            self.name.clone(),
+            None,
+            &self.client_config,
+        ))));
+    }
+}
+
+fn describe_image(image: &ImageSource) -> String {
+    match image {
+        ImageSource::Builtin(index) => tr_with(
+            "lobby.image_builtin",
+            &[("number", &(index + 1).to_string())],
+        ),
+        ImageSource::Uploaded(_) => tr("lobby.image_custom"),
+        ImageSource::Url(_) => tr("lobby.image_url"),
+    }
+}
+
+impl geng::State for Lobby {
+    fn update(&mut self, _delta_time: f64) {
+        if let Some(rooms) = self.loading.as_mut().and_then(|task| task.poll()) {
+            self.loading = None;
+            self.rooms = Some(rooms);
+        }
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(
+            framebuffer,
+            Some(self.theme.palette().background),
+            None,
+            None,
+        );
+    }
+    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
+        use geng::ui::*;
+        let text = |text: String| {
+            Text::new(
+                text,
+                self.geng.default_font().clone(),
+                cx.theme().text_size,
+                self.theme.palette().text,
+            )
+        };
+        let mut rows: Vec<Box<dyn Widget + 'a>> = Vec::new();
+        let mut join = None;
+        match &self.rooms {
+            None => rows.push(text(tr("lobby.loading")).center().boxed()),
+            Some(rooms) if rooms.is_empty() => {
+                rows.push(text(tr("lobby.no_rooms")).center().boxed())
+            }
+            Some(rooms) => {
+                for room in rooms {
+                    let join_button = button::Button::new(cx, self.theme, &tr("lobby.join"));
+                    if join_button.was_clicked() {
+                        join = Some(room.name.clone());
+                    }
+                    let mut description = tr_with(
+                        if room.locked {
+                            "lobby.room_locked"
+                        } else {
+                            "lobby.room"
+                        },
+                        &[
+                            ("image", &describe_image(&room.image)),
+                            ("pieces", &room.pieces.to_string()),
+                            (
+                                "players",
+                                &match room.max_players {
+                                    Some(max) => format!("{}/{}", room.players, max),
+                                    None => room.players.to_string(),
+                                },
+                            ),
+                        ],
+                    );
+                    if room.named {
+                        description = tr_with(
+                            "lobby.named_room",
+                            &[("name", &room.name), ("room", &description)],
+                        );
+                    }
+                    rows.push((text(description), join_button).row().center().boxed());
+                }
+            }
+        }
+        if let Some(room) = join {
+            self.join(&room);
+        }
+        let refresh_button = button::Button::new(cx, self.theme, &tr("lobby.refresh"));
+        if refresh_button.was_clicked() && self.loading.is_none() {
+            self.refresh();
+        }
+        let back_button = button::Button::new(cx, self.theme, &tr("common.back"));
+        if back_button.was_clicked() {
+            self.transition = Some(geng::Transition::Pop);
+        }
+        rows.push((refresh_button, back_button).row().center().boxed());
+        column(rows).center().boxed()
+    }
+    fn transition(&mut self) -> Option<geng::Transition> {
+        self.transition.take()
+    }
+}
@@ -1,23 +1,41 @@
 use geng::prelude::*;
 
 mod assets;
+mod button;
+mod client_config;
 mod game;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+mod hot_reload;
+mod image_levels;
 mod interop;
 mod interpolation;
+mod invite_screen;
 mod jigsaw;
+mod lines;
+mod loading_screen;
+mod lobby;
 mod main_menu;
+mod message_screen;
+mod password_prompt;
 #[cfg(not(target_arch = "wasm32"))]
 mod server;
+mod settings;
 mod slider;
 mod splitscreen;
 mod text_input;
+mod translations;
+mod tutorial;
 mod util;
+mod victory;
 
 use assets::Assets;
+use client_config::*;
 use interop::*;
 use interpolation::*;
+use settings::*;
 use slider::*;
 use text_input::*;
+use translations::{tr, tr_with, BASE_LANGUAGE};
 
 type Connection = geng::net::client::Connection<ServerMessage, ClientMessage>;
 
@@ -27,7 +45,8 @@ struct Opt {
     pub server: Option<String>,
     #[clap(long)]
     pub connect: Option<String>,
-    #[clap(long)]
+    /// Room code to join right away, skipping the menu
+    #[clap(long, alias = "join")]
     pub room: Option<String>,
     #[clap(long)]
     pub splits: Option<usize>,
@@ -35,6 +54,70 @@ struct Opt {
     pub room_config: Option<std::path::PathBuf>,
     #[clap(long)]
     pub name: Option<String>,
+    #[clap(long)]
+    pub server_config: Option<std::path::PathBuf>,
+    /// Client config file, defaults to JIGSAW_CONFIG env var
+    #[clap(long)]
+    pub config: Option<std::path::PathBuf>,
+    /// Create a new room instead of joining one
+    #[clap(long)]
+    pub create: bool,
+    /// Number of the bundled image for the created room, from 1 like in the menu
+    #[clap(long, value_parser = parse_image)]
+    pub image: Option<usize>,
+    /// Grid size for the created room, like 20x15
+    #[clap(long, value_parser = parse_size)]
+    pub size: Option<Vec2<usize>>,
+    /// Seed for the piece shapes and scattering of the created room, random by default
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// Password of the room to join or create
+    #[clap(long)]
+    pub password: Option<String>,
+}
+
+fn parse_size(s: &str) -> Result<Vec2<usize>, String> {
+    let (x, y) = s
+        .split_once('x')
+        .ok_or_else(|| "Size should look like 20x15".to_owned())?;
+    Ok(vec2(
+        x.trim().parse().map_err(|_| "Invalid grid width")?,
+        y.trim().parse().map_err(|_| "Invalid grid height")?,
+    ))
+}
+
+fn parse_image(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(number) if (1..=RoomConfig::BUILTIN_IMAGES).contains(&number) => Ok(number),
+        _ => Err(format!(
+            "Image should be a number from 1 to {}",
+            RoomConfig::BUILTIN_IMAGES
+        )),
+    }
+}
+
+/// Reports a problem with the command line or the files it points to and exits,
+/// a backtrace would not tell anything more
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
+
+fn load_json<T: for<'de> Deserialize<'de>>(path: &std::path::Path) -> T {
+    let file = std::fs::File::open(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("Failed to open {path:?}: {e}")));
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .unwrap_or_else(|e| exit_with_error(&format!("Failed to parse {path:?}: {e}")))
+}
+
+/// Width over height of a builtin image, read from the file without loading the assets
+fn builtin_image_aspect(index: usize) -> Option<f32> {
+    let path = run_dir()
+        .join("assets")
+        .join("images")
+        .join(format!("{}.png", index + 1));
+    let (width, height) = image::image_dimensions(path).ok()?;
+    Some(width as f32 / height as f32)
 }
 
 fn main() {
@@ -42,6 +125,24 @@ fn main() {
     geng::setup_panic_handler();
     let mut opt: Opt = program_args::parse();
 
+    #[cfg(not(target_arch = "wasm32"))]
+    let config = match opt
+        .config
+        .clone()
+        .or_else(|| std::env::var_os("JIGSAW_CONFIG").map(std::path::PathBuf::from))
+    {
+        Some(path) => ClientConfig::load(&path).unwrap_or_else(|e| exit_with_error(&e)),
+        None => ClientConfig::default(),
+    };
+    #[cfg(target_arch = "wasm32")]
+    let config = ClientConfig::default();
+    opt.connect = opt.connect.or_else(|| config.connect.clone());
+    #[cfg(target_arch = "wasm32")]
+    {
+        opt.room = opt.room.or_else(util::room_from_url);
+    }
+    opt.name = opt.name.or_else(|| config.name.clone());
+
     if opt.connect.is_none() && opt.server.is_none() {
         if cfg!(target_arch = "wasm32") {
             opt.connect = Some(
@@ -55,13 +156,23 @@ fn main() {
         }
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    let server_config: server::Config = match &opt.server_config {
+        Some(path) => load_json(path),
+        None => default(),
+    };
+
     if opt.server.is_some() && opt.connect.is_none() {
         #[cfg(not(target_arch = "wasm32"))]
-        geng::net::Server::new(server::App::new(), opt.server.as_deref().unwrap()).run();
+        geng::net::Server::new(
+            server::App::new(server_config).with_console(),
+            opt.server.as_deref().unwrap(),
+        )
+        .run();
     } else {
         #[cfg(not(target_arch = "wasm32"))]
         let server = if let Some(addr) = &opt.server {
-            let server = geng::net::Server::new(server::App::new(), addr);
+            let server = geng::net::Server::new(server::App::new(server_config), addr);
             let server_handle = server.handle();
             let server_thread = std::thread::spawn(move || {
                 server.run();
@@ -76,18 +187,50 @@ fn main() {
             target_ui_resolution: Some(vec2(800.0, 600.0)),
             ..default()
         });
-        if let Some(config) = &opt.room_config {
-            let config: RoomConfig =
-                serde_json::from_reader(std::fs::File::open(config).unwrap()).unwrap();
+        Settings::load(&config).window.apply(&geng);
+        let room_config = if let Some(config) = &opt.room_config {
+            Some(load_json(config))
+        } else if opt.create {
+            Some(RoomConfig {
+                seed: opt.seed.unwrap_or_else(|| thread_rng().gen()),
+                size: opt.size.unwrap_or(vec2(6, 5)),
+                image: ImageSource::Builtin(opt.image.map_or(0, |number| number - 1)),
+                rotation: Rotation::Off,
+                public: false,
+                edge_style: EdgeStyle::Classic,
+                tiling: Tiling::Grid,
+                password: opt.password.clone(),
+                max_players: None,
+                lock_placed: false,
+                snap_distance: RoomConfig::default_snap_distance(),
+                race: false,
+                physics: false,
+                hard_mode: false,
+                custom_name: None,
+                image_aspect: None,
+            })
+        } else {
+            None
+        };
+        if let Some(mut config) = room_config {
+            if let ImageSource::Builtin(index) = config.image {
+                config.image_aspect = config.image_aspect.or_else(|| builtin_image_aspect(index));
+            }
             futures::executor::block_on(async {
                 let mut con: Connection =
                     geng::net::client::connect(opt.connect.as_deref().unwrap()).await;
                 con.send(ClientMessage::CreateRoom(config));
-                match con.next().await {
-                    Some(ServerMessage::RoomCreated(name)) => {
-                        opt.room = Some(name);
+                let reply = util::wait_for_reply(&mut con, |message| match message {
+                    ServerMessage::RoomCreated(name) => Some(Ok(name)),
+                    ServerMessage::RoomRejected(reason) => Some(Err(reason)),
+                    _ => None,
+                });
+                match reply.await {
+                    Some(Ok(name)) => opt.room = Some(name),
+                    Some(Err(reason)) => {
+                        exit_with_error(&format!("Failed to create room: {reason}"))
                     }
-                    _ => unreachable!(),
+                    None => exit_with_error("Disconnected from server"),
                 }
             });
         }
@@ -103,6 +246,8 @@ fn main() {
                                 opt.connect.as_deref().unwrap(),
                                 room,
                                 None,
+                                opt.password.clone(),
+                                &config,
                             )) as Box<dyn geng::State>
                         }),
                     ),
@@ -115,13 +260,15 @@ fn main() {
                         opt.connect.as_deref().unwrap(),
                         room,
                         opt.name.clone(),
+                        opt.password.clone(),
+                        &config,
                     ),
                 );
             }
         } else {
             geng::run(
                 &geng,
-                main_menu::run(&geng, opt.connect.as_deref().unwrap()),
+                main_menu::run(&geng, opt.connect.as_deref().unwrap(), &config),
             );
         }
 
@@ -1,35 +1,429 @@
 use super::*;
 
+/// Approximate piece counts to choose from, the grid is fit to the image
+const PIECE_COUNTS: [usize; 10] = [12, 30, 60, 120, 252, 500, 750, 1000, 1500, 2000];
+
+/// Preset room options for players who don't want to tune them one by one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    const ALL: [Self; 4] = [Self::Easy, Self::Medium, Self::Hard, Self::Expert];
+
+    fn name(self) -> String {
+        tr(match self {
+            Self::Easy => "menu.easy",
+            Self::Medium => "menu.medium",
+            Self::Hard => "menu.hard",
+            Self::Expert => "menu.expert",
+        })
+    }
+    /// Piece count, snap distance and rotation of the preset
+    fn options(self) -> (usize, f32, Rotation) {
+        match self {
+            Self::Easy => (30, 0.35, Rotation::Off),
+            Self::Medium => (120, RoomConfig::default_snap_distance(), Rotation::Off),
+            Self::Hard => (500, 0.15, Rotation::Half),
+            Self::Expert => (1000, 0.1, Rotation::Full),
+        }
+    }
+    fn apply(self, config: &mut RoomConfig) {
+        let (pieces, snap_distance, rotation) = self.options();
+        config.size.x = pieces;
+        config.snap_distance = snap_distance;
+        config.rotation = rotation;
+    }
+    /// Preset matching the current options, if any
+    fn of(config: &RoomConfig) -> Option<Self> {
+        Self::ALL.into_iter().find(|difficulty| {
+            difficulty.options() == (config.size.x, config.snap_distance, config.rotation)
+        })
+    }
+}
+
 struct ConfigScreen {
     assets: Rc<Assets>,
     geng: Geng,
     config: RoomConfig,
     addr: String,
+    client_config: ClientConfig,
+    theme: UiTheme,
     transition: Option<geng::Transition>,
     texture: ugli::Texture,
+    creating: Option<util::Task<Result<String, String>>>,
+    /// Room from the previous session to jump back into
+    last_room: Option<LastRoom>,
+    /// Waiting for the server to pick a room to quick play in
+    quick_playing: Option<util::Task<Result<String, String>>>,
+    error: Option<String>,
+    /// Image chosen by the user instead of a bundled one
+    custom_image: Option<(CustomImage, ugli::Texture)>,
+    name: String,
+    name_typing: bool,
+    image_url: String,
+    url_typing: bool,
+    /// Seed as typed by the user, parsed when creating the room
+    seed: String,
+    seed_typing: bool,
+    /// Password for the room, empty for none
+    password: String,
+    password_typing: bool,
+    /// Name for the room instead of a generated code, may be empty
+    room_name: String,
+    room_name_typing: bool,
+    /// Download of the image at the url, along with that url
+    loading_url: Option<util::Task<(String, Result<Vec<u8>, String>)>>,
+    /// Contents of a file dropped onto the page
+    #[cfg(target_arch = "wasm32")]
+    dropped_file: Rc<RefCell<Option<Vec<u8>>>>,
+    /// Text pasted into the page
+    #[cfg(target_arch = "wasm32")]
+    pasted_text: Rc<RefCell<Option<String>>>,
+    /// Page listeners filling [Self::dropped_file] and [Self::pasted_text],
+    /// removed along with the screen
+    #[cfg(target_arch = "wasm32")]
+    _listeners: (
+        WindowListener<web_sys::DragEvent>,
+        WindowListener<web_sys::DragEvent>,
+        WindowListener<web_sys::ClipboardEvent>,
+    ),
+}
+
+enum CustomImage {
+    /// Contents of a local file, uploaded to the server
+    File(Vec<u8>),
+    Url(String),
 }
 
 impl ConfigScreen {
-    fn new(geng: &Geng, assets: Rc<Assets>, addr: &str) -> Self {
+    fn new(geng: &Geng, assets: Rc<Assets>, addr: &str, client_config: ClientConfig) -> Self {
+        let seed: u64 = thread_rng().gen();
         let texture = generate_background(geng, &assets);
+        #[cfg(target_arch = "wasm32")]
+        let dropped_file = Rc::new(RefCell::new(None));
+        #[cfg(target_arch = "wasm32")]
+        let (dragover, drop) = listen_for_dropped_files(&dropped_file);
+        #[cfg(target_arch = "wasm32")]
+        let pasted_text = Rc::new(RefCell::new(None));
+        #[cfg(target_arch = "wasm32")]
+        let paste = listen_for_paste(&pasted_text);
+        let settings = Settings::load(&client_config);
         Self {
             assets,
             addr: addr.to_owned(),
+            client_config,
+            theme: settings.ui_theme,
             geng: geng.clone(),
             config: RoomConfig {
-                seed: thread_rng().gen(),
+                seed,
                 size: vec2(30, 1), // LUL
-                image: 0,
+                image: ImageSource::Builtin(0),
+                rotation: Rotation::Off,
+                public: false,
+                edge_style: EdgeStyle::Classic,
+                tiling: Tiling::Grid,
+                password: None,
+                max_players: None,
+                lock_placed: false,
+                snap_distance: RoomConfig::default_snap_distance(),
+                race: false,
+                physics: false,
+                hard_mode: false,
+                custom_name: None,
+                image_aspect: None,
             },
             transition: None,
             texture,
+            creating: None,
+            quick_playing: None,
+            last_room: settings.last_room,
+            error: None,
+            custom_image: None,
+            name: settings.name,
+            name_typing: false,
+            image_url: String::new(),
+            url_typing: false,
+            seed: seed.to_string(),
+            seed_typing: false,
+            password: String::new(),
+            password_typing: false,
+            room_name: String::new(),
+            room_name_typing: false,
+            loading_url: None,
+            #[cfg(target_arch = "wasm32")]
+            dropped_file,
+            #[cfg(target_arch = "wasm32")]
+            pasted_text,
+            #[cfg(target_arch = "wasm32")]
+            _listeners: (dragover, drop, paste),
+        }
+    }
+
+    fn image(&self) -> &ugli::Texture {
+        match &self.custom_image {
+            Some((_, texture)) => texture,
+            None => match self.config.image {
+                ImageSource::Builtin(index) => &self.assets.images[index],
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    fn image_aspect(&self) -> f32 {
+        let image_size = self.image().size().map(|x| x as f32);
+        image_size.x / image_size.y
+    }
+
+    /// Grid with about the selected number of pieces that keeps them square for the image
+    fn grid_size(&self) -> Vec2<usize> {
+        jigsaw::Jigsaw::grid_size(self.config.size.x, self.image_aspect())
+    }
+
+    /// Room created by quick play when there is nobody to join
+    fn quick_play_config(&self) -> RoomConfig {
+        let mut rng = thread_rng();
+        let index = rng.gen_range(0..self.assets.images.len());
+        let image_size = self.assets.images[index].size().map(|x| x as f32);
+        let mut config = RoomConfig {
+            seed: rng.gen(),
+            size: vec2(0, 1),
+            image: ImageSource::Builtin(index),
+            rotation: Rotation::Off,
+            public: true,
+            edge_style: EdgeStyle::Classic,
+            tiling: Tiling::Grid,
+            password: None,
+            max_players: None,
+            lock_placed: false,
+            snap_distance: RoomConfig::default_snap_distance(),
+            race: false,
+            physics: false,
+            hard_mode: false,
+            custom_name: None,
+            image_aspect: Some(image_size.x / image_size.y),
+        };
+        Difficulty::Medium.apply(&mut config);
+        config.size = jigsaw::Jigsaw::grid_size(config.size.x, image_size.x / image_size.y);
+        config
+    }
+
+    fn password(&self) -> Option<String> {
+        Some(self.password.clone()).filter(|password| !password.is_empty())
+    }
+
+    fn set_custom_image(&mut self, source: CustomImage, data: &[u8]) {
+        match assets::texture_from_bytes(
+            self.geng.ugli(),
+            data,
+            self.client_config.max_texture_size(),
+        ) {
+            Ok(texture) => {
+                self.error = None;
+                self.custom_image = Some((source, texture));
+            }
+            Err(e) => {
+                warn!("Failed to load image: {}", e);
+                self.error = Some(tr("menu.error_load_image"));
+            }
         }
     }
 }
 
+/// Callback for an event on the page window, stops listening when dropped
+#[cfg(target_arch = "wasm32")]
+struct WindowListener<E: wasm_bindgen::convert::FromWasmAbi + 'static> {
+    event: &'static str,
+    callback: wasm_bindgen::closure::Closure<dyn FnMut(E)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<E: wasm_bindgen::convert::FromWasmAbi + 'static> WindowListener<E> {
+    fn new(event: &'static str, callback: wasm_bindgen::closure::Closure<dyn FnMut(E)>) -> Self {
+        use wasm_bindgen::JsCast;
+        web_sys::window()
+            .unwrap()
+            .add_event_listener_with_callback(event, callback.as_ref().unchecked_ref())
+            .unwrap();
+        Self { event, callback }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<E: wasm_bindgen::convert::FromWasmAbi + 'static> Drop for WindowListener<E> {
+    fn drop(&mut self) {
+        use wasm_bindgen::JsCast;
+        if let Some(window) = web_sys::window() {
+            if let Err(e) = window.remove_event_listener_with_callback(
+                self.event,
+                self.callback.as_ref().unchecked_ref(),
+            ) {
+                warn!("Failed to remove the {} listener: {:?}", self.event, e);
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn listen_for_dropped_files(
+    dropped_file: &Rc<RefCell<Option<Vec<u8>>>>,
+) -> (
+    WindowListener<web_sys::DragEvent>,
+    WindowListener<web_sys::DragEvent>,
+) {
+    use wasm_bindgen::closure::Closure;
+    let dragover = Closure::<dyn FnMut(web_sys::DragEvent)>::new(|event: web_sys::DragEvent| {
+        // Required for the drop event to fire
+        event.prevent_default();
+    });
+    let drop = Closure::<dyn FnMut(web_sys::DragEvent)>::new({
+        let dropped_file = dropped_file.clone();
+        move |event: web_sys::DragEvent| {
+            event.prevent_default();
+            let file = match event
+                .data_transfer()
+                .and_then(|data| data.files())
+                .and_then(|files| files.get(0))
+            {
+                Some(file) => file,
+                None => return,
+            };
+            let dropped_file = dropped_file.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let buffer = wasm_bindgen_futures::JsFuture::from(file.array_buffer())
+                    .await
+                    .unwrap();
+                *dropped_file.borrow_mut() = Some(js_sys::Uint8Array::new(&buffer).to_vec());
+            });
+        }
+    });
+    (
+        WindowListener::new("dragover", dragover),
+        WindowListener::new("drop", drop),
+    )
+}
+
+#[cfg(target_arch = "wasm32")]
+fn listen_for_paste(
+    pasted_text: &Rc<RefCell<Option<String>>>,
+) -> WindowListener<web_sys::ClipboardEvent> {
+    use wasm_bindgen::closure::Closure;
+    let paste = Closure::<dyn FnMut(web_sys::ClipboardEvent)>::new({
+        let pasted_text = pasted_text.clone();
+        move |event: web_sys::ClipboardEvent| {
+            if let Some(text) = event
+                .clipboard_data()
+                .and_then(|data| data.get_data("text").ok())
+            {
+                *pasted_text.borrow_mut() = Some(text);
+            }
+        }
+    });
+    WindowListener::new("paste", paste)
+}
+
 impl geng::State for ConfigScreen {
+    fn update(&mut self, _delta_time: f64) {
+        #[cfg(target_arch = "wasm32")]
+        if let Some(data) = self.dropped_file.borrow_mut().take() {
+            self.set_custom_image(CustomImage::File(data.clone()), &data);
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(text) = self.pasted_text.borrow_mut().take() {
+            if self.name_typing {
+                self.name.push_str(text.trim());
+                while self.name.len() > 15 {
+                    self.name.pop();
+                }
+            } else if self.url_typing {
+                self.image_url.push_str(text.trim());
+            } else if self.seed_typing {
+                self.seed
+                    .extend(text.chars().filter(|c| c.is_ascii_digit()));
+                while self.seed.len() > 20 {
+                    self.seed.pop();
+                }
+            } else if self.password_typing {
+                self.password.push_str(text.trim());
+                while self.password.len() > 64 {
+                    self.password.pop();
+                }
+            } else if self.room_name_typing {
+                self.room_name.push_str(text.trim());
+                while self.room_name.len() > RoomConfig::MAX_NAME_LEN {
+                    self.room_name.pop();
+                }
+            }
+        }
+        if let Some((url, result)) = self.loading_url.as_mut().and_then(|task| task.poll()) {
+            self.loading_url = None;
+            match result {
+                Ok(data) => self.set_custom_image(CustomImage::Url(url), &data),
+                Err(e) => {
+                    warn!("Failed to download {:?}: {}", url, e);
+                    self.error = Some(tr("menu.error_download_image"));
+                }
+            }
+        }
+        if let Some(result) = self.creating.as_mut().and_then(|task| task.poll()) {
+            self.creating = None;
+            match result {
+                Ok(room) => {
+                    info!("room: {:?}", room);
+                    util::set_room_url(Some(&room));
+                    self.transition = Some(geng::Transition::Switch(Box::new(
+                        invite_screen::InviteScreen::new(
+                            &self.geng,
+                            &self.addr,
+                            &room,
+                            Some(self.name.clone()),
+                            self.password(),
+                            &self.client_config,
+                        ),
+                    )));
+                }
+                Err(reason) => {
+                    warn!("Failed to create room: {}", reason);
+                    self.error = Some(reason);
+                }
+            }
+        }
+        if let Some(result) = self.quick_playing.as_mut().and_then(|task| task.poll()) {
+            self.quick_playing = None;
+            match result {
+                Ok(room) => {
+                    info!("quick play room: {:?}", room);
+                    util::set_room_url(Some(&room));
+                    let mut settings = Settings::load(&self.client_config);
+                    settings.name = self.name.clone();
+                    settings.save();
+                    self.transition = Some(geng::Transition::Switch(Box::new(game::run(
+                        &self.geng,
+                        &self.addr,
+                        &room,
+                        Some(self.name.clone()).filter(|name| !name.is_empty()),
+                        None,
+                        &self.client_config,
+                    ))));
+                }
+                Err(reason) => {
+                    warn!("Failed to quick play: {}", reason);
+                    self.error = Some(reason);
+                }
+            }
+        }
+    }
     fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
-        ugli::clear(framebuffer, Some(Rgba::BLACK), None, None);
+        ugli::clear(
+            framebuffer,
+            Some(self.theme.palette().background),
+            None,
+            None,
+        );
         let texture = &self.texture;
         let framebuffer_size = framebuffer.size().map(|x| x as f32);
         let size = texture.size().map(|x| x as f32);
@@ -43,107 +437,541 @@ impl geng::State for ConfigScreen {
                 texture,
             ),
         );
+
+        // Preview of the selected image to the left of the menu
+        let image = self.image();
+        let image_size = image.size().map(|x| x as f32);
+        let max_size = framebuffer_size * vec2(0.25, 0.5);
+        let size = image_size * (max_size.x / image_size.x).min(max_size.y / image_size.y);
+        let preview = AABB::point(framebuffer_size * vec2(0.17, 0.5)).extend_symmetric(size * 0.5);
+        self.geng.draw_2d(
+            framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::Quad::new(preview.extend_uniform(4.0), Rgba::WHITE),
+        );
+        self.geng.draw_2d(
+            framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::TexturedQuad::new(preview, image),
+        );
     }
     fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
         use geng::ui::*;
-        let play_button = Button::new(cx, "PLAY");
-        if play_button.was_clicked() {
+        let play_button = button::Button::new(
+            cx,
+            self.theme,
+            &if self.creating.is_some() {
+                "...".to_owned()
+            } else {
+                tr("menu.play")
+            },
+        );
+        if play_button.was_clicked() && self.creating.is_none() {
+            self.error = None;
+            match self.seed.parse() {
+                Ok(seed) => self.config.seed = seed,
+                Err(_) => {
+                    self.config.seed = thread_rng().gen();
+                    self.seed = self.config.seed.to_string();
+                }
+            }
+            // Settings screen could have changed the rest since this screen was created
+            let mut settings = Settings::load(&self.client_config);
+            settings.name = self.name.clone();
+            settings.save();
             let future = {
-                let geng = self.geng.clone();
                 let addr = self.addr.clone();
                 let mut config = self.config.clone();
-                config.size = (1..=config.size.x)
-                    .filter_map(|x| {
-                        if config.size.x % x == 0 {
-                            Some(vec2(x, config.size.x / x))
-                        } else {
-                            None
-                        }
-                    })
-                    .min_by_key(|size| {
-                        let aspect = size.x as f64 / size.y as f64;
-                        let image = &self.assets.images[config.image];
-                        let image_aspect = image.size().x as f64 / image.size().y as f64;
-                        r64(aspect - image_aspect).abs()
-                    })
-                    .unwrap();
+                let mut upload = None;
+                match &self.custom_image {
+                    Some((CustomImage::File(data), _)) => upload = Some(data.clone()),
+                    Some((CustomImage::Url(url), _)) => {
+                        config.image = ImageSource::Url(url.clone())
+                    }
+                    None => {}
+                }
+                config.size = self.grid_size();
+                config.image_aspect = Some(self.image_aspect());
+                config.password = self.password();
+                config.custom_name = Some(self.room_name.clone()).filter(|name| !name.is_empty());
                 async move {
                     let mut con: Connection = geng::net::client::connect(&addr).await;
+                    if let Some(data) = upload {
+                        con.send(ClientMessage::UploadImage(data));
+                        let uploaded = util::wait_for_reply(&mut con, |message| match message {
+                            ServerMessage::ImageUploaded(image) => Some(Ok(image)),
+                            ServerMessage::RoomRejected(reason) => Some(Err(reason)),
+                            _ => None,
+                        });
+                        match uploaded.await {
+                            Some(Ok(image)) => config.image = ImageSource::Uploaded(image),
+                            Some(Err(reason)) => return Err(reason),
+                            None => return Err(tr("message.disconnected")),
+                        }
+                    }
                     con.send(ClientMessage::CreateRoom(config));
-                    let room = match con.next().await {
-                        Some(ServerMessage::RoomCreated(name)) => name,
-                        _ => unreachable!(),
-                    };
-                    info!("room: {:?}", room);
-                    #[cfg(target_arch = "wasm32")]
-                    web_sys::window()
-                        .unwrap()
-                        .location()
-                        .set_href(&format!("?room={}", room))
-                        .unwrap();
-                    game::run(&geng, &addr, &room, None)
+                    util::wait_for_reply(&mut con, |message| match message {
+                        ServerMessage::RoomCreated(name) => Some(Ok(name)),
+                        ServerMessage::RoomRejected(reason) => Some(Err(reason)),
+                        _ => None,
+                    })
+                    .await
+                    .unwrap_or_else(|| Err(tr("message.disconnected")))
                 }
             };
-            let state =
-                geng::LoadingScreen::new(&self.geng, geng::EmptyLoadingScreen, future, |state| {
-                    state
-                });
-            self.transition = Some(geng::Transition::Switch(Box::new(state)));
+            self.creating = Some(util::Task::new(future));
+        }
+        let quick_play_button = button::Button::new(
+            cx,
+            self.theme,
+            &if self.quick_playing.is_some() {
+                "...".to_owned()
+            } else {
+                tr("menu.quick_play")
+            },
+        );
+        if quick_play_button.was_clicked() && self.quick_playing.is_none() {
+            self.error = None;
+            let addr = self.addr.clone();
+            let config = self.quick_play_config();
+            self.quick_playing = Some(util::Task::new(async move {
+                let mut con: Connection = geng::net::client::connect(&addr).await;
+                con.send(ClientMessage::QuickPlay(config));
+                util::wait_for_reply(&mut con, |message| match message {
+                    ServerMessage::QuickPlayRoom(name) => Some(Ok(name)),
+                    ServerMessage::RoomRejected(reason) => Some(Err(reason)),
+                    _ => None,
+                })
+                .await
+                .unwrap_or_else(|| Err(tr("message.disconnected")))
+            }));
+        }
+        let mut continue_button = None;
+        if let Some(last) = &self.last_room {
+            let button = button::Button::new(
+                cx,
+                self.theme,
+                &tr_with("menu.continue", &[("room", &last.room)]),
+            );
+            if button.was_clicked() {
+                util::set_room_url(Some(&last.room));
+                self.transition = Some(geng::Transition::Switch(Box::new(game::run(
+                    &self.geng,
+                    &last.addr,
+                    &last.room,
+                    Some(self.name.clone()).filter(|name| !name.is_empty()),
+                    None,
+                    &self.client_config,
+                ))));
+            }
+            continue_button = Some(button);
+        }
+        let browse_button = button::Button::new(cx, self.theme, &tr("menu.browse_rooms"));
+        if browse_button.was_clicked() {
+            self.transition = Some(geng::Transition::Push(Box::new(lobby::Lobby::new(
+                &self.geng,
+                &self.addr,
+                self.client_config.clone(),
+                Some(self.name.clone()).filter(|name| !name.is_empty()),
+            ))));
+        }
+        let settings_button = button::Button::new(cx, self.theme, &tr("menu.settings"));
+        if settings_button.was_clicked() {
+            self.transition = Some(geng::Transition::Push(Box::new(SettingsScreen::new(
+                &self.geng,
+                &self.client_config,
+            ))));
         }
-        let image_button = Button::new(cx, &format!("Image: Harvest #{}", self.config.image + 1));
+        let image_button = button::Button::new(
+            cx,
+            self.theme,
+            &match (&self.custom_image, &self.config.image) {
+                (Some((CustomImage::File(_), _)), _) => tr("menu.image_custom"),
+                (Some((CustomImage::Url(_), _)), _) => tr("menu.image_url"),
+                (None, ImageSource::Builtin(index)) => tr_with(
+                    "menu.image_builtin",
+                    &[("number", &(index + 1).to_string())],
+                ),
+                (None, _) => unreachable!(),
+            },
+        );
         if image_button.was_clicked() {
-            self.config.image = (self.config.image + 1) % self.assets.images.len();
+            if self.custom_image.take().is_none() {
+                if let ImageSource::Builtin(index) = &mut self.config.image {
+                    *index = (*index + 1) % self.assets.images.len();
+                }
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let upload_button = button::Button::new(cx, self.theme, &tr("menu.upload_image"));
+        #[cfg(not(target_arch = "wasm32"))]
+        if upload_button.was_clicked() {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter(&tr("menu.image_filter"), &["png", "jpg", "jpeg"])
+                .pick_file()
+            {
+                match std::fs::read(&path) {
+                    Ok(data) => self.set_custom_image(CustomImage::File(data.clone()), &data),
+                    Err(e) => {
+                        warn!("Failed to read {:?}: {}", path, e);
+                        self.error = Some(tr("menu.error_read_image"));
+                    }
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let upload_button = Text::new(
+            tr("menu.drop_image"),
+            self.geng.default_font().clone(),
+            cx.theme().text_size,
+            self.theme.palette().text,
+        );
+        let load_url_button = button::Button::new(
+            cx,
+            self.theme,
+            &if self.loading_url.is_some() {
+                "...".to_owned()
+            } else {
+                tr("menu.load_url")
+            },
+        );
+        if load_url_button.was_clicked() && self.loading_url.is_none() && !self.image_url.is_empty()
+        {
+            let url = self.image_url.clone();
+            let download = util::fetch_bytes(&url);
+            self.loading_url = Some(util::Task::new(async move { (url, download.await) }));
+        }
+        let reroll_button = button::Button::new(cx, self.theme, &tr("menu.reroll"));
+        if reroll_button.was_clicked() {
+            self.seed = thread_rng().gen::<u64>().to_string();
+        }
+        let grid_size = self.grid_size();
+        let name_input = TextInput::new(cx, &mut self.name, 15);
+        self.name_typing = *name_input.capture;
+        let url_input =
+            TextInput::new(cx, &mut self.image_url, 2048).placeholder(tr("menu.url_placeholder"));
+        self.url_typing = *url_input.capture;
+        let seed_label = Text::new(
+            tr("menu.seed"),
+            self.geng.default_font().clone(),
+            cx.theme().text_size,
+            self.theme.palette().text,
+        );
+        let seed_input =
+            TextInput::new(cx, &mut self.seed, 20).placeholder(tr("menu.seed_placeholder"));
+        self.seed_typing = *seed_input.capture;
+        let password_input =
+            TextInput::new(cx, &mut self.password, 64).placeholder(tr("menu.password_placeholder"));
+        self.password_typing = *password_input.capture;
+        let room_name_input = TextInput::new(cx, &mut self.room_name, RoomConfig::MAX_NAME_LEN)
+            .placeholder(tr("menu.room_name_placeholder"));
+        self.room_name_typing = *room_name_input.capture;
+        let difficulty_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr_with(
+                "menu.pieces",
+                &[
+                    ("count", &self.config.size.x.to_string()),
+                    ("width", &grid_size.x.to_string()),
+                    ("height", &grid_size.y.to_string()),
+                ],
+            ),
+        );
+        let current_preset = Difficulty::of(&self.config);
+        let [easy, medium, hard, expert] = Difficulty::ALL.map(|difficulty| {
+            let button = button::Button::new(
+                cx,
+                self.theme,
+                &if current_preset == Some(difficulty) {
+                    format!("[{}]", difficulty.name())
+                } else {
+                    difficulty.name()
+                },
+            );
+            if button.was_clicked() {
+                difficulty.apply(&mut self.config);
+            }
+            button
+        });
+        let fewer_pieces = button::Button::new(cx, self.theme, "-");
+        let more_pieces = button::Button::new(cx, self.theme, "+");
+        let option = PIECE_COUNTS
+            .iter()
+            .position(|&x| x == self.config.size.x)
+            .unwrap();
+        if fewer_pieces.was_clicked() && option > 0 {
+            self.config.size.x = PIECE_COUNTS[option - 1];
+        }
+        if more_pieces.was_clicked() && option + 1 < PIECE_COUNTS.len() {
+            self.config.size.x = PIECE_COUNTS[option + 1];
         }
-        let difficulty_button =
-            Button::new(cx, &format!("Difficulty: {} pieces", self.config.size.x));
         if difficulty_button.was_clicked() {
-            let options = [30, 120, 500, 1000];
-            self.config.size.x = options[(options
-                .iter()
-                .position(|x| *x == self.config.size.x)
-                .unwrap()
-                + 1)
-                % options.len()];
+            self.config.size.x = PIECE_COUNTS[(option + 1) % PIECE_COUNTS.len()];
         }
+        let rotation_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(match self.config.rotation {
+                Rotation::Off => "menu.rotation_off",
+                Rotation::Half => "menu.rotation_half",
+                Rotation::Full => "menu.rotation_full",
+            }),
+        );
+        if rotation_button.was_clicked() {
+            self.config.rotation = match self.config.rotation {
+                Rotation::Off => Rotation::Half,
+                Rotation::Half => Rotation::Full,
+                Rotation::Full => Rotation::Off,
+            };
+        }
+        let edge_style_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(match self.config.edge_style {
+                EdgeStyle::Classic => "menu.cut_classic",
+                EdgeStyle::Angular => "menu.cut_angular",
+                EdgeStyle::Straight => "menu.cut_straight",
+            }),
+        );
+        if edge_style_button.was_clicked() {
+            self.config.edge_style = match self.config.edge_style {
+                EdgeStyle::Classic => EdgeStyle::Angular,
+                EdgeStyle::Angular => EdgeStyle::Straight,
+                EdgeStyle::Straight => EdgeStyle::Classic,
+            };
+        }
+        let tiling_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(match self.config.tiling {
+                Tiling::Grid => "menu.shape_squares",
+                Tiling::Hexagonal => "menu.shape_hexagons",
+            }),
+        );
+        if tiling_button.was_clicked() {
+            self.config.tiling = match self.config.tiling {
+                Tiling::Grid => Tiling::Hexagonal,
+                Tiling::Hexagonal => Tiling::Grid,
+            };
+        }
+        let max_players_button = button::Button::new(
+            cx,
+            self.theme,
+            &match self.config.max_players {
+                Some(max) => tr_with("menu.max_players", &[("count", &max.to_string())]),
+                None => tr("menu.max_players_unlimited"),
+            },
+        );
+        if max_players_button.was_clicked() {
+            self.config.max_players = match self.config.max_players {
+                None => Some(2),
+                Some(max) if max < 16 => Some(max * 2),
+                Some(_) => None,
+            };
+        }
+        let lock_placed_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(if self.config.lock_placed {
+                "menu.lock_placed_on"
+            } else {
+                "menu.lock_placed_off"
+            }),
+        );
+        if lock_placed_button.was_clicked() {
+            self.config.lock_placed = !self.config.lock_placed;
+        }
+        let snap_slider = slider::Slider::new(
+            cx,
+            tr_with(
+                "menu.snap_distance",
+                &[(
+                    "value",
+                    &format!("{:.0}", self.config.snap_distance * 100.0),
+                )],
+            ),
+            self.config.snap_distance as f64,
+            *RoomConfig::SNAP_DISTANCE_RANGE.start() as f64
+                ..=*RoomConfig::SNAP_DISTANCE_RANGE.end() as f64,
+        );
+        if let Some(value) = snap_slider.get_change() {
+            self.config.snap_distance = value as f32;
+        }
+        let race_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(if self.config.race {
+                "menu.race_on"
+            } else {
+                "menu.race_off"
+            }),
+        );
+        if race_button.was_clicked() {
+            self.config.race = !self.config.race;
+        }
+        let physics_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(if self.config.physics {
+                "menu.physics_on"
+            } else {
+                "menu.physics_off"
+            }),
+        );
+        if physics_button.was_clicked() {
+            self.config.physics = !self.config.physics;
+        }
+        let hard_mode_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(if self.config.hard_mode {
+                "menu.hard_mode_on"
+            } else {
+                "menu.hard_mode_off"
+            }),
+        );
+        if hard_mode_button.was_clicked() {
+            self.config.hard_mode = !self.config.hard_mode;
+        }
+        let public_button = button::Button::new(
+            cx,
+            self.theme,
+            &tr(if self.config.public {
+                "menu.public_on"
+            } else {
+                "menu.public_off"
+            }),
+        );
+        if public_button.was_clicked() {
+            self.config.public = !self.config.public;
+        }
+        let error = Text::new(
+            self.error.as_deref().unwrap_or(""),
+            self.geng.default_font().clone(),
+            cx.theme().text_size,
+            Rgba::RED,
+        );
         (
-            image_button.center(),
-            difficulty_button.center(),
-            play_button.center(),
+            (
+                name_input.center(),
+                image_button.center(),
+                upload_button.center(),
+                (url_input, load_url_button).row().center(),
+            )
+                .column()
+                .center(),
+            (
+                (easy, medium, hard, expert).row().center(),
+                (fewer_pieces, difficulty_button, more_pieces)
+                    .row()
+                    .center(),
+                rotation_button.center(),
+                edge_style_button.center(),
+                tiling_button.center(),
+                max_players_button.center(),
+                lock_placed_button.center(),
+                snap_slider.center(),
+            )
+                .column()
+                .center(),
+            (
+                race_button.center(),
+                physics_button.center(),
+                hard_mode_button.center(),
+                public_button.center(),
+                (seed_label, seed_input, reroll_button).row().center(),
+                room_name_input.center(),
+                password_input.center(),
+            )
+                .column()
+                .center(),
+            column(
+                std::iter::once(play_button.center().boxed())
+                    .chain(continue_button.map(|button| button.center().boxed()))
+                    .chain([
+                        quick_play_button.center().boxed(),
+                        browse_button.center().boxed(),
+                        settings_button.center().boxed(),
+                        error.center().boxed(),
+                    ])
+                    .collect(),
+            )
+            .center(),
         )
             .column()
             .center()
             .boxed()
     }
+    fn handle_event(&mut self, event: geng::Event) {
+        if let geng::Event::KeyDown {
+            key: geng::Key::F11,
+        } = event
+        {
+            let mut settings = Settings::load(&self.client_config);
+            settings.window.toggle_fullscreen(&self.geng);
+            settings.save();
+            return;
+        }
+        let digits_only = self.seed_typing;
+        let (text, max_len) = if self.name_typing {
+            (&mut self.name, 15)
+        } else if self.url_typing {
+            (&mut self.image_url, 2048)
+        } else if self.seed_typing {
+            (&mut self.seed, 20)
+        } else if self.password_typing {
+            (&mut self.password, 64)
+        } else if self.room_name_typing {
+            (&mut self.room_name, RoomConfig::MAX_NAME_LEN)
+        } else {
+            return;
+        };
+        if let geng::Event::KeyDown { key } = event {
+            text_input::type_key(&self.geng, text, key, max_len);
+            if digits_only {
+                text.retain(|c| c.is_ascii_digit());
+            }
+        }
+    }
     fn transition(&mut self) -> Option<geng::Transition> {
         self.transition.take()
     }
 }
 
-pub fn run(geng: &Geng, addr: &str) -> impl geng::State {
+pub fn run(geng: &Geng, addr: &str, client_config: &ClientConfig) -> impl geng::State {
+    let loading_screen = loading_screen::LoadingScreen::new(geng, client_config);
     let future = {
         let geng = geng.clone();
         let addr = addr.to_owned();
+        let client_config = client_config.clone();
         async move {
-            ConfigScreen::new(
-                &geng,
-                geng::LoadAsset::load(&geng, &run_dir().join("assets"))
-                    .await
-                    .unwrap(),
-                &addr,
-            )
+            let assets: Rc<Assets> = geng::LoadAsset::load(&geng, &run_dir().join("assets"))
+                .await
+                .unwrap();
+            translations::init(&assets, &client_config);
+            ConfigScreen::new(&geng, assets, &addr, client_config)
         }
     };
-    geng::LoadingScreen::new(geng, geng::EmptyLoadingScreen, future, |state| state)
+    geng::LoadingScreen::new(geng, loading_screen, future, |state| state)
 }
 
 fn generate_background(geng: &Geng, assets: &Assets) -> ugli::Texture {
-    let mut jigsaw = jigsaw::Jigsaw::generate(geng.ugli(), 0, vec2(40.0, 30.0), vec2(40, 30));
+    let mut jigsaw = jigsaw::Jigsaw::generate(
+        geng.ugli(),
+        0,
+        vec2(40.0, 30.0),
+        vec2(40, 30),
+        Tiling::Grid,
+        EdgeStyle::Classic,
+    );
     let camera = geng::Camera2d {
         center: vec2(40.0, 30.0) / 2.0,
         rotation: 0.0,
         fov: 40.0 / 2.0,
     };
 
+    let shared_mesh = &jigsaw.mesh;
     let mesh: Vec<jigsaw::JigsawVertex> = jigsaw
         .tiles
         .iter_mut()
@@ -151,11 +979,13 @@ fn generate_background(geng: &Geng, assets: &Assets) -> ugli::Texture {
             tile.interpolated
                 .teleport(tile.interpolated.get() * 1.05, Vec2::ZERO);
             let matrix = tile.matrix();
-            tile.mesh.iter().map(move |&(mut v)| {
-                let pos = matrix * v.a_pos.extend(1.0);
-                v.a_pos = pos.xy() / pos.z;
-                v
-            })
+            shared_mesh[tile.mesh_range.clone()]
+                .iter()
+                .map(move |&(mut v)| {
+                    let pos = matrix * v.a_pos.extend(1.0);
+                    v.a_pos = pos.xy() / pos.z;
+                    v
+                })
         })
         .collect();
     let mesh = ugli::VertexBuffer::new_dynamic(geng.ugli(), mesh);
@@ -178,6 +1008,7 @@ fn generate_background(geng: &Geng, assets: &Assets) -> ugli::Texture {
                 ugli::uniforms! {
                     u_model_matrix: Mat3::identity(),
                     u_texture: &jigsaw_texture,
+                    u_alpha: 1.0,
                 },
                 geng::camera2d_uniforms(&camera, framebuffer.size().map(|x| x as f32)),
             ),
@@ -187,30 +1018,31 @@ fn generate_background(geng: &Geng, assets: &Assets) -> ugli::Texture {
                 ..Default::default()
             },
         );
+        let mut line_vertices = Vec::new();
         for tile in tiles {
             let matrix = tile.matrix();
-            let outline_color = Rgba::BLACK;
-            let depth = 0.0;
-            ugli::draw(
-                framebuffer,
-                &assets.shaders.outline,
-                ugli::DrawMode::LineLoop { line_width: 1.0 },
-                &tile.outline,
-                (
-                    ugli::uniforms! {
-                        u_model_matrix: matrix,
-                        u_color: outline_color,
-                        u_depth: depth,
-                    },
-                    geng::camera2d_uniforms(&camera, framebuffer.size().map(|x| x as f32)),
-                ),
-                ugli::DrawParameters {
-                    blend_mode: Some(ugli::BlendMode::default()),
-                    depth_func: Some(ugli::DepthFunc::LessOrEqual),
-                    ..Default::default()
-                },
-            );
+            let outline = &tile.outline;
+            for i in 0..outline.len() {
+                let [a, b] = [outline[i], outline[(i + 1) % outline.len()]].map(|v| {
+                    let pos = matrix * v.a_pos.extend(1.0);
+                    pos.xy() / pos.z
+                });
+                lines::push_line(&mut line_vertices, a, b, 0.0, 1.0, Rgba::BLACK);
+            }
         }
+        ugli::draw(
+            framebuffer,
+            &assets.shaders.outline,
+            ugli::DrawMode::Triangles,
+            &ugli::VertexBuffer::new_dynamic(geng.ugli(), line_vertices),
+            (
+                ugli::uniforms! {
+                    u_framebuffer_size: framebuffer.size().map(|x| x as f32),
+                },
+                geng::camera2d_uniforms(&camera, framebuffer.size().map(|x| x as f32)),
+            ),
+            lines::draw_parameters(),
+        );
     }
 
     texture
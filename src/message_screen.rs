@@ -0,0 +1,61 @@
+use super::*;
+
+/// Explains why a room could not be joined
+pub struct MessageScreen {
+    geng: Geng,
+    addr: String,
+    client_config: ClientConfig,
+    theme: UiTheme,
+    message: String,
+    transition: Option<geng::Transition>,
+}
+
+impl MessageScreen {
+    pub fn new(geng: &Geng, addr: &str, client_config: ClientConfig, message: &str) -> Self {
+        Self {
+            geng: geng.clone(),
+            addr: addr.to_owned(),
+            theme: Settings::load(&client_config).ui_theme,
+            client_config,
+            message: message.to_owned(),
+            transition: None,
+        }
+    }
+}
+
+impl geng::State for MessageScreen {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(
+            framebuffer,
+            Some(self.theme.palette().background),
+            None,
+            None,
+        );
+    }
+    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
+        use geng::ui::*;
+        let message = Text::new(
+            &self.message,
+            self.geng.default_font().clone(),
+            cx.theme().text_size,
+            self.theme.palette().text,
+        );
+        let menu_button = button::Button::new(cx, self.theme, &tr("common.back_to_menu"));
+        if menu_button.was_clicked() {
+            // Drop the room from the url so that reloading does not try it again
+            util::set_room_url(None);
+            self.transition = Some(geng::Transition::Switch(Box::new(main_menu::run(
+                &self.geng,
+                &self.addr,
+                &self.client_config,
+            ))));
+        }
+        (message.center(), menu_button.center())
+            .column()
+            .center()
+            .boxed()
+    }
+    fn transition(&mut self) -> Option<geng::Transition> {
+        self.transition.take()
+    }
+}
@@ -0,0 +1,109 @@
+use super::*;
+
+/// Asks for the password of a room before joining it
+pub struct PasswordPrompt {
+    geng: Geng,
+    addr: String,
+    room: String,
+    name: Option<String>,
+    client_config: ClientConfig,
+    theme: UiTheme,
+    password: String,
+    password_typing: bool,
+    /// Whether a wrong password was entered before
+    wrong: bool,
+    transition: Option<geng::Transition>,
+}
+
+impl PasswordPrompt {
+    pub fn new(
+        geng: &Geng,
+        addr: &str,
+        room: &str,
+        name: Option<String>,
+        client_config: ClientConfig,
+        wrong: bool,
+    ) -> Self {
+        Self {
+            geng: geng.clone(),
+            addr: addr.to_owned(),
+            room: room.to_owned(),
+            name,
+            theme: Settings::load(&client_config).ui_theme,
+            client_config,
+            password: String::new(),
+            password_typing: false,
+            wrong,
+            transition: None,
+        }
+    }
+}
+
+impl geng::State for PasswordPrompt {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(
+            framebuffer,
+            Some(self.theme.palette().background),
+            None,
+            None,
+        );
+    }
+    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
+        use geng::ui::*;
+        let title = Text::new(
+            tr(if self.wrong {
+                "password.wrong"
+            } else {
+                "password.title"
+            }),
+            self.geng.default_font().clone(),
+            cx.theme().text_size,
+            if self.wrong {
+                Rgba::RED
+            } else {
+                self.theme.palette().text
+            },
+        );
+        let join_button = button::Button::new(cx, self.theme, &tr("lobby.join"));
+        if join_button.was_clicked() && !self.password.is_empty() {
+            self.transition = Some(geng::Transition::Switch(Box::new(game::run(
+                &self.geng,
+                &self.addr,
+                &self.room,
+                self.name.clone(),
+                Some(self.password.clone()),
+                &self.client_config,
+            ))));
+        }
+        let back_button = button::Button::new(cx, self.theme, &tr("common.back_to_menu"));
+        if back_button.was_clicked() {
+            util::set_room_url(None);
+            self.transition = Some(geng::Transition::Switch(Box::new(main_menu::run(
+                &self.geng,
+                &self.addr,
+                &self.client_config,
+            ))));
+        }
+        let password_input =
+            TextInput::new(cx, &mut self.password, 64).placeholder(tr("password.placeholder"));
+        self.password_typing = *password_input.capture;
+        (
+            title.center(),
+            password_input.center(),
+            (join_button, back_button).row().center(),
+        )
+            .column()
+            .center()
+            .boxed()
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        if let geng::Event::KeyDown { key } = event {
+            if self.password_typing {
+                text_input::type_key(&self.geng, &mut self.password, key, 64);
+            }
+        }
+    }
+    fn transition(&mut self) -> Option<geng::Transition> {
+        self.transition.take()
+    }
+}
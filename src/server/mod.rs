@@ -0,0 +1,1904 @@
+use super::*;
+
+mod rate_limit;
+mod storage;
+
+use rate_limit::*;
+use storage::*;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct IdGen {
+    next_id: u64,
+}
+
+impl IdGen {
+    fn new() -> Self {
+        Self { next_id: 0 }
+    }
+    fn gen(&mut self) -> Id {
+        let id = Id(self.next_id);
+        self.next_id += 1;
+        id
+    }
+}
+
+const MAX_URL_LENGTH: usize = 2048;
+const MAX_LISTED_ROOMS: usize = 20;
+const MAX_PASSWORD_LENGTH: usize = 64;
+/// Seconds a player has to wait between pings
+const MIN_PING_INTERVAL: f64 = 0.5;
+/// How much further apart than the snap distance connected tiles may be,
+/// a group snapping at several tiles at once only lines up exactly at one of them
+const CONNECT_TOLERANCE: f32 = 2.0;
+/// Seconds a grab may be dated back to win over a grab of another player that arrived earlier
+const MAX_LAG_COMPENSATION: f64 = 0.3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Maximum total number of pieces in a room
+    pub max_pieces: usize,
+    /// Maximum number of pieces along either side of the puzzle
+    pub max_grid_size: usize,
+    /// Approximate amount of memory (in bytes) rooms are allowed to use
+    /// before inactive ones get evicted to disk
+    pub memory_budget: usize,
+    /// Directory where rooms are stored
+    pub storage_path: std::path::PathBuf,
+    /// Seconds between saving rooms that changed, so that they survive a restart
+    pub autosave_interval: f64,
+    /// Seconds without players after which a room is removed, `None` keeps rooms forever
+    pub abandoned_timeout: Option<f64>,
+    /// Whether removed rooms are moved to the archive instead of being deleted
+    pub archive_abandoned: bool,
+    /// Seconds between status lines in the log, `None` disables them
+    pub status_interval: Option<f64>,
+    /// Message of the day shown to players joining a room
+    pub motd: Option<String>,
+    /// Maximum size of an uploaded image in bytes
+    pub max_image_size: usize,
+    pub rate_limits: RateLimits,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_pieces: 2000,
+            max_grid_size: 100,
+            memory_budget: 256 * 1024 * 1024,
+            storage_path: "rooms".into(),
+            autosave_interval: 60.0,
+            abandoned_timeout: Some(7.0 * 24.0 * 60.0 * 60.0),
+            archive_abandoned: true,
+            status_interval: Some(5.0 * 60.0),
+            motd: None,
+            max_image_size: 4 * 1024 * 1024,
+            rate_limits: default(),
+        }
+    }
+}
+
+impl Config {
+    fn validate(&self, room: &RoomConfig) -> Result<(), String> {
+        if room.size.x == 0 || room.size.y == 0 {
+            return Err("Puzzle must have at least one piece".to_owned());
+        }
+        if room.size.x > self.max_grid_size || room.size.y > self.max_grid_size {
+            return Err(format!(
+                "Puzzle can be at most {max}x{max} pieces, requested {}x{}",
+                room.size.x,
+                room.size.y,
+                max = self.max_grid_size,
+            ));
+        }
+        if room
+            .password
+            .as_ref()
+            .map_or(false, |password| password.len() > MAX_PASSWORD_LENGTH)
+        {
+            return Err("Password is too long".to_owned());
+        }
+        if !RoomConfig::SNAP_DISTANCE_RANGE.contains(&room.snap_distance) {
+            return Err("Snap distance is out of range".to_owned());
+        }
+        match room.image_aspect {
+            Some(aspect)
+                if (1.0 / RoomConfig::MAX_IMAGE_ASPECT..=RoomConfig::MAX_IMAGE_ASPECT)
+                    .contains(&aspect) => {}
+            _ => return Err("Image aspect ratio is missing or out of range".to_owned()),
+        }
+        if room.max_players == Some(0) {
+            return Err("Room must allow at least one player".to_owned());
+        }
+        if let ImageSource::Builtin(index) = room.image {
+            if index >= RoomConfig::BUILTIN_IMAGES {
+                return Err("There is no such bundled image".to_owned());
+            }
+        }
+        if let ImageSource::Url(url) = &room.image {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err("Image url must start with http:// or https://".to_owned());
+            }
+            if url.len() > MAX_URL_LENGTH {
+                return Err("Image url is too long".to_owned());
+            }
+        }
+        let pieces = room.tile_count();
+        if pieces > self.max_pieces {
+            return Err(format!(
+                "Puzzle can have at most {} pieces, requested {}",
+                self.max_pieces, pieces,
+            ));
+        }
+        Ok(())
+    }
+    fn validate_image(&self, data: &[u8]) -> Result<(), String> {
+        if data.len() > self.max_image_size {
+            return Err(format!(
+                "Image can be at most {} KB, uploaded {} KB",
+                self.max_image_size / 1024,
+                data.len() / 1024,
+            ));
+        }
+        match image::guess_format(data) {
+            Ok(image::ImageFormat::Png | image::ImageFormat::Jpeg) => {}
+            _ => return Err("Only png and jpeg images are supported".to_owned()),
+        }
+        // Only reads the header, clients would fail to load anything larger
+        let (width, height) = image::io::Reader::new(std::io::Cursor::new(data))
+            .with_guessed_format()
+            .map_err(|e| e.to_string())?
+            .into_dimensions()
+            .map_err(|e| e.to_string())?;
+        if width as u64 * height as u64 > RoomConfig::MAX_IMAGE_PIXELS {
+            return Err(format!("Image is too large: {width}x{height}"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(HasId)]
+struct Player {
+    id: Id,
+    room: String,
+    name: String,
+    color: Rgba<f32>,
+    cursor: CursorSkin,
+    sender: Box<dyn geng::net::Sender<ServerMessage>>,
+    compression: bool,
+    outgoing: Vec<ServerMessage>,
+    /// Whether others have been notified about this player joining
+    announced: bool,
+    /// Last cursor position, sent to players joining later
+    pos: Option<CompactPos>,
+    /// Whether [Self::pos] changed since the last tick
+    pos_changed: bool,
+    /// Cursor position as of the last tick
+    ticked_pos: Option<CompactPos>,
+    /// Area the player sees, everything is sent until it is reported
+    viewport: Option<AABB<f32>>,
+    /// Changed tiles not sent yet because they are out of view, with positions the player knows
+    stale_tiles: HashMap<usize, Vec2<f32>>,
+    /// Same as [Self::stale_tiles] for cursors of other players
+    stale_cursors: HashMap<Id, Vec2<f32>>,
+    /// Tiles held by the player along with the grab offsets, one per group
+    grabbed: Vec<(usize, Vec2<f32>)>,
+    /// Id the player had before reconnecting
+    resumed_from: Option<Id>,
+    /// Sent by the client in the handshake to be recognized in later sessions
+    token: Option<String>,
+    /// When the player last pinged, to limit how often they can
+    last_ping: Option<std::time::Instant>,
+    /// When the unanswered [ServerMessage::LatencyProbe] was sent
+    probe_sent: Option<std::time::Instant>,
+    /// Smoothed round trip time in seconds
+    latency: Option<f64>,
+    limits: ConnectionLimits,
+    /// Sent too many messages, everything from them is ignored now
+    kicked: bool,
+}
+
+impl Player {
+    /// Queues a message to be sent on the next tick
+    fn send(&mut self, message: ServerMessage) {
+        self.outgoing.push(message);
+    }
+    fn flush(&mut self) {
+        let message = match self.outgoing.len() {
+            0 => return,
+            1 => self.outgoing.pop().unwrap(),
+            _ => ServerMessage::Batch(std::mem::take(&mut self.outgoing)),
+        };
+        let message = if self.compression {
+            message.pack()
+        } else {
+            message
+        };
+        self.sender.send(message);
+    }
+}
+
+/// Stands in for the sender of a kicked player, messages go nowhere
+struct Disconnected;
+
+impl geng::net::Sender<ServerMessage> for Disconnected {
+    fn send(&mut self, _message: ServerMessage) {}
+}
+
+/// Seconds since the unix epoch, unlike [std::time::Instant] it is kept between restarts
+fn unix_time() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |time| time.as_secs_f64())
+}
+
+/// Random position for a loose tile
+fn spawn_pos(rng: &mut impl Rng) -> Vec2<f32> {
+    let bounds = AABB::ZERO.extend_uniform(3.0);
+    let spawn_area = AABB::point(bounds.bottom_left()).extend_positive(vec2(bounds.width(), 3.0));
+    vec2(
+        rng.gen_range(spawn_area.x_min..=spawn_area.x_max),
+        rng.gen_range(spawn_area.y_min..=spawn_area.y_max),
+    )
+}
+
+fn create_room() -> String {
+    rand::distributions::DistString::sample_string(
+        &rand::distributions::Alphanumeric,
+        &mut thread_rng(),
+        16,
+    )
+}
+
+struct State {
+    config: Config,
+    storage: Box<dyn Storage>,
+    id_gen: IdGen,
+    players: Collection<Player>,
+    rooms: Collection<Room>,
+    started: std::time::Instant,
+    /// Messages handled since [Self::last_status]
+    messages: usize,
+    last_status: std::time::Instant,
+    last_player_stats: std::time::Instant,
+    last_storage_sweep: std::time::Instant,
+    /// Images uploaded lately, kept for the room they are about to be used in
+    recent_uploads: HashMap<u64, std::time::Instant>,
+}
+
+#[derive(HasId)]
+struct Room {
+    #[has_id(id)]
+    name: String,
+    tiles: Vec<TileState>,
+    config: RoomConfig,
+    last_active: std::time::Instant,
+    /// Tiles changed since the last broadcast
+    dirty_tiles: HashSet<usize>,
+    last_keyframe: std::time::Instant,
+    last_saved: std::time::Instant,
+    /// Whether anything changed since the room was last saved
+    unsaved: bool,
+    /// Connections made by each player since the last tick
+    recent_connections: HashMap<Id, usize>,
+    /// Largest group size that was reported in the activity feed
+    reported_group: usize,
+    /// Size of [Self::largest_group] as of the last tick anything connected
+    largest: usize,
+    /// When the room was loaded into memory
+    loaded: std::time::Instant,
+    /// Seconds the room has been played before it was loaded
+    elapsed_before: f64,
+    /// Seconds it took to complete the puzzle
+    completion_time: Option<f64>,
+    /// Everyone who has ever joined the room
+    player_stats: HashMap<Id, PlayerStats>,
+    /// Id each player with a token last had in the room, to give them back their stats
+    tokens: HashMap<String, Id>,
+    /// Player who created the room, unknown for rooms restored from disk
+    host: Option<Id>,
+    /// Tile positions as of the last tick
+    ticked_pos: Vec<Vec2<f32>>,
+    /// Home positions and neighbors of tiles in a puzzle of size 1x1
+    layout: Vec<(Vec2<f32>, Vec<usize>)>,
+    /// Room time held tiles were grabbed at, as seen by the players who grabbed them
+    grab_times: HashMap<usize, f64>,
+}
+
+impl Room {
+    fn new(name: String, config: RoomConfig, tiles: Vec<TileState>) -> Self {
+        let mut room = Self {
+            ticked_pos: tiles.iter().map(|tile| tile.pos).collect(),
+            layout: jigsaw::layout(
+                config.seed,
+                vec2(1.0, 1.0),
+                config.size,
+                config.tiling,
+                config.edge_style,
+            ),
+            name,
+            tiles,
+            config,
+            last_active: std::time::Instant::now(),
+            dirty_tiles: HashSet::new(),
+            last_keyframe: std::time::Instant::now(),
+            last_saved: std::time::Instant::now(),
+            unsaved: false,
+            recent_connections: HashMap::new(),
+            reported_group: 1,
+            loaded: std::time::Instant::now(),
+            elapsed_before: 0.0,
+            completion_time: None,
+            player_stats: HashMap::new(),
+            tokens: HashMap::new(),
+            host: None,
+            grab_times: HashMap::new(),
+            largest: 0,
+        };
+        room.largest = room.largest_group();
+        room
+    }
+
+    /// Seconds since the room was created
+    fn elapsed(&self) -> f64 {
+        self.elapsed_before + self.loaded.elapsed().as_secs_f64()
+    }
+
+    fn stats(&self) -> RoomStats {
+        let mut players: Vec<PlayerStats> = self.player_stats.values().cloned().collect();
+        players.sort_by(|a, b| b.pieces.cmp(&a.pieces));
+        RoomStats {
+            time: self.completion_time.unwrap_or_else(|| self.elapsed()),
+            players,
+        }
+    }
+
+    /// Whether the id was last had in this room by the player with the token,
+    /// a claim to an id is only honoured when this holds
+    fn owned_by(&self, previous: Id, token: Option<&str>) -> bool {
+        token.map_or(false, |token| self.tokens.get(token) == Some(&previous))
+    }
+
+    /// All tiles connected to the given one
+    fn group(&self, tile: usize) -> Vec<usize> {
+        let mut visited = HashSet::new();
+        visited.insert(tile);
+        let mut stack = vec![tile];
+        while let Some(tile) = stack.pop() {
+            for &other in &self.tiles[tile].connections {
+                if visited.insert(other) {
+                    stack.push(other);
+                }
+            }
+        }
+        visited.into_iter().collect()
+    }
+
+    /// Whether the player may grab the tile, nobody else may be holding any of its group.
+    /// Tiles in the tray have to be taken out with [ClientMessage::SetInTray] first
+    fn can_grab(&self, tile: usize, player: Id) -> bool {
+        match self.tiles.get(tile) {
+            Some(state) if !state.locked && !state.in_tray => self
+                .group(tile)
+                .into_iter()
+                .all(|other| self.tiles[other].grabbed_by.map_or(true, |id| id == player)),
+            _ => false,
+        }
+    }
+
+    /// Holder of the group who grabbed it after the given time, the grab goes to an earlier one
+    fn later_grab(&self, tile: usize, player: Id, time: f64) -> Option<Id> {
+        let state = self.tiles.get(tile)?;
+        if state.locked || state.in_tray {
+            return None;
+        }
+        let group = self.group(tile);
+        let (holder, grabbed_at) = group.iter().find_map(|&other| {
+            let holder = self.tiles[other].grabbed_by.filter(|&id| id != player)?;
+            Some((holder, *self.grab_times.get(&other)?))
+        })?;
+        (grabbed_at > time).then_some(holder)
+    }
+
+    /// Checks that the tiles are neighbors lying where they would snap together
+    fn check_connection(&self, a: usize, b: usize) -> Result<(), &'static str> {
+        if a == b || a >= self.tiles.len() || b >= self.tiles.len() {
+            return Err("invalid tiles");
+        }
+        if !self.layout[a].1.contains(&b) {
+            return Err("not neighbors");
+        }
+        if self.tiles[a].connections.contains(&b) {
+            return Err("already connected");
+        }
+        let (tile_a, tile_b) = (&self.tiles[a], &self.tiles[b]);
+        if tile_a.in_tray || tile_b.in_tray {
+            return Err("tile in tray");
+        }
+        if tile_a.rotation != tile_b.rotation {
+            return Err("different rotation");
+        }
+        // Offset between homes in the 1x1 layout, scaled to the puzzle size
+        let expected = self.layout[a].0 - self.layout[b].0;
+        let delta = util::rotate_quarters(tile_a.pos - tile_b.pos, 4 - tile_b.rotation % 4);
+        let tile_height = PUZZLE_HEIGHT / self.config.size.y as f32;
+        let tolerance = self.config.effective_snap_distance() * tile_height * CONNECT_TOLERANCE;
+        let width = match self.config.image_aspect {
+            Some(aspect) => aspect * PUZZLE_HEIGHT,
+            // Rooms created before the aspect was sent: take whatever width fits best
+            None if expected.x != 0.0 => {
+                (delta.x / expected.x).clamp(0.0, PUZZLE_HEIGHT * RoomConfig::MAX_IMAGE_ASPECT)
+            }
+            None => 0.0,
+        };
+        let expected = vec2(expected.x * width, expected.y * PUZZLE_HEIGHT);
+        if (delta - expected).len() > tolerance {
+            return Err("too far apart");
+        }
+        Ok(())
+    }
+
+    /// Size of the assembled puzzle, unknown for rooms created before the aspect was sent
+    fn puzzle_size(&self) -> Option<Vec2<f32>> {
+        let aspect = self.config.image_aspect?;
+        Some(vec2(aspect * PUZZLE_HEIGHT, PUZZLE_HEIGHT))
+    }
+
+    /// Area tiles can be moved in, the table the clients draw around the frame
+    fn bounds(&self) -> AABB<f32> {
+        let size = self.puzzle_size().unwrap_or(vec2(
+            PUZZLE_HEIGHT * RoomConfig::MAX_IMAGE_ASPECT,
+            PUZZLE_HEIGHT,
+        ));
+        AABB::ZERO.extend_symmetric(size / 2.0).extend_uniform(3.0)
+    }
+
+    /// Whether every tile of the group lies at its spot inside the frame, unrotated
+    fn group_placed(&self, tile: usize) -> bool {
+        // Without the size there is no telling where the spots are
+        let size = match self.puzzle_size() {
+            Some(size) => size,
+            None => return false,
+        };
+        let frame_bottom_left = -size / 2.0;
+        let tile_size = size / self.config.size.map(|x| x as f32);
+        let snap_distance =
+            self.config.effective_snap_distance() * partial_min(tile_size.x, tile_size.y);
+        self.group(tile).into_iter().all(|tile| {
+            let state = &self.tiles[tile];
+            let home = frame_bottom_left + self.layout[tile].0 * size;
+            state.rotation == 0 && !state.in_tray && (state.pos - home).len() <= snap_distance
+        })
+    }
+
+    fn lock_group(&mut self, tile: usize) {
+        for tile in self.group(tile) {
+            self.tiles[tile].locked = true;
+            self.dirty_tiles.insert(tile);
+        }
+    }
+
+    fn largest_group(&self) -> usize {
+        let mut visited = vec![false; self.tiles.len()];
+        let mut largest = 0;
+        for start in 0..self.tiles.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut size = 0;
+            let mut stack = vec![start];
+            while let Some(tile) = stack.pop() {
+                size += 1;
+                for &other in &self.tiles[tile].connections {
+                    if !visited[other] {
+                        visited[other] = true;
+                        stack.push(other);
+                    }
+                }
+            }
+            largest = largest.max(size);
+        }
+        largest
+    }
+
+    fn saved(&self) -> SavedRoom {
+        SavedRoom {
+            config: self.config.clone(),
+            tiles: self.tiles.clone(),
+            completion_time: self.completion_time,
+            elapsed: self.elapsed(),
+            player_stats: self.player_stats.clone(),
+            tokens: self.tokens.clone(),
+            last_active: Some(unix_time() - self.last_active.elapsed().as_secs_f64()),
+        }
+    }
+
+    /// Rough estimate of how much memory this room occupies
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.name.len()
+            + self
+                .tiles
+                .iter()
+                .map(|tile| {
+                    std::mem::size_of::<TileState>()
+                        + tile.connections.capacity() * std::mem::size_of::<usize>()
+                })
+                .sum::<usize>()
+            + self
+                .layout
+                .iter()
+                .map(|(_, neighbors)| {
+                    std::mem::size_of::<(Vec2<f32>, Vec<usize>)>()
+                        + neighbors.capacity() * std::mem::size_of::<usize>()
+                })
+                .sum::<usize>()
+    }
+}
+
+impl State {
+    fn new(config: Config) -> Self {
+        let mut state = Self {
+            storage: Box::new(JsonFiles::new(&config.storage_path)),
+            config,
+            id_gen: IdGen::new(),
+            players: Collection::new(),
+            rooms: Collection::new(),
+            started: std::time::Instant::now(),
+            messages: 0,
+            last_status: std::time::Instant::now(),
+            last_player_stats: std::time::Instant::now(),
+            last_storage_sweep: std::time::Instant::now(),
+            recent_uploads: HashMap::new(),
+        };
+        state.restore_saved();
+        state
+    }
+    /// Loads rooms saved before a restart, as many as fit into the memory budget
+    fn restore_saved(&mut self) {
+        for name in self.storage.rooms() {
+            let total: usize = self.rooms.iter().map(|room| room.memory_usage()).sum();
+            if total >= self.config.memory_budget {
+                break;
+            }
+            self.restore_room(&name);
+        }
+        info!("Restored {} rooms", self.rooms.len());
+    }
+    /// Removes rooms nobody has been active in for [Config::abandoned_timeout]
+    fn remove_abandoned(&mut self) {
+        let timeout = match self.config.abandoned_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        let abandoned: Vec<String> = self
+            .rooms
+            .iter()
+            .filter(|room| {
+                room.last_active.elapsed().as_secs_f64() > timeout
+                    && self.player_count(&room.name) == 0
+            })
+            .map(|room| room.name.clone())
+            .collect();
+        for name in abandoned {
+            let room = self.rooms.remove(&name).unwrap();
+            self.remove_stored(&name, &room.saved());
+        }
+    }
+    /// Same as [Self::remove_abandoned] for rooms that are only on disk,
+    /// evicted or not restored after a restart
+    fn remove_abandoned_stored(&mut self) {
+        let timeout = match self.config.abandoned_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        for name in self.storage.rooms() {
+            if self.rooms.get(&name).is_some() {
+                continue;
+            }
+            let mut saved = match self.storage.load(&name) {
+                Some(saved) => saved,
+                None => continue,
+            };
+            match saved.last_active {
+                Some(time) if unix_time() - time > timeout => self.remove_stored(&name, &saved),
+                Some(_) => {}
+                // Saved before the time was kept, counts down from now
+                None => {
+                    saved.last_active = Some(unix_time());
+                    if let Err(e) = self.storage.save(&name, &saved) {
+                        error!("Failed to save room {:?}: {}", name, e);
+                    }
+                }
+            }
+        }
+    }
+    fn remove_stored(&mut self, name: &str, room: &SavedRoom) {
+        info!("Removing abandoned room {:?}", name);
+        let result = if self.config.archive_abandoned {
+            self.storage.archive(name, room)
+        } else {
+            self.storage.remove(name)
+        };
+        if let Err(e) = result {
+            error!("Failed to remove room {:?}: {}", name, e);
+        }
+    }
+    /// Looks through the rooms and images on disk, at most once per [STORAGE_SWEEP_INTERVAL]
+    fn sweep_storage(&mut self) {
+        if self.last_storage_sweep.elapsed().as_secs_f64() < STORAGE_SWEEP_INTERVAL {
+            return;
+        }
+        self.last_storage_sweep = std::time::Instant::now();
+        self.remove_abandoned_stored();
+        self.remove_unused_images();
+    }
+    /// Deletes uploaded images that no room uses, live, stored or archived
+    fn remove_unused_images(&mut self) {
+        self.recent_uploads
+            .retain(|_, time| time.elapsed().as_secs_f64() < STORAGE_SWEEP_INTERVAL);
+        let uploaded = |config: &RoomConfig| match config.image {
+            ImageSource::Uploaded(image) => Some(image),
+            _ => None,
+        };
+        let mut used: HashSet<u64> = self.recent_uploads.keys().copied().collect();
+        used.extend(self.rooms.iter().filter_map(|room| uploaded(&room.config)));
+        for name in self.storage.rooms() {
+            used.extend(
+                self.storage
+                    .load(&name)
+                    .and_then(|room| uploaded(&room.config)),
+            );
+        }
+        used.extend(
+            self.storage
+                .archived()
+                .iter()
+                .filter_map(|room| uploaded(&room.config)),
+        );
+        for image in self.storage.images() {
+            if !used.contains(&image) {
+                info!("Removing unused image {:016x}", image);
+                if let Err(e) = self.storage.remove_image(image) {
+                    error!("Failed to remove image {:016x}: {}", image, e);
+                }
+            }
+        }
+    }
+    /// Saves rooms that changed, at most once per [Config::autosave_interval]
+    fn autosave(&mut self) {
+        for room in &mut self.rooms {
+            if !room.unsaved
+                || room.last_saved.elapsed().as_secs_f64() < self.config.autosave_interval
+            {
+                continue;
+            }
+            room.unsaved = false;
+            room.last_saved = std::time::Instant::now();
+            if let Err(e) = self.storage.save(&room.name, &room.saved()) {
+                error!("Failed to save room {:?}: {}", room.name, e);
+            }
+        }
+    }
+    /// One line summary of the server load
+    fn status(&self) -> String {
+        let uptime = self.started.elapsed().as_secs();
+        format!(
+            "{} rooms ({} stored), {} players, {:.1} messages/s, uptime {}d {:02}:{:02}:{:02}",
+            self.rooms.len(),
+            self.storage.rooms().len(),
+            self.players.len(),
+            self.messages as f64 / self.last_status.elapsed().as_secs_f64().max(1.0),
+            uptime / 86400,
+            uptime / 3600 % 24,
+            uptime / 60 % 60,
+            uptime % 60,
+        )
+    }
+    /// Logs the status every [Config::status_interval]
+    fn report_status(&mut self) {
+        let interval = match self.config.status_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        if self.last_status.elapsed().as_secs_f64() < interval {
+            return;
+        }
+        info!("Status: {}", self.status());
+        self.messages = 0;
+        self.last_status = std::time::Instant::now();
+    }
+    /// Tells everyone how the others are doing and measures their ping
+    fn send_player_stats(&mut self) {
+        if self.last_player_stats.elapsed().as_secs_f64() < PLAYER_STATS_INTERVAL {
+            return;
+        }
+        self.last_player_stats = std::time::Instant::now();
+        for room in &self.rooms {
+            let stats: Vec<PlayerStatus> = self
+                .players
+                .iter()
+                .filter(|player| player.room == room.name)
+                .map(|player| PlayerStatus {
+                    id: player.id,
+                    pieces: room
+                        .player_stats
+                        .get(&player.id)
+                        .map_or(0, |stats| stats.pieces),
+                    ping: player
+                        .latency
+                        .map(|latency| (latency * 1000.0).round() as u32),
+                })
+                .collect();
+            if stats.is_empty() {
+                continue;
+            }
+            for player in &mut self.players {
+                if player.room == room.name {
+                    player.send(ServerMessage::PlayerStats(stats.clone()));
+                }
+            }
+        }
+        for room in &self.rooms {
+            let (code, team) = match RoomConfig::race_team(&room.name) {
+                Some(team) if room.config.race => team,
+                _ => continue,
+            };
+            let progress = room.largest as f32 / room.tiles.len() as f32;
+            for rival in 0..RoomConfig::TEAMS {
+                if rival != team {
+                    let rival = RoomConfig::team_room(code, rival);
+                    for player in &mut self.players {
+                        if player.room == rival {
+                            player.send(ServerMessage::RivalProgress(progress));
+                        }
+                    }
+                }
+            }
+        }
+        // Connections outside of rooms only wait for a single reply
+        for player in &mut self.players {
+            if player.probe_sent.is_none() && self.rooms.get(&player.room).is_some() {
+                player.probe_sent = Some(std::time::Instant::now());
+                player.send(ServerMessage::LatencyProbe);
+            }
+        }
+    }
+    /// Creates a room hosted by the player, returns its name or why the config was rejected
+    fn new_room(&mut self, host: Id, mut config: RoomConfig) -> Result<String, String> {
+        self.config
+            .validate(&config)
+            .and_then(|()| match config.image {
+                ImageSource::Uploaded(image) if !self.storage.has_image(image) => {
+                    Err("Uploaded image not found".to_owned())
+                }
+                _ => Ok(()),
+            })?;
+        let saved = self.storage.rooms();
+        let custom_name = config
+            .custom_name
+            .as_deref()
+            .map(RoomConfig::sanitize_name)
+            .filter(|name| !name.is_empty());
+        config.custom_name = custom_name.clone();
+        let name = match custom_name {
+            // Numbered like `name-2` when taken
+            Some(base) => (1..)
+                .map(|i| {
+                    if i == 1 {
+                        base.clone()
+                    } else {
+                        format!("{}-{}", base, i)
+                    }
+                })
+                .find(|name| !self.room_name_taken(name, &saved))
+                .unwrap(),
+            None => loop {
+                let name = create_room();
+                if !self.room_name_taken(&name, &saved) {
+                    break name;
+                }
+                warn!("Rng room name collision");
+            },
+        };
+        let names: Vec<String> = if config.race {
+            (0..RoomConfig::TEAMS)
+                .map(|team| RoomConfig::team_room(&name, team))
+                .collect()
+        } else {
+            vec![name.clone()]
+        };
+        let mut rng = thread_rng();
+        let tiles = (0..config.tile_count())
+            .map(|_| {
+                let pos = spawn_pos(&mut rng);
+                TileState {
+                    grabbed_by: None,
+                    pos,
+                    connections: Vec::new(),
+                    rotation: match config.rotation.step() {
+                        Some(step) => rng.gen_range(0..4 / step) * step,
+                        None => 0,
+                    },
+                    in_tray: true,
+                    locked: false,
+                }
+            })
+            .collect::<Vec<_>>();
+        // Every team races on an identical copy of the board
+        for room_name in names {
+            let mut room = Room::new(room_name, config.clone(), tiles.clone());
+            room.host = Some(host);
+            room.unsaved = true;
+            self.rooms.insert(room);
+        }
+        self.enforce_memory_budget(Some(&name));
+        Ok(name)
+    }
+    /// Whether a room under this name, or a race under this code, would clash
+    /// with a room in memory or one of the saved ones
+    fn room_name_taken(&self, name: &str, saved: &[String]) -> bool {
+        std::iter::once(name.to_owned())
+            .chain((0..RoomConfig::TEAMS).map(|team| RoomConfig::team_room(name, team)))
+            .any(|name| self.rooms.get(&name).is_some() || saved.contains(&name))
+    }
+    fn player_count(&self, room: &str) -> usize {
+        self.players
+            .iter()
+            .filter(|player| player.room == room)
+            .count()
+    }
+    /// Picks the board for a player joining a race by its code:
+    /// the team the player was on before, or the one with fewer players
+    fn race_room(&mut self, code: &str, id: Id) -> Option<String> {
+        let teams: Vec<String> = (0..RoomConfig::TEAMS)
+            .map(|team| RoomConfig::team_room(code, team))
+            .collect();
+        for team in &teams {
+            self.restore_room(team);
+        }
+        if teams.iter().any(|team| self.rooms.get(team).is_none()) {
+            return None;
+        }
+        let player = self.players.get(&id)?;
+        if let Some(previous) = player.resumed_from {
+            if let Some(team) = teams.iter().find(|team| {
+                let room = self.rooms.get(*team).unwrap();
+                room.owned_by(previous, player.token.as_deref())
+                    && room.player_stats.contains_key(&previous)
+            }) {
+                return Some(team.clone());
+            }
+        }
+        teams.into_iter().min_by_key(|team| self.player_count(team))
+    }
+    /// Team that finished its board first, if any
+    fn race_winner(&self, code: &str) -> Option<usize> {
+        (0..RoomConfig::TEAMS)
+            .filter_map(|team| {
+                let name = RoomConfig::team_room(code, team);
+                let time = match self.rooms.get(&name) {
+                    Some(room) => room.completion_time,
+                    None => self.storage.load(&name)?.completion_time,
+                };
+                Some((team, time?))
+            })
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(team, _)| team)
+    }
+    /// Evicts least recently active rooms without players to disk
+    /// until total memory usage fits into the budget, except for the room
+    /// (or the boards of the race) under the given name that a player is about to join
+    fn enforce_memory_budget(&mut self, keep: Option<&str>) {
+        let mut total: usize = self.rooms.iter().map(|room| room.memory_usage()).sum();
+        while total > self.config.memory_budget {
+            let name = match self
+                .rooms
+                .iter()
+                .filter(|room| self.player_count(&room.name) == 0)
+                .filter(|room| {
+                    keep.map_or(true, |keep| {
+                        room.name != keep
+                            && RoomConfig::race_team(&room.name).map(|(code, _)| code) != Some(keep)
+                    })
+                })
+                .min_by_key(|room| room.last_active)
+            {
+                Some(room) => room.name.clone(),
+                None => {
+                    warn!(
+                        "Memory budget exceeded ({} > {}) but no rooms can be evicted",
+                        total, self.config.memory_budget,
+                    );
+                    break;
+                }
+            };
+            let room = self.rooms.remove(&name).unwrap();
+            total -= room.memory_usage();
+            info!("Evicting room {:?} to disk", room.name);
+            if let Err(e) = self.storage.save(&room.name, &room.saved()) {
+                error!("Failed to save room {:?}: {}", room.name, e);
+            }
+        }
+    }
+    /// Brings an evicted room back into memory
+    fn restore_room(&mut self, name: &str) {
+        if self.rooms.get(&name.to_owned()).is_some() {
+            return;
+        }
+        // Names come from clients and end up in file paths
+        if !RoomConfig::is_valid_name(name) {
+            return;
+        }
+        if let Some(saved) = self.storage.load(name) {
+            info!("Restoring room {:?} from disk", name);
+            let mut room = Room::new(name.to_owned(), saved.config, saved.tiles);
+            room.completion_time = saved.completion_time;
+            room.elapsed_before = saved.elapsed;
+            room.player_stats = saved.player_stats;
+            room.tokens = saved.tokens;
+            // Keeps counting down to removal from when someone was last there
+            if let Some(time) = saved.last_active {
+                let idle = std::time::Duration::from_secs_f64((unix_time() - time).max(0.0));
+                room.last_active = room
+                    .last_active
+                    .checked_sub(idle)
+                    .unwrap_or(room.last_active);
+            }
+            self.rooms.insert(room);
+            self.enforce_memory_budget(Some(name));
+        }
+    }
+    /// Takes the player out of their room, releasing whatever they held
+    fn leave(&mut self, id: Id) {
+        let player = self.players.get_mut(&id).unwrap();
+        let room_name = std::mem::replace(&mut player.room, create_room());
+        player.grabbed.clear();
+        let announced = std::mem::replace(&mut player.announced, false);
+        let name = player.name.clone();
+        player.pos = None;
+        // Others see the tiles released with the next update
+        if let Some(room) = self.rooms.get_mut(&room_name) {
+            for (tile_id, tile) in room.tiles.iter_mut().enumerate() {
+                if tile.grabbed_by == Some(id) {
+                    tile.grabbed_by = None;
+                    room.grab_times.remove(&tile_id);
+                    room.dirty_tiles.insert(tile_id);
+                }
+            }
+        }
+        self.broadcast(&room_name, ServerMessage::PlayerDisconnected(id));
+        if announced {
+            self.broadcast(
+                &room_name,
+                ServerMessage::Activity(Activity::PlayerLeft { name }),
+            );
+        }
+    }
+    /// Cuts the player off: releases what they hold, tells them why and stops sending them anything,
+    /// the connection itself goes away when the client drops it
+    fn kick(&mut self, id: Id, reason: &str) {
+        let player = self.players.get_mut(&id).unwrap();
+        warn!("Kicking player {:?}: {}", player.name, reason);
+        player.kicked = true;
+        player.send(ServerMessage::Kicked(reason.to_owned()));
+        player.flush();
+        player.sender = Box::new(Disconnected);
+        self.leave(id);
+    }
+    fn broadcast(&mut self, room: &str, message: ServerMessage) {
+        for player in &mut self.players {
+            if player.room == room {
+                player.send(message.clone());
+            }
+        }
+    }
+    /// Sends a banner message to everyone in every room
+    fn announce(&mut self, text: &str) {
+        info!("Announcing {:?}", text);
+        // Menu and lobby connections are not showing a banner
+        for player in &mut self.players {
+            if self.rooms.get(&player.room).is_some() {
+                player.send(ServerMessage::Announcement(text.to_owned()));
+            }
+        }
+    }
+    fn tick(&mut self) {
+        let mut activity = Vec::new();
+        let mut completed = Vec::new();
+        for room in &mut self.rooms {
+            let connections = std::mem::take(&mut room.recent_connections);
+            let connected = !connections.is_empty();
+            if connected {
+                room.largest = room.largest_group();
+            }
+            for (player, pieces) in connections {
+                if let Some(player) = self.players.get(&player) {
+                    activity.push((
+                        room.name.clone(),
+                        Activity::PiecesConnected {
+                            name: player.name.clone(),
+                            pieces,
+                        },
+                    ));
+                }
+            }
+            // Checked even without new connections, a puzzle of one piece is complete from the start
+            let largest = room.largest;
+            let step = (room.tiles.len() / 10).max(2);
+            if largest == room.tiles.len() && room.completion_time.is_none() {
+                let time = room.elapsed();
+                info!("Room {:?} completed in {:.1}s", room.name, time);
+                room.completion_time = Some(time);
+                completed.push((room.name.clone(), room.stats()));
+            } else if connected && largest >= room.reported_group + step {
+                room.reported_group = largest;
+                activity.push((room.name.clone(), Activity::LargestGroup(largest)));
+            }
+        }
+        for (room, activity) in activity {
+            self.broadcast(&room, ServerMessage::Activity(activity));
+        }
+        for (room, stats) in completed {
+            self.broadcast(&room, ServerMessage::PuzzleCompleted(stats));
+            let race = self.rooms.get(&room).map_or(false, |room| room.config.race);
+            if let Some((code, team)) = race.then(|| RoomConfig::race_team(&room)).flatten() {
+                // Teams finishing after the winner have nothing to announce
+                if let Some(winner) = self.race_winner(code).filter(|&winner| winner == team) {
+                    for team in 0..RoomConfig::TEAMS {
+                        self.broadcast(
+                            &RoomConfig::team_room(code, team),
+                            ServerMessage::RaceWon(winner),
+                        );
+                    }
+                }
+            }
+        }
+        for room in &mut self.rooms {
+            if !self.players.iter().any(|player| player.room == room.name) {
+                continue;
+            }
+            // Cursors moved since the last tick, with their previous positions
+            let moved: Vec<(Id, Vec2<f32>)> = self
+                .players
+                .iter_mut()
+                .filter(|player| player.room == room.name)
+                .filter_map(|player| {
+                    if !std::mem::take(&mut player.pos_changed) {
+                        return None;
+                    }
+                    let pos = player.pos?;
+                    let previous = player.ticked_pos.replace(pos).unwrap_or(pos);
+                    Some((player.id, previous.into()))
+                })
+                .collect();
+            let cursors: HashMap<Id, CompactPos> = self
+                .players
+                .iter()
+                .filter(|player| player.room == room.name)
+                .filter_map(|player| Some((player.id, player.pos?)))
+                .collect();
+            let keyframe = if room.last_keyframe.elapsed().as_secs_f64() > KEYFRAME_INTERVAL {
+                room.last_keyframe = std::time::Instant::now();
+                room.dirty_tiles.clear();
+                Some(ServerMessage::Keyframe(room.tiles.clone()))
+            } else {
+                None
+            };
+            let changed: Vec<usize> = std::mem::take(&mut room.dirty_tiles).into_iter().collect();
+            for player in &mut self.players {
+                if player.room != room.name {
+                    continue;
+                }
+                if let Some(keyframe) = &keyframe {
+                    player.send(keyframe.clone());
+                    player.stale_tiles.clear();
+                }
+                for &tile in &changed {
+                    player
+                        .stale_tiles
+                        .entry(tile)
+                        .or_insert(room.ticked_pos[tile]);
+                }
+                for &(id, previous) in &moved {
+                    if id != player.id {
+                        player.stale_cursors.entry(id).or_insert(previous);
+                    }
+                }
+                // Send whatever is seen by the player now or was seen last time
+                let viewport = player
+                    .viewport
+                    .map(|viewport| viewport.extend_uniform(VIEWPORT_MARGIN));
+                let visible = |pos: Vec2<f32>| viewport.map_or(true, |area| area.contains(pos));
+                let mut tiles = Vec::new();
+                player.stale_tiles.retain(|&tile, &mut known| {
+                    let state = &room.tiles[tile];
+                    if state.in_tray || visible(known) || visible(state.pos) {
+                        tiles.push((tile, state.clone()));
+                        false
+                    } else {
+                        true
+                    }
+                });
+                let mut positions = Vec::new();
+                player.stale_cursors.retain(|&id, &mut known| {
+                    let pos = match cursors.get(&id) {
+                        Some(&pos) => pos,
+                        None => return false,
+                    };
+                    if visible(known) || visible(pos.into()) {
+                        positions.push((id, pos));
+                        false
+                    } else {
+                        true
+                    }
+                });
+                if !positions.is_empty() || !tiles.is_empty() {
+                    player.send(ServerMessage::Updates { positions, tiles });
+                }
+            }
+            for tile in changed {
+                room.ticked_pos[tile] = room.tiles[tile].pos;
+                room.unsaved = true;
+            }
+        }
+        self.autosave();
+        self.remove_abandoned();
+        self.sweep_storage();
+        self.report_status();
+        self.send_player_stats();
+        for player in &mut self.players {
+            player.flush();
+        }
+    }
+    fn handle(&mut self, id: Id, message: ClientMessage) {
+        self.messages += 1;
+        let player = self.players.get_mut(&id).unwrap();
+        if player.kicked {
+            return;
+        }
+        if !player.limits.allow(&message) {
+            // The client waits for an answer to these
+            if let ClientMessage::UploadImage(..) = message {
+                player.send(ServerMessage::RoomRejected(
+                    "Too many uploads, try again later".to_owned(),
+                ));
+            }
+            if self
+                .config
+                .rate_limits
+                .max_dropped
+                .map_or(false, |max| player.limits.dropped >= max)
+            {
+                self.kick(id, "Disconnected for sending too many messages");
+            }
+            return;
+        }
+        let room = player.room.clone();
+        if let Some(room) = self.rooms.get_mut(&room) {
+            room.last_active = std::time::Instant::now();
+        }
+        match message {
+            // Clients never send these broken, so there is no point in listening further
+            message @ (ClientMessage::Batch(..) | ClientMessage::Packed(..)) => {
+                match message.unpack() {
+                    Ok(messages) => {
+                        for message in messages {
+                            self.handle(id, message);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to unpack message: {}", e);
+                        self.kick(id, "Disconnected for sending a malformed message");
+                    }
+                }
+            }
+            ClientMessage::Handshake {
+                compression,
+                name,
+                token,
+            } => {
+                let player = self.players.get_mut(&id).unwrap();
+                player.compression = compression;
+                player.name = name;
+                if let Some(token) = &token {
+                    // Same color every time the player comes back
+                    let hash = {
+                        use std::hash::{Hash, Hasher};
+                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                        token.hash(&mut hasher);
+                        hasher.finish()
+                    };
+                    player.color = player_colors()
+                        .nth(hash as usize % player_colors().count())
+                        .unwrap();
+                }
+                player.token = token;
+            }
+            ClientMessage::Resume(previous) => {
+                self.players.get_mut(&id).unwrap().resumed_from = Some(previous);
+            }
+            ClientMessage::ListRooms => {
+                let mut rooms: Vec<RoomInfo> = self
+                    .rooms
+                    .iter()
+                    .filter(|room| room.config.public && room.completion_time.is_none())
+                    .filter_map(|room| {
+                        // Races are listed once under their code
+                        let (name, players) = match RoomConfig::race_team(&room.name) {
+                            Some((code, 0)) if room.config.race => (
+                                code.to_owned(),
+                                (0..RoomConfig::TEAMS)
+                                    .map(|team| {
+                                        self.player_count(&RoomConfig::team_room(code, team))
+                                    })
+                                    .sum(),
+                            ),
+                            Some(_) if room.config.race => return None,
+                            _ => (room.name.clone(), self.player_count(&room.name)),
+                        };
+                        Some(RoomInfo {
+                            name,
+                            image: room.config.image.clone(),
+                            pieces: room.tiles.len(),
+                            players,
+                            locked: room.config.password.is_some(),
+                            max_players: room.config.max_players,
+                            named: room.config.custom_name.is_some(),
+                        })
+                    })
+                    .collect();
+                rooms.sort_by(|a, b| b.players.cmp(&a.players));
+                rooms.truncate(MAX_LISTED_ROOMS);
+                let player = self.players.get_mut(&id).unwrap();
+                player.send(ServerMessage::RoomList(rooms));
+            }
+            ClientMessage::UploadImage(data) => {
+                let result = self.config.validate_image(&data).and_then(|()| {
+                    let hash = {
+                        use std::hash::{Hash, Hasher};
+                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                        data.hash(&mut hasher);
+                        hasher.finish()
+                    };
+                    match self.storage.save_image(hash, &data) {
+                        Ok(()) => {
+                            self.recent_uploads.insert(hash, std::time::Instant::now());
+                            Ok(hash)
+                        }
+                        Err(e) => {
+                            error!("Failed to save image: {}", e);
+                            Err("Failed to save image".to_owned())
+                        }
+                    }
+                });
+                let player = self.players.get_mut(&id).unwrap();
+                match result {
+                    Ok(image) => player.send(ServerMessage::ImageUploaded(image)),
+                    Err(reason) => {
+                        warn!("Rejected image upload: {}", reason);
+                        player.send(ServerMessage::RoomRejected(reason));
+                    }
+                }
+            }
+            ClientMessage::CreateRoom(config) => {
+                let message = match self.new_room(id, config) {
+                    Ok(name) => ServerMessage::RoomCreated(name),
+                    Err(reason) => {
+                        warn!("Rejected room creation: {}", reason);
+                        ServerMessage::RoomRejected(reason)
+                    }
+                };
+                self.players.get_mut(&id).unwrap().send(message);
+            }
+            ClientMessage::QuickPlay(config) => {
+                let open: Vec<String> = self
+                    .rooms
+                    .iter()
+                    .filter(|room| {
+                        let players = self.player_count(&room.name);
+                        room.config.public
+                            && !room.config.race
+                            && room.config.password.is_none()
+                            && room.completion_time.is_none()
+                            && players > 0
+                            && room.config.max_players.map_or(true, |max| players < max)
+                    })
+                    .map(|room| room.name.clone())
+                    .collect();
+                let message = match open.choose(&mut thread_rng()) {
+                    Some(name) => ServerMessage::QuickPlayRoom(name.clone()),
+                    None => match self.new_room(id, config) {
+                        Ok(name) => ServerMessage::QuickPlayRoom(name),
+                        Err(reason) => {
+                            warn!("Rejected quick play room: {}", reason);
+                            ServerMessage::RoomRejected(reason)
+                        }
+                    },
+                };
+                self.players.get_mut(&id).unwrap().send(message);
+            }
+            ClientMessage::UpdatePos(pos) => {
+                // Sent to others on the next tick
+                let player = self.players.get_mut(&id).unwrap();
+                player.pos = Some(pos);
+                player.pos_changed = true;
+            }
+            ClientMessage::UpdateViewport(a, b) => {
+                let (a, b): (Vec2<f32>, Vec2<f32>) = (a.into(), b.into());
+                self.players.get_mut(&id).unwrap().viewport = Some(AABB {
+                    x_min: a.x.min(b.x),
+                    x_max: a.x.max(b.x),
+                    y_min: a.y.min(b.y),
+                    y_max: a.y.max(b.y),
+                });
+            }
+            ClientMessage::UpdateName(name) => {
+                let player = self.players.get_mut(&id).unwrap();
+                player.name = name.clone();
+                if let Some(stats) = self
+                    .rooms
+                    .get_mut(&room)
+                    .and_then(|room| room.player_stats.get_mut(&id))
+                {
+                    stats.name = name.clone();
+                }
+                let announce = !std::mem::replace(&mut player.announced, true);
+                for player in &mut self.players {
+                    if player.id != id && player.room == room {
+                        player.send(ServerMessage::UpdatePlayerName(id, name.clone()));
+                    }
+                }
+                if announce && self.rooms.get(&room).is_some() {
+                    self.broadcast(
+                        &room,
+                        ServerMessage::Activity(Activity::PlayerJoined { name }),
+                    );
+                }
+            }
+            ClientMessage::UpdateColor(color) => {
+                self.players.get_mut(&id).unwrap().color = color;
+                for player in &mut self.players {
+                    if player.id != id && player.room == room {
+                        player.send(ServerMessage::UpdatePlayerColor(id, color));
+                    }
+                }
+            }
+            ClientMessage::UpdateCursor(cursor) => {
+                self.players.get_mut(&id).unwrap().cursor = cursor;
+                for player in &mut self.players {
+                    if player.id != id && player.room == room {
+                        player.send(ServerMessage::UpdatePlayerCursor(id, cursor));
+                    }
+                }
+            }
+            ClientMessage::SelectRoom { mut room, password } => {
+                self.restore_room(&room);
+                if self.rooms.get(&room).is_none() {
+                    if let Some(team_room) = self.race_room(&room, id) {
+                        room = team_room;
+                    }
+                }
+                if let Some(expected) = self
+                    .rooms
+                    .get(&room)
+                    .and_then(|room| room.config.password.as_ref())
+                {
+                    if password.as_ref() != Some(expected) {
+                        let player = self.players.get_mut(&id).unwrap();
+                        player.send(ServerMessage::WrongPassword);
+                        return;
+                    }
+                }
+                if let Some(max_players) = self
+                    .rooms
+                    .get(&room)
+                    .and_then(|room| room.config.max_players)
+                {
+                    let others = self
+                        .players
+                        .iter()
+                        .filter(|player| player.id != id && player.room == room)
+                        .count();
+                    if others >= max_players {
+                        let player = self.players.get_mut(&id).unwrap();
+                        player.send(ServerMessage::RoomFull);
+                        return;
+                    }
+                }
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    let player = self.players.get(&id).unwrap();
+                    let name = player.name.clone();
+                    // Carry over the stats of a reconnecting player or of one coming back
+                    // in a later session, unless that id is still in use
+                    let previous = player
+                        .resumed_from
+                        .filter(|&previous| room.owned_by(previous, player.token.as_deref()))
+                        .or_else(|| {
+                            let token = player.token.as_ref()?;
+                            room.tokens.get(token).copied()
+                        })
+                        .filter(|&previous| {
+                            previous != id && self.players.get(&previous).is_none()
+                        });
+                    if let Some(token) = &player.token {
+                        room.tokens.insert(token.clone(), id);
+                    }
+                    if previous.is_some() && room.host == previous {
+                        room.host = Some(id);
+                    }
+                    let resumed = previous.and_then(|previous| room.player_stats.remove(&previous));
+                    room.player_stats
+                        .entry(id)
+                        .or_insert_with(|| resumed.unwrap_or(PlayerStats { name, pieces: 0 }));
+                }
+                let player = self.players.get_mut(&id).unwrap();
+                let color = player.color;
+                let cursor = player.cursor;
+                let mut messages = Vec::new();
+                if let Some(room) = self.rooms.get(&room) {
+                    player.room = room.name.clone();
+                    player.send(ServerMessage::SetupId {
+                        player_id: id,
+                        room_config: RoomConfig {
+                            password: None,
+                            ..room.config.clone()
+                        },
+                        tiles: room.tiles.clone(),
+                    });
+                    // A resuming client still has the image from before reconnecting
+                    let resuming = player.resumed_from.is_some();
+                    if let (ImageSource::Uploaded(image), false) = (room.config.image, resuming) {
+                        match self.storage.load_image(image) {
+                            Some(data) => player.send(ServerMessage::ImageData(data)),
+                            None => error!("Image of room {:?} is missing", room.name),
+                        }
+                    }
+                    messages.push(ServerMessage::UpdatePlayerColor(id, color));
+                    for player in &self.players {
+                        if player.id != id && player.room == room.name {
+                            messages.push(ServerMessage::PlayerJoined {
+                                id: player.id,
+                                name: player.name.clone(),
+                                color: player.color,
+                                cursor: player.cursor,
+                            });
+                            if let Some(pos) = player.pos {
+                                messages.push(ServerMessage::UpdatePos(player.id, pos));
+                            }
+                            let held: Vec<(usize, Vec2<f32>)> = player
+                                .grabbed
+                                .iter()
+                                .copied()
+                                .filter(|&(tile, _)| room.tiles[tile].grabbed_by == Some(player.id))
+                                .collect();
+                            match held.len() {
+                                0 => {}
+                                1 => {
+                                    let (tile, offset) = held[0];
+                                    messages.push(ServerMessage::TileGrabbed {
+                                        player: player.id,
+                                        tile,
+                                        offset,
+                                    });
+                                }
+                                _ => messages.push(ServerMessage::TilesGrabbed {
+                                    player: player.id,
+                                    tiles: held,
+                                }),
+                            }
+                        }
+                    }
+                    if let Some(motd) = &self.config.motd {
+                        messages.push(ServerMessage::Announcement(motd.clone()));
+                    }
+                    messages.push(ServerMessage::Elapsed(room.elapsed()));
+                    messages.push(ServerMessage::RoomHost(room.host));
+                    if room.completion_time.is_some() {
+                        messages.push(ServerMessage::PuzzleCompleted(room.stats()));
+                    }
+                    if let Some((code, team)) = room
+                        .config
+                        .race
+                        .then(|| RoomConfig::race_team(&room.name))
+                        .flatten()
+                    {
+                        messages.push(ServerMessage::RaceTeam(team));
+                        if let Some(winner) = self.race_winner(code) {
+                            messages.push(ServerMessage::RaceWon(winner));
+                        }
+                    }
+                } else {
+                    player.send(ServerMessage::RoomNotFound);
+                }
+                let player = self.players.get_mut(&id).unwrap(); // KEKW
+                for message in messages {
+                    player.send(message);
+                }
+                let room = player.room.clone();
+                let name = player.name.clone();
+                for player in &mut self.players {
+                    if player.id != id && player.room == room {
+                        player.send(ServerMessage::PlayerJoined {
+                            id,
+                            name: name.clone(),
+                            color,
+                            cursor,
+                        });
+                    }
+                }
+            }
+            ClientMessage::GrabTile {
+                tile: tile_id,
+                offset,
+                time,
+            } => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    let now = room.elapsed();
+                    let time = time.clamp(now - MAX_LAG_COMPENSATION, now);
+                    if let Some(holder) = room.later_grab(tile_id, id, time) {
+                        // Grabbed first as far as this player could see, take it from the holder
+                        for other in room.group(tile_id) {
+                            if room.tiles[other].grabbed_by == Some(holder) {
+                                room.tiles[other].grabbed_by = None;
+                                room.grab_times.remove(&other);
+                                room.dirty_tiles.insert(other);
+                            }
+                        }
+                        let group = room.group(tile_id);
+                        if let Some(holder) = self.players.get_mut(&holder) {
+                            let mut lost = Vec::new();
+                            holder.grabbed.retain(|&(tile, _)| {
+                                let keep = !group.contains(&tile);
+                                if !keep {
+                                    lost.push(tile);
+                                }
+                                keep
+                            });
+                            for tile in lost {
+                                holder.send(ServerMessage::GrabDenied(tile));
+                            }
+                        }
+                    }
+                    if !room.can_grab(tile_id, id) {
+                        self.players
+                            .get_mut(&id)
+                            .unwrap()
+                            .send(ServerMessage::GrabDenied(tile_id));
+                        return;
+                    }
+                    room.tiles[tile_id].grabbed_by = Some(id);
+                    room.grab_times.insert(tile_id, time);
+                    room.dirty_tiles.insert(tile_id);
+                    self.players.get_mut(&id).unwrap().grabbed = vec![(tile_id, offset)];
+                    for player in &mut self.players {
+                        if player.id != id && player.room == room.name {
+                            player.send(ServerMessage::TileGrabbed {
+                                player: id,
+                                tile: tile_id,
+                                offset,
+                            });
+                        }
+                    }
+                }
+            }
+            ClientMessage::GrabTiles(tiles) => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    let mut grabbed = Vec::new();
+                    for (tile_id, offset) in tiles {
+                        if room.can_grab(tile_id, id) {
+                            room.tiles[tile_id].grabbed_by = Some(id);
+                            room.grab_times.insert(tile_id, room.elapsed());
+                            room.dirty_tiles.insert(tile_id);
+                            grabbed.push((tile_id, offset));
+                        } else {
+                            self.players
+                                .get_mut(&id)
+                                .unwrap()
+                                .send(ServerMessage::GrabDenied(tile_id));
+                        }
+                    }
+                    if grabbed.is_empty() {
+                        return;
+                    }
+                    self.players.get_mut(&id).unwrap().grabbed = grabbed.clone();
+                    for player in &mut self.players {
+                        if player.id != id && player.room == room.name {
+                            player.send(ServerMessage::TilesGrabbed {
+                                player: id,
+                                tiles: grabbed.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+            ClientMessage::ReleaseTile(updates) => {
+                let grabbed = std::mem::take(&mut self.players.get_mut(&id).unwrap().grabbed);
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    // Only groups the player really holds, a release after a denied grab moves nothing
+                    let owned: HashSet<usize> = grabbed
+                        .iter()
+                        .filter(|&&(tile, _)| room.tiles[tile].grabbed_by == Some(id))
+                        .flat_map(|&(tile, _)| room.group(tile))
+                        .collect();
+                    let updates: Vec<(usize, Vec2<f32>)> = updates
+                        .into_iter()
+                        .filter(|(tile, _)| owned.contains(tile))
+                        .collect();
+                    for (tile_id, pos) in updates {
+                        let tile = &mut room.tiles[tile_id];
+                        tile.grabbed_by.take();
+                        tile.pos = pos;
+                        room.dirty_tiles.insert(tile_id);
+                    }
+                }
+            }
+            ClientMessage::ConnectTiles(a, b) => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    if let Err(reason) = room.check_connection(a, b) {
+                        warn!(
+                            "Rejected connecting tiles {} and {} in room {:?}: {}",
+                            a, b, room.name, reason
+                        );
+                        return;
+                    }
+                    room.tiles[a].connections.push(b);
+                    room.tiles[b].connections.push(a);
+                    room.dirty_tiles.insert(a);
+                    room.dirty_tiles.insert(b);
+                    // Pieces joining a locked group are in place as well
+                    if room.tiles[a].locked || room.tiles[b].locked {
+                        room.lock_group(a);
+                    }
+                    *room.recent_connections.entry(id).or_default() += 1;
+                    if let Some(stats) = room.player_stats.get_mut(&id) {
+                        stats.pieces += 1;
+                    }
+                }
+            }
+            ClientMessage::RotateTile { tile, rotation } => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    if room.tiles.get(tile).map(|tile| tile.grabbed_by) != Some(Some(id)) {
+                        return;
+                    }
+                    if !room.config.rotation.allows(rotation) {
+                        warn!("Rejected rotation to {} in room {:?}", rotation, room.name);
+                        return;
+                    }
+                    for other in room.group(tile) {
+                        room.tiles[other].rotation = rotation % 4;
+                        room.dirty_tiles.insert(other);
+                    }
+                }
+            }
+            ClientMessage::SetInTray { tile, in_tray } => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    let state = match room.tiles.get_mut(tile) {
+                        Some(state) => state,
+                        None => return,
+                    };
+                    // Only loose tiles fit in the tray
+                    if state.grabbed_by.map_or(false, |holder| holder != id)
+                        || state.locked
+                        || in_tray && !state.connections.is_empty()
+                    {
+                        return;
+                    }
+                    state.in_tray = in_tray;
+                    room.dirty_tiles.insert(tile);
+                }
+            }
+            ClientMessage::Scatter => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    // Anyone can scatter once the host is gone
+                    let host_present = room.host.map_or(false, |host| {
+                        self.players
+                            .get(&host)
+                            .map_or(false, |host| host.room == room.name)
+                    });
+                    if host_present && room.host != Some(id) {
+                        return;
+                    }
+                    let mut rng = thread_rng();
+                    for (index, tile) in room.tiles.iter_mut().enumerate() {
+                        if tile.connections.is_empty()
+                            && tile.grabbed_by.is_none()
+                            && !tile.in_tray
+                            && !tile.locked
+                        {
+                            tile.pos = spawn_pos(&mut rng);
+                            room.dirty_tiles.insert(index);
+                        }
+                    }
+                }
+            }
+            ClientMessage::MoveTiles(moves) => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    for (index, pos) in moves {
+                        let tile = match room.tiles.get(index) {
+                            Some(tile) => tile,
+                            None => continue,
+                        };
+                        // Only pieces nobody holds and not part of any group,
+                        // whole groups slide around in physics rooms
+                        let movable = if tile.connections.is_empty() {
+                            tile.grabbed_by.is_none() && !tile.locked
+                        } else {
+                            room.config.physics && room.can_grab(index, id)
+                        };
+                        if movable && !tile.in_tray && tile.grabbed_by.is_none() {
+                            room.tiles[index].pos = pos.clamp_aabb(room.bounds());
+                            room.dirty_tiles.insert(index);
+                        }
+                    }
+                }
+            }
+            ClientMessage::Ping(pos) => {
+                let player = self.players.get_mut(&id).unwrap();
+                if player.last_ping.map_or(false, |time| {
+                    time.elapsed().as_secs_f64() < MIN_PING_INTERVAL
+                }) {
+                    return;
+                }
+                player.last_ping = Some(std::time::Instant::now());
+                for player in &mut self.players {
+                    if player.id != id && player.room == room {
+                        player.send(ServerMessage::Ping { player: id, pos });
+                    }
+                }
+            }
+            ClientMessage::Leave => {
+                self.leave(id);
+            }
+            ClientMessage::LatencyReply => {
+                let player = self.players.get_mut(&id).unwrap();
+                if let Some(sent) = player.probe_sent.take() {
+                    let sample = sent.elapsed().as_secs_f64();
+                    player.latency = Some(
+                        player
+                            .latency
+                            .map_or(sample, |latency| latency * 0.7 + sample * 0.3),
+                    );
+                }
+            }
+            ClientMessage::LockGroup(tile) => {
+                if let Some(room) = self.rooms.get_mut(&room) {
+                    if room.config.lock_placed && tile < room.tiles.len() && room.group_placed(tile)
+                    {
+                        room.lock_group(tile);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct App {
+    state: Arc<Mutex<State>>,
+}
+
+const TICKS_PER_SECOND: f64 = 20.0;
+/// Seconds between full tile state broadcasts
+const KEYFRAME_INTERVAL: f64 = 30.0;
+/// Seconds between [ServerMessage::PlayerStats] broadcasts and latency probes
+const PLAYER_STATS_INTERVAL: f64 = 1.0;
+/// Seconds between looking for abandoned rooms and unused images on disk,
+/// an image uploaded more recently than that is kept even if no room uses it yet
+const STORAGE_SWEEP_INTERVAL: f64 = 3600.0;
+/// Distance around the reported viewport within which updates are still sent
+const VIEWPORT_MARGIN: f32 = 1.0;
+
+impl App {
+    pub fn new(config: Config) -> Self {
+        let state = Arc::new(Mutex::new(State::new(config)));
+        std::thread::spawn({
+            let state = Arc::downgrade(&state);
+            move || loop {
+                std::thread::sleep(std::time::Duration::from_secs_f64(1.0 / TICKS_PER_SECOND));
+                match state.upgrade() {
+                    Some(state) => state.lock().unwrap().tick(),
+                    None => break,
+                }
+            }
+        });
+        Self { state }
+    }
+
+    /// Also reads admin commands from stdin, for a dedicated server only,
+    /// since the one embedded in the client does not own the terminal
+    pub fn with_console(self) -> Self {
+        std::thread::spawn({
+            let state = Arc::downgrade(&self.state);
+            move || Self::console(state)
+        });
+        self
+    }
+
+    /// Reads admin commands from stdin
+    fn console(state: std::sync::Weak<Mutex<State>>) {
+        for line in std::io::stdin().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let state = match state.upgrade() {
+                Some(state) => state,
+                None => break,
+            };
+            let mut state = state.lock().unwrap();
+            let (command, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            match command {
+                "" => {}
+                "announce" => state.announce(args.trim()),
+                "status" => info!("Status: {}", state.status()),
+                _ => warn!(
+                    "Unknown command {:?}, available: announce <message>, status",
+                    command
+                ),
+            }
+        }
+    }
+}
+
+impl geng::net::server::App for App {
+    type Client = Client;
+    type ServerMessage = ServerMessage;
+    type ClientMessage = ClientMessage;
+    fn connect(&mut self, sender: Box<dyn geng::net::Sender<ServerMessage>>) -> Client {
+        let mut state = self.state.lock().unwrap();
+        let id = state.id_gen.gen();
+        let player = Player {
+            id,
+            name: "".to_owned(),
+            color: player_colors()
+                .nth(id.0 as usize % player_colors().count())
+                .unwrap(),
+            cursor: default(),
+            room: create_room(),
+            sender,
+            compression: false,
+            outgoing: Vec::new(),
+            announced: false,
+            pos: None,
+            pos_changed: false,
+            ticked_pos: None,
+            viewport: None,
+            stale_tiles: HashMap::new(),
+            stale_cursors: HashMap::new(),
+            grabbed: Vec::new(),
+            resumed_from: None,
+            token: None,
+            last_ping: None,
+            probe_sent: None,
+            latency: None,
+            limits: ConnectionLimits::new(&state.config.rate_limits),
+            kicked: false,
+        };
+        state.players.insert(player);
+        Client {
+            id,
+            state: self.state.clone(),
+        }
+    }
+}
+
+pub struct Client {
+    id: Id,
+    state: Arc<Mutex<State>>,
+}
+
+impl geng::net::Receiver<ClientMessage> for Client {
+    fn handle(&mut self, message: ClientMessage) {
+        self.state.lock().unwrap().handle(self.id, message);
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.leave(self.id);
+        state.players.remove(&self.id);
+        state.enforce_memory_budget(None);
+    }
+}
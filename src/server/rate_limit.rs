@@ -0,0 +1,122 @@
+use super::*;
+
+/// Messages per second a single connection may send, the excess is dropped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimits {
+    /// All messages, each message inside a batch counts separately
+    pub messages: f64,
+    /// Batches and packed messages, clients send one or two per frame
+    pub batches: f64,
+    /// Cursor position updates, clients send at most one per frame
+    pub update_pos: f64,
+    /// Grabs of a single tile or a selection
+    pub grabs: f64,
+    /// Image uploads, each one is written to disk
+    pub uploads: f64,
+    /// Dropped messages after which the connection is cut off, `None` only drops them
+    pub max_dropped: Option<usize>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            messages: 500.0,
+            batches: 300.0,
+            update_pos: 200.0,
+            grabs: 20.0,
+            uploads: 0.05,
+            max_dropped: Some(1000),
+        }
+    }
+}
+
+/// Token bucket that allows bursts of up to a second worth of messages,
+/// or a single one for rates below one per second
+struct RateLimiter {
+    /// Messages per second
+    rate: f64,
+    tokens: f64,
+    last: std::time::Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate.max(1.0),
+            last: std::time::Instant::now(),
+        }
+    }
+    fn allow(&mut self, now: std::time::Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate.max(1.0));
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Limits of a single connection
+pub struct ConnectionLimits {
+    messages: RateLimiter,
+    batches: RateLimiter,
+    update_pos: RateLimiter,
+    grabs: RateLimiter,
+    uploads: RateLimiter,
+    /// Messages dropped so far
+    pub dropped: usize,
+}
+
+impl ConnectionLimits {
+    pub fn new(limits: &RateLimits) -> Self {
+        Self {
+            messages: RateLimiter::new(limits.messages),
+            batches: RateLimiter::new(limits.batches),
+            update_pos: RateLimiter::new(limits.update_pos),
+            grabs: RateLimiter::new(limits.grabs),
+            uploads: RateLimiter::new(limits.uploads),
+            dropped: 0,
+        }
+    }
+    /// Whether the message should be handled, counts it as dropped otherwise
+    pub fn allow(&mut self, message: &ClientMessage) -> bool {
+        let now = std::time::Instant::now();
+        let allowed = match message {
+            // Limited apart from the messages inside, which are checked one by one
+            ClientMessage::Batch(..) | ClientMessage::Packed(..) => self.batches.allow(now),
+            ClientMessage::UpdatePos(..) => self.messages.allow(now) && self.update_pos.allow(now),
+            ClientMessage::GrabTile { .. } | ClientMessage::GrabTiles(..) => {
+                self.messages.allow(now) && self.grabs.allow(now)
+            }
+            ClientMessage::UploadImage(..) => self.messages.allow(now) && self.uploads.allow(now),
+            _ => self.messages.allow(now),
+        };
+        if !allowed {
+            self.dropped += 1;
+        }
+        allowed
+    }
+}
+
+#[test]
+fn test_rate_limiter() {
+    let start = std::time::Instant::now();
+    let mut limiter = RateLimiter {
+        rate: 10.0,
+        tokens: 10.0,
+        last: start,
+    };
+    assert_eq!((0..20).filter(|_| limiter.allow(start)).count(), 10);
+    let later = start + std::time::Duration::from_millis(500);
+    assert_eq!((0..20).filter(|_| limiter.allow(later)).count(), 5);
+
+    let mut slow = RateLimiter::new(0.1);
+    slow.last = start;
+    assert_eq!((0..5).filter(|_| slow.allow(start)).count(), 1);
+    assert!(!slow.allow(later));
+    assert!(slow.allow(start + std::time::Duration::from_secs(20)));
+}
@@ -0,0 +1,181 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedRoom {
+    pub config: RoomConfig,
+    pub tiles: Vec<TileState>,
+    #[serde(default)]
+    pub completion_time: Option<f64>,
+    /// Seconds the room has been played before saving
+    #[serde(default)]
+    pub elapsed: f64,
+    #[serde(default)]
+    pub player_stats: HashMap<Id, PlayerStats>,
+    /// Id each player with a token last had in the room
+    #[serde(default)]
+    pub tokens: HashMap<String, Id>,
+    /// Seconds since the unix epoch when someone was last active in the room
+    #[serde(default)]
+    pub last_active: Option<f64>,
+}
+
+/// Keeps rooms and uploaded images between server restarts
+pub trait Storage: Send {
+    fn save(&self, name: &str, room: &SavedRoom) -> std::io::Result<()>;
+    fn load(&self, name: &str) -> Option<SavedRoom>;
+    /// Names of all saved rooms
+    fn rooms(&self) -> Vec<String>;
+    /// Deletes the room if it was saved
+    fn remove(&self, name: &str) -> std::io::Result<()>;
+    /// Keeps the final state of a removed room aside, it is not listed in [Self::rooms]
+    fn archive(&self, name: &str, room: &SavedRoom) -> std::io::Result<()>;
+    /// Final states of all archived rooms
+    fn archived(&self) -> Vec<SavedRoom>;
+    fn save_image(&self, id: u64, data: &[u8]) -> std::io::Result<()>;
+    fn has_image(&self, id: u64) -> bool;
+    fn load_image(&self, id: u64) -> Option<Vec<u8>>;
+    /// Ids of all saved images
+    fn images(&self) -> Vec<u64>;
+    fn remove_image(&self, id: u64) -> std::io::Result<()>;
+}
+
+/// Keeps rooms as json files in a directory, uploaded images go to the `images` subdirectory
+pub struct JsonFiles {
+    path: std::path::PathBuf,
+}
+
+impl JsonFiles {
+    pub fn new(path: impl AsRef<std::path::Path>) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+        }
+    }
+
+    fn archive_path(&self) -> std::path::PathBuf {
+        self.path.join("archive")
+    }
+
+    fn room_path(&self, name: &str) -> std::io::Result<std::path::PathBuf> {
+        if !RoomConfig::is_valid_name(name) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid room name {name:?}"),
+            ));
+        }
+        Ok(self.path.join(format!("{name}.json")))
+    }
+
+    fn image_path(&self, id: u64) -> std::path::PathBuf {
+        self.path.join("images").join(format!("{id:016x}"))
+    }
+
+    fn write_room(path: &std::path::Path, room: &SavedRoom) -> std::io::Result<()> {
+        // Write to a temporary file first so that a crash never leaves a half written room
+        let temp = path.with_extension("json.tmp");
+        let file = std::fs::File::create(&temp)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), room)?;
+        std::fs::rename(temp, path)
+    }
+}
+
+impl Storage for JsonFiles {
+    fn save(&self, name: &str, room: &SavedRoom) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.path)?;
+        Self::write_room(&self.room_path(name)?, room)
+    }
+
+    fn load(&self, name: &str) -> Option<SavedRoom> {
+        let file = std::fs::File::open(self.room_path(name).ok()?).ok()?;
+        match serde_json::from_reader(std::io::BufReader::new(file)) {
+            Ok(room) => Some(room),
+            Err(e) => {
+                error!("Failed to load room {:?}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    fn rooms(&self) -> Vec<String> {
+        let entries = match std::fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_owned())
+            })
+            .collect()
+    }
+
+    fn remove(&self, name: &str) -> std::io::Result<()> {
+        match std::fs::remove_file(self.room_path(name)?) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn archive(&self, name: &str, room: &SavedRoom) -> std::io::Result<()> {
+        self.room_path(name)?;
+        let archive = self.archive_path();
+        std::fs::create_dir_all(&archive)?;
+        Self::write_room(&archive.join(format!("{name}.json")), room)?;
+        self.remove(name)
+    }
+
+    fn archived(&self) -> Vec<SavedRoom> {
+        let entries = match std::fs::read_dir(self.archive_path()) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                let file = std::fs::File::open(&path).ok()?;
+                match serde_json::from_reader(std::io::BufReader::new(file)) {
+                    Ok(room) => Some(room),
+                    Err(e) => {
+                        error!("Failed to load archived room {:?}: {}", path, e);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn save_image(&self, id: u64, data: &[u8]) -> std::io::Result<()> {
+        std::fs::create_dir_all(self.path.join("images"))?;
+        std::fs::write(self.image_path(id), data)
+    }
+
+    fn has_image(&self, id: u64) -> bool {
+        self.image_path(id).is_file()
+    }
+
+    fn load_image(&self, id: u64) -> Option<Vec<u8>> {
+        std::fs::read(self.image_path(id)).ok()
+    }
+
+    fn images(&self) -> Vec<u64> {
+        let entries = match std::fs::read_dir(self.path.join("images")) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                u64::from_str_radix(name.to_str()?, 16).ok()
+            })
+            .collect()
+    }
+
+    fn remove_image(&self, id: u64) -> std::io::Result<()> {
+        std::fs::remove_file(self.image_path(id))
+    }
+}
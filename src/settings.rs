@@ -0,0 +1,726 @@
+use super::*;
+
+/// Sound settings, saved in preferences and shared between the menu and the game
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f64,
+    pub sfx_volume: f64,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            sfx_volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn music_volume(&self) -> f64 {
+        if self.muted {
+            0.0
+        } else {
+            self.master_volume
+        }
+    }
+    pub fn effect_volume(&self) -> f64 {
+        self.music_volume() * self.sfx_volume
+    }
+    /// Sliders and a mute toggle, changes apply immediately
+    pub fn ui<'a>(
+        &mut self,
+        cx: &'a geng::ui::Controller,
+        theme: UiTheme,
+    ) -> impl geng::ui::Widget + 'a {
+        use geng::ui::*;
+        let master = slider::Slider::new(
+            cx,
+            tr_with(
+                "settings.volume",
+                &[("value", &format!("{:.0}", self.master_volume * 100.0))],
+            ),
+            self.master_volume,
+            0.0..=1.0,
+        );
+        if let Some(value) = master.get_change() {
+            self.master_volume = value;
+        }
+        let sfx = slider::Slider::new(
+            cx,
+            tr_with(
+                "settings.sfx_volume",
+                &[("value", &format!("{:.0}", self.sfx_volume * 100.0))],
+            ),
+            self.sfx_volume,
+            0.0..=1.0,
+        );
+        if let Some(value) = sfx.get_change() {
+            self.sfx_volume = value;
+        }
+        let mute = button::Button::new(
+            cx,
+            theme,
+            &tr(if self.muted {
+                "settings.sound_off"
+            } else {
+                "settings.sound_on"
+            }),
+        );
+        if mute.was_clicked() {
+            self.muted = !self.muted;
+        }
+        (master.center(), sfx.center(), mute.center()).column()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HighlightColor {
+    White,
+    Yellow,
+    Cyan,
+    Magenta,
+}
+
+impl HighlightColor {
+    pub fn color(self) -> Rgba<f32> {
+        match self {
+            Self::White => Rgba::WHITE,
+            Self::Yellow => Rgba::new(1.0, 0.9, 0.0, 1.0),
+            Self::Cyan => Rgba::new(0.0, 0.9, 1.0, 1.0),
+            Self::Magenta => Rgba::new(1.0, 0.2, 1.0, 1.0),
+        }
+    }
+}
+
+/// What the board lies on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TableTheme {
+    Wood,
+    Felt,
+    Dark,
+    Light,
+}
+
+impl Default for TableTheme {
+    fn default() -> Self {
+        Self::Wood
+    }
+}
+
+/// Screen corner the thumbnail of the target image is pinned to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Corner {
+    BottomRight,
+    BottomLeft,
+}
+
+/// Small picture of the target image that stays on screen while playing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThumbnailSettings {
+    /// `None` hides the thumbnail
+    pub corner: Option<Corner>,
+    /// Height relative to the screen
+    pub size: f32,
+}
+
+impl Default for ThumbnailSettings {
+    fn default() -> Self {
+        Self {
+            corner: Some(Corner::BottomRight),
+            size: 0.15,
+        }
+    }
+}
+
+impl ThumbnailSettings {
+    pub const SIZE_RANGE: RangeInclusive<f32> = 0.05..=0.4;
+
+    pub fn ui<'a>(
+        &mut self,
+        cx: &'a geng::ui::Controller,
+        theme: UiTheme,
+    ) -> impl geng::ui::Widget + 'a {
+        let corner = button::Button::new(
+            cx,
+            theme,
+            &tr(match self.corner {
+                Some(Corner::BottomRight) => "settings.thumbnail_bottom_right",
+                Some(Corner::BottomLeft) => "settings.thumbnail_bottom_left",
+                None => "settings.thumbnail_off",
+            }),
+        );
+        if corner.was_clicked() {
+            self.corner = match self.corner {
+                Some(Corner::BottomRight) => Some(Corner::BottomLeft),
+                Some(Corner::BottomLeft) => None,
+                None => Some(Corner::BottomRight),
+            };
+        }
+        corner
+    }
+}
+
+/// Colors of the menus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UiTheme {
+    Dark,
+    Light,
+}
+
+impl Default for UiTheme {
+    fn default() -> Self {
+        Self::Dark
+    }
+}
+
+/// Colors of a [UiTheme]
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub background: Rgba<f32>,
+    pub button: Rgba<f32>,
+    pub button_hover: Rgba<f32>,
+    pub text: Rgba<f32>,
+}
+
+impl UiTheme {
+    pub fn palette(self) -> Palette {
+        match self {
+            Self::Dark => Palette {
+                background: Rgba::try_from("#322214").unwrap(),
+                button: Rgba::try_from("#6b4a26").unwrap(),
+                button_hover: Rgba::try_from("#8f6633").unwrap(),
+                text: Rgba::WHITE,
+            },
+            Self::Light => Palette {
+                background: Rgba::try_from("#f0e6d2").unwrap(),
+                button: Rgba::try_from("#d9bf99").unwrap(),
+                button_hover: Rgba::try_from("#f2d6a6").unwrap(),
+                text: Rgba::try_from("#322214").unwrap(),
+            },
+        }
+    }
+}
+
+/// How hovered and grabbed tiles are outlined
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighlightSettings {
+    pub color: HighlightColor,
+    /// Outline width in pixels
+    pub thickness: usize,
+    pub pulse: bool,
+}
+
+impl Default for HighlightSettings {
+    fn default() -> Self {
+        Self {
+            color: HighlightColor::White,
+            thickness: 1,
+            pulse: false,
+        }
+    }
+}
+
+impl HighlightSettings {
+    pub const MAX_THICKNESS: usize = 4;
+
+    /// How much to brighten the highlight at the given time
+    pub fn glow(&self, time: f32) -> f32 {
+        if self.pulse {
+            ((time * 4.0).sin() + 1.0) * 0.3
+        } else {
+            0.0
+        }
+    }
+    pub fn ui<'a>(
+        &mut self,
+        cx: &'a geng::ui::Controller,
+        theme: UiTheme,
+    ) -> impl geng::ui::Widget + 'a {
+        use geng::ui::*;
+        let color_button = button::Button::new(
+            cx,
+            theme,
+            &tr(match self.color {
+                HighlightColor::White => "settings.highlight_white",
+                HighlightColor::Yellow => "settings.highlight_yellow",
+                HighlightColor::Cyan => "settings.highlight_cyan",
+                HighlightColor::Magenta => "settings.highlight_magenta",
+            }),
+        );
+        if color_button.was_clicked() {
+            self.color = match self.color {
+                HighlightColor::White => HighlightColor::Yellow,
+                HighlightColor::Yellow => HighlightColor::Cyan,
+                HighlightColor::Cyan => HighlightColor::Magenta,
+                HighlightColor::Magenta => HighlightColor::White,
+            };
+        }
+        let thickness_button = button::Button::new(
+            cx,
+            theme,
+            &tr_with(
+                "settings.outline_width",
+                &[("value", &self.thickness.to_string())],
+            ),
+        );
+        if thickness_button.was_clicked() {
+            self.thickness = self.thickness % Self::MAX_THICKNESS + 1;
+        }
+        let pulse_button = button::Button::new(
+            cx,
+            theme,
+            &tr(if self.pulse {
+                "settings.pulse_on"
+            } else {
+                "settings.pulse_off"
+            }),
+        );
+        if pulse_button.was_clicked() {
+            self.pulse = !self.pulse;
+        }
+        (
+            color_button.center(),
+            thickness_button.center(),
+            pulse_button.center(),
+        )
+            .column()
+    }
+}
+
+/// How movements of other players are smoothed out
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Seconds to catch up with a position update from the server
+    pub smoothing: f32,
+    /// Part of the smoothing delay to make up for by moving
+    /// remote cursors ahead along their recent velocity
+    pub prediction: f32,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            smoothing: INTERPOLATION_TIME,
+            prediction: 0.0,
+        }
+    }
+}
+
+impl NetworkSettings {
+    pub const SMOOTHING_RANGE: RangeInclusive<f32> = 0.05..=0.5;
+
+    pub fn ui<'a>(&mut self, cx: &'a geng::ui::Controller) -> impl geng::ui::Widget + 'a {
+        use geng::ui::*;
+        let smoothing = slider::Slider::new(
+            cx,
+            tr_with(
+                "settings.smoothing",
+                &[("value", &format!("{:.0}", self.smoothing * 1000.0))],
+            ),
+            self.smoothing as f64,
+            *Self::SMOOTHING_RANGE.start() as f64..=*Self::SMOOTHING_RANGE.end() as f64,
+        );
+        if let Some(value) = smoothing.get_change() {
+            self.smoothing = value as f32;
+        }
+        let prediction = slider::Slider::new(
+            cx,
+            tr_with(
+                "settings.prediction",
+                &[("value", &format!("{:.0}", self.prediction * 100.0))],
+            ),
+            self.prediction as f64,
+            0.0..=1.0,
+        );
+        if let Some(value) = prediction.get_change() {
+            self.prediction = value as f32;
+        }
+        (smoothing.center(), prediction.center()).column()
+    }
+}
+
+/// Window mode
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub fullscreen: bool,
+}
+
+impl WindowSettings {
+    /// Restores the saved mode on startup
+    pub fn apply(&self, geng: &Geng) {
+        // Browsers only allow going fullscreen in response to user input
+        if self.fullscreen && cfg!(not(target_arch = "wasm32")) {
+            geng.window().set_fullscreen(true);
+        }
+    }
+    /// Switches between fullscreen and windowed mode
+    pub fn toggle_fullscreen(&mut self, geng: &Geng) {
+        self.fullscreen = !geng.window().is_fullscreen();
+        geng.window().set_fullscreen(self.fullscreen);
+    }
+    pub fn ui<'a>(
+        &mut self,
+        geng: &Geng,
+        cx: &'a geng::ui::Controller,
+        theme: UiTheme,
+    ) -> impl geng::ui::Widget + 'a {
+        let fullscreen_button = button::Button::new(
+            cx,
+            theme,
+            &tr(if geng.window().is_fullscreen() {
+                "settings.fullscreen_on"
+            } else {
+                "settings.fullscreen_off"
+            }),
+        );
+        if fullscreen_button.was_clicked() {
+            self.toggle_fullscreen(geng);
+        }
+        fullscreen_button
+    }
+}
+
+/// Random string that identifies this player to servers across sessions, created on first use
+pub fn player_token() -> String {
+    batbox::preferences::load("token").unwrap_or_else(|| {
+        let token = rand::distributions::DistString::sample_string(
+            &rand::distributions::Alphanumeric,
+            &mut thread_rng(),
+            32,
+        );
+        batbox::preferences::save("token", &token);
+        token
+    })
+}
+
+/// Room the player was in most recently, for the continue button in the menu
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastRoom {
+    /// Address of the server the room is on
+    pub addr: String,
+    pub room: String,
+}
+
+/// Everything the player can change, kept between sessions
+/// in a file on native and in local storage on the web
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub name: String,
+    /// `None` until the player picks a color themselves
+    pub color: Option<Rgba<f32>>,
+    pub show_names: bool,
+    pub show_feed: bool,
+    /// List of players with the number of pieces each connected
+    pub show_players: bool,
+    pub table: TableTheme,
+    /// Color each connected group with its own hue
+    pub tint_groups: bool,
+    pub ui_theme: UiTheme,
+    pub cursor: CursorSkin,
+    /// Code of the language from `assets/translations`
+    pub language: String,
+    pub thumbnail: ThumbnailSettings,
+    pub audio: AudioSettings,
+    pub highlight: HighlightSettings,
+    pub window: WindowSettings,
+    pub controls: Controls,
+    pub network: NetworkSettings,
+    pub last_room: Option<LastRoom>,
+    /// Whether the tutorial was finished or skipped, it is shown in the first game otherwise
+    pub tutorial_done: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            color: None,
+            show_names: true,
+            show_feed: true,
+            show_players: true,
+            table: default(),
+            tint_groups: false,
+            ui_theme: default(),
+            cursor: default(),
+            language: BASE_LANGUAGE.to_owned(),
+            thumbnail: default(),
+            audio: default(),
+            highlight: default(),
+            window: default(),
+            controls: default(),
+            network: default(),
+            last_room: None,
+            tutorial_done: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Saved settings with values from the client config taking priority
+    pub fn load(config: &ClientConfig) -> Self {
+        let mut settings =
+            batbox::preferences::load("settings").unwrap_or_else(Self::load_separate);
+        if let Some(name) = &config.name {
+            settings.name = name.clone();
+        }
+        if let Some(show_names) = config.show_names {
+            settings.show_names = show_names;
+        }
+        if let Some(show_feed) = config.show_feed {
+            settings.show_feed = show_feed;
+        }
+        if let Some(volume) = config.volume {
+            settings.audio.master_volume = volume;
+        }
+        if let Some(controls) = &config.controls {
+            settings.controls = controls.clone();
+        }
+        settings
+    }
+    /// Settings saved by older versions under separate keys
+    fn load_separate() -> Self {
+        let default = Self::default();
+        Self {
+            name: batbox::preferences::load("name").unwrap_or(default.name),
+            color: batbox::preferences::load("color"),
+            show_names: batbox::preferences::load("show_names").unwrap_or(default.show_names),
+            show_feed: batbox::preferences::load("show_feed").unwrap_or(default.show_feed),
+            show_players: default.show_players,
+            table: default.table,
+            tint_groups: default.tint_groups,
+            ui_theme: default.ui_theme,
+            cursor: default.cursor,
+            language: default.language,
+            thumbnail: default.thumbnail,
+            audio: batbox::preferences::load("audio").unwrap_or(default.audio),
+            highlight: batbox::preferences::load("highlight").unwrap_or(default.highlight),
+            window: batbox::preferences::load("window").unwrap_or(default.window),
+            controls: default.controls,
+            network: default.network,
+            last_room: default.last_room,
+            tutorial_done: default.tutorial_done,
+        }
+    }
+    pub fn save(&self) {
+        batbox::preferences::save("settings", self);
+    }
+    /// Options shared by the settings screen and the in-game menu, changes apply immediately
+    pub fn ui<'a>(
+        &mut self,
+        geng: &Geng,
+        cx: &'a geng::ui::Controller,
+    ) -> impl geng::ui::Widget + 'a {
+        use geng::ui::*;
+        // Taken before the theme button below can change it
+        let theme = self.ui_theme;
+        let show_names = button::Button::new(
+            cx,
+            theme,
+            &tr(if self.show_names {
+                "settings.show_names_on"
+            } else {
+                "settings.show_names_off"
+            }),
+        );
+        if show_names.was_clicked() {
+            self.show_names = !self.show_names;
+        }
+        let show_feed = button::Button::new(
+            cx,
+            theme,
+            &tr(if self.show_feed {
+                "settings.feed_on"
+            } else {
+                "settings.feed_off"
+            }),
+        );
+        if show_feed.was_clicked() {
+            self.show_feed = !self.show_feed;
+        }
+        let show_players = button::Button::new(
+            cx,
+            theme,
+            &tr(if self.show_players {
+                "settings.players_on"
+            } else {
+                "settings.players_off"
+            }),
+        );
+        if show_players.was_clicked() {
+            self.show_players = !self.show_players;
+        }
+        let table = button::Button::new(
+            cx,
+            theme,
+            &tr(match self.table {
+                TableTheme::Wood => "settings.table_wood",
+                TableTheme::Felt => "settings.table_felt",
+                TableTheme::Dark => "settings.table_dark",
+                TableTheme::Light => "settings.table_light",
+            }),
+        );
+        if table.was_clicked() {
+            self.table = match self.table {
+                TableTheme::Wood => TableTheme::Felt,
+                TableTheme::Felt => TableTheme::Dark,
+                TableTheme::Dark => TableTheme::Light,
+                TableTheme::Light => TableTheme::Wood,
+            };
+        }
+        let tint_groups = button::Button::new(
+            cx,
+            theme,
+            &tr(if self.tint_groups {
+                "settings.tint_on"
+            } else {
+                "settings.tint_off"
+            }),
+        );
+        if tint_groups.was_clicked() {
+            self.tint_groups = !self.tint_groups;
+        }
+        let ui_theme = button::Button::new(
+            cx,
+            theme,
+            &tr(match self.ui_theme {
+                UiTheme::Dark => "settings.theme_dark",
+                UiTheme::Light => "settings.theme_light",
+            }),
+        );
+        if ui_theme.was_clicked() {
+            self.ui_theme = match self.ui_theme {
+                UiTheme::Dark => UiTheme::Light,
+                UiTheme::Light => UiTheme::Dark,
+            };
+        }
+        let cursor = button::Button::new(
+            cx,
+            theme,
+            &tr(match self.cursor {
+                CursorSkin::Hand => "settings.cursor_hand",
+                CursorSkin::Glove => "settings.cursor_glove",
+                CursorSkin::Harvester => "settings.cursor_harvester",
+            }),
+        );
+        if cursor.was_clicked() {
+            self.cursor = match self.cursor {
+                CursorSkin::Hand => CursorSkin::Glove,
+                CursorSkin::Glove => CursorSkin::Harvester,
+                CursorSkin::Harvester => CursorSkin::Hand,
+            };
+        }
+        let languages = translations::languages();
+        let language_name = languages
+            .iter()
+            .find(|(code, _)| *code == self.language)
+            .map_or(self.language.as_str(), |(_, name)| name.as_str());
+        let language = button::Button::new(
+            cx,
+            theme,
+            &tr_with("settings.language", &[("value", language_name)]),
+        );
+        if language.was_clicked() && !languages.is_empty() {
+            let index = languages
+                .iter()
+                .position(|(code, _)| *code == self.language)
+                .map_or(0, |index| (index + 1) % languages.len());
+            self.language = languages[index].0.clone();
+            translations::set_language(&self.language);
+        }
+        let controls = button::Button::new(
+            cx,
+            theme,
+            &tr(match self.controls.grab {
+                MouseButton::Left => "settings.grab_left",
+                MouseButton::Middle => "settings.grab_middle",
+                MouseButton::Right => "settings.grab_right",
+            }),
+        );
+        if controls.was_clicked() {
+            std::mem::swap(&mut self.controls.grab, &mut self.controls.pan);
+        }
+        (
+            (
+                show_names.center(),
+                show_feed.center(),
+                show_players.center(),
+                self.thumbnail.ui(cx, theme).center(),
+                ui_theme.center(),
+                cursor.center(),
+                language.center(),
+            )
+                .column(),
+            table.center(),
+            tint_groups.center(),
+            controls.center(),
+            self.audio.ui(cx, theme).center(),
+            self.highlight.ui(cx, theme).center(),
+            self.window.ui(geng, cx, theme).center(),
+            self.network.ui(cx).center(),
+        )
+            .column()
+    }
+}
+
+/// Settings screen opened from the menu
+pub struct SettingsScreen {
+    geng: Geng,
+    settings: Settings,
+    transition: Option<geng::Transition>,
+}
+
+impl SettingsScreen {
+    pub fn new(geng: &Geng, config: &ClientConfig) -> Self {
+        Self {
+            geng: geng.clone(),
+            settings: Settings::load(config),
+            transition: None,
+        }
+    }
+}
+
+impl geng::State for SettingsScreen {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(
+            framebuffer,
+            Some(self.settings.ui_theme.palette().background),
+            None,
+            None,
+        );
+    }
+    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
+        use geng::ui::*;
+        let back_button = button::Button::new(cx, self.settings.ui_theme, &tr("common.back"));
+        if back_button.was_clicked() {
+            self.settings.save();
+            self.transition = Some(geng::Transition::Pop);
+        }
+        (
+            self.settings.ui(&self.geng, cx).center(),
+            back_button.center(),
+        )
+            .column()
+            .center()
+            .boxed()
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        if let geng::Event::KeyDown {
+            key: geng::Key::F11,
+        } = event
+        {
+            self.settings.window.toggle_fullscreen(&self.geng);
+        }
+    }
+    fn transition(&mut self) -> Option<geng::Transition> {
+        self.transition.take()
+    }
+}
@@ -6,6 +6,7 @@ pub struct Slider<'a> {
     cx: &'a Controller,
     sense: &'a mut Sense,
     pos: &'a mut Option<AABB<f64>>,
+    text: String,
     value: f64,
     range: RangeInclusive<f64>,
     change: RefCell<&'a mut Option<f64>>,
@@ -19,6 +20,7 @@ impl<'a> Slider<'a> {
             cx,
             sense: cx.get_state(),
             pos: cx.get_state(),
+            text,
             value,
             range,
             change: RefCell::new(cx.get_state()),
@@ -36,7 +38,39 @@ impl<'a> Widget for Slider<'a> {
     }
     fn update(&mut self, delta_time: f64) {}
     fn draw(&mut self, cx: &mut DrawContext) {
-        // cx.geng.draw_2d(cx.framebuffer, &geng::PixelPerfectCamera, &draw_2d::Quad::new())
+        *self.pos = Some(cx.position);
+        let aabb = cx.position.map(|x| x as f32);
+        let color = if self.sense.is_hovered() || self.sense.is_captured() {
+            cx.theme.hover_color
+        } else {
+            cx.theme.usable_color
+        };
+        let knob = aabb.height() / 6.0;
+        let line = AABB::point(aabb.center())
+            .extend_symmetric(vec2(aabb.width() / 2.0 - knob, aabb.height() / 40.0));
+        cx.geng.draw_2d(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::Quad::new(line, color),
+        );
+        let t = ((self.value - *self.range.start()) / (*self.range.end() - *self.range.start()))
+            .clamp(0.0, 1.0) as f32;
+        let knob_pos = vec2(line.x_min + line.width() * t, aabb.center().y);
+        cx.geng.draw_2d(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::Quad::new(AABB::point(knob_pos).extend_uniform(knob), color),
+        );
+        let size = aabb.height() / 3.0;
+        cx.geng.default_font().draw(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &self.text,
+            vec2(aabb.center().x, aabb.y_max - size),
+            geng::TextAlign::CENTER,
+            size,
+            color,
+        );
     }
     fn handle_event(&mut self, event: &geng::Event) {
         let aabb = match *self.pos {
@@ -59,7 +93,7 @@ impl<'a> Widget for Slider<'a> {
 
     fn calc_constraints(&mut self, _children: &ConstraintsContext) -> Constraints {
         Constraints {
-            min_size: vec2(1.0, 1.0) * self.cx.theme().text_size as f64,
+            min_size: vec2(10.0, 1.5) * self.cx.theme().text_size as f64,
             flex: vec2(1.0, 0.0),
         }
     }
@@ -9,6 +9,7 @@ pub struct TextInput<'a> {
     text: &'a mut String,
     t: &'a mut f64,
     max_len: usize,
+    placeholder: String,
     pub capture: &'a mut bool,
 }
 
@@ -22,8 +23,14 @@ impl<'a> TextInput<'a> {
             capture: cx.get_state(),
             text,
             max_len,
+            placeholder: tr("menu.name_placeholder"),
         }
     }
+    /// Text shown while the input is empty
+    pub fn placeholder(mut self, placeholder: String) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
 }
 
 impl<'a> Widget for TextInput<'a> {
@@ -43,7 +50,7 @@ impl<'a> Widget for TextInput<'a> {
             if *self.capture {
                 text = "";
             } else {
-                text = "click to change your name";
+                text = &self.placeholder;
             }
         }
         let _size = partial_min(
@@ -91,3 +98,30 @@ impl<'a> Widget for TextInput<'a> {
         }
     }
 }
+
+/// Applies a key press to the text being typed, keeping at most `max_len` bytes
+pub fn type_key(geng: &Geng, text: &mut String, key: geng::Key, max_len: usize) {
+    match key {
+        geng::Key::Backspace => {
+            text.pop();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        geng::Key::V if geng.window().is_key_pressed(geng::Key::LCtrl) => {
+            match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+                Ok(pasted) => text.push_str(pasted.trim()),
+                Err(e) => warn!("Failed to paste: {}", e),
+            }
+        }
+        _ => {
+            let s = format!("{:?}", key);
+            // Digit keys are named Num0..Num9
+            let s = s.strip_prefix("Num").unwrap_or(&s);
+            if s.len() == 1 {
+                text.push_str(s);
+            }
+        }
+    }
+    while text.len() > max_len {
+        text.pop();
+    }
+}
@@ -0,0 +1,164 @@
+use super::*;
+
+/// Language used for texts missing from the chosen one
+pub const BASE_LANGUAGE: &str = "en";
+
+/// All user facing texts of a single language
+#[derive(Debug, Clone)]
+pub struct Language {
+    pub code: String,
+    /// Name of the language in itself, shown in the picker
+    pub name: String,
+    /// Texts by dotted key, like `menu.play` for `play` in the `[menu]` table
+    texts: HashMap<String, String>,
+}
+
+/// Translations from `assets/translations`, `languages.toml` maps codes to names
+/// and every language has a `<code>.toml` file next to it
+#[derive(Debug, Clone, Default)]
+pub struct Translations {
+    languages: Vec<Language>,
+}
+
+impl Translations {
+    pub fn languages(&self) -> &[Language] {
+        &self.languages
+    }
+    fn get(&self, language: &str, key: &str) -> Option<&str> {
+        let language = self.languages.iter().find(|other| other.code == language)?;
+        language.texts.get(key).map(|text| text.as_str())
+    }
+}
+
+impl geng::LoadAsset for Translations {
+    fn load(geng: &Geng, path: &std::path::Path) -> geng::AssetFuture<Self> {
+        let geng = geng.clone();
+        let path = path.to_owned();
+        Box::pin(async move {
+            let names =
+                <String as geng::LoadAsset>::load(&geng, &path.join("languages.toml")).await?;
+            let names: BTreeMap<String, String> = toml::from_str(&names)?;
+            let mut languages = Vec::new();
+            for (code, name) in names {
+                let file = path.join(format!("{}.toml", code));
+                let texts = <String as geng::LoadAsset>::load(&geng, &file).await?;
+                let mut language = Language {
+                    code,
+                    name,
+                    texts: HashMap::new(),
+                };
+                flatten("", toml::from_str(&texts)?, &mut language.texts);
+                languages.push(language);
+            }
+            Ok(Self { languages })
+        })
+    }
+    const DEFAULT_EXT: Option<&'static str> = None;
+}
+
+fn flatten(prefix: &str, value: toml::Value, texts: &mut HashMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, texts);
+            }
+        }
+        toml::Value::String(text) => {
+            texts.insert(prefix.to_owned(), text);
+        }
+        other => {
+            warn!("Translation {:?} is not a string: {}", prefix, other);
+        }
+    }
+}
+
+struct Current {
+    translations: Translations,
+    language: String,
+}
+
+thread_local! {
+    // Screens are created all over the place, so texts are looked up here
+    // instead of passing the assets to every one of them
+    static CURRENT: RefCell<Current> = RefCell::new(Current {
+        translations: default(),
+        language: BASE_LANGUAGE.to_owned(),
+    });
+}
+
+/// Starts using the loaded translations in the language from the settings
+pub fn init(assets: &Assets, config: &ClientConfig) {
+    set_translations(assets.translations.clone());
+    set_language(&Settings::load(config).language);
+}
+
+/// Replaces the loaded translations, like after reloading the assets
+pub fn set_translations(translations: Translations) {
+    CURRENT.with(|current| current.borrow_mut().translations = translations);
+}
+
+pub fn set_language(language: &str) {
+    CURRENT.with(|current| current.borrow_mut().language = language.to_owned());
+}
+
+/// Codes and names of the loaded languages
+pub fn languages() -> Vec<(String, String)> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .translations
+            .languages()
+            .iter()
+            .map(|language| (language.code.clone(), language.name.clone()))
+            .collect()
+    })
+}
+
+/// Text in the chosen language, falls back to English and then to the key itself
+pub fn tr(key: &str) -> String {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        let translations = &current.translations;
+        translations
+            .get(&current.language, key)
+            .or_else(|| translations.get(BASE_LANGUAGE, key))
+            .unwrap_or(key)
+            .to_owned()
+    })
+}
+
+/// Like [tr] with `{name}` placeholders replaced by the values
+pub fn tr_with(key: &str, args: &[(&str, &str)]) -> String {
+    let mut text = tr(key);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+#[test]
+fn test_fallback() {
+    let mut translations = Translations::default();
+    for (code, texts) in [
+        ("en", "[menu]\nplay = \"Play\"\nback = \"Back\""),
+        ("xx", "[menu]\nplay = \"Go {name}\""),
+    ] {
+        let mut language = Language {
+            code: code.to_owned(),
+            name: code.to_owned(),
+            texts: HashMap::new(),
+        };
+        flatten("", toml::from_str(texts).unwrap(), &mut language.texts);
+        translations.languages.push(language);
+    }
+    set_translations(translations);
+    set_language("xx");
+    assert_eq!(tr_with("menu.play", &[("name", "now")]), "Go now");
+    assert_eq!(tr("menu.back"), "Back");
+    assert_eq!(tr("menu.missing"), "menu.missing");
+}
@@ -0,0 +1,77 @@
+use super::*;
+
+/// Things the tutorial teaches, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TutorialStep {
+    Grab,
+    Snap,
+    Pan,
+    Zoom,
+}
+
+impl TutorialStep {
+    const ALL: [Self; 4] = [Self::Grab, Self::Snap, Self::Pan, Self::Zoom];
+}
+
+/// Guide shown over the board until the player has done every step once or skipped it,
+/// see [Settings::tutorial_done]
+pub struct Tutorial {
+    step: usize,
+}
+
+impl Tutorial {
+    pub fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    pub fn step(&self) -> TutorialStep {
+        TutorialStep::ALL[self.step]
+    }
+
+    /// Moves on if the player did what the current step asks,
+    /// returns whether the tutorial is over
+    pub fn complete(&mut self, step: TutorialStep) -> bool {
+        if step == self.step() {
+            self.step += 1;
+        }
+        self.step == TutorialStep::ALL.len()
+    }
+
+    /// Instructions for the current step
+    pub fn text(&self, controls: &Controls) -> String {
+        let button_name = |button| {
+            tr(match button {
+                MouseButton::Left => "tutorial.left",
+                MouseButton::Middle => "tutorial.middle",
+                MouseButton::Right => "tutorial.right",
+            })
+        };
+        let text = match self.step() {
+            TutorialStep::Grab => {
+                tr_with("tutorial.grab", &[("button", &button_name(controls.grab))])
+            }
+            TutorialStep::Snap => tr("tutorial.snap"),
+            TutorialStep::Pan => tr_with("tutorial.pan", &[("button", &button_name(controls.pan))]),
+            TutorialStep::Zoom => tr("tutorial.zoom"),
+        };
+        tr_with(
+            "tutorial.step",
+            &[
+                ("step", &(self.step + 1).to_string()),
+                ("steps", &TutorialStep::ALL.len().to_string()),
+                ("text", &text),
+            ],
+        )
+    }
+}
+
+#[test]
+fn test_tutorial_steps() {
+    let mut tutorial = Tutorial::new();
+    assert!(!tutorial.complete(TutorialStep::Zoom));
+    assert_eq!(tutorial.step(), TutorialStep::Grab);
+    assert!(!tutorial.complete(TutorialStep::Grab));
+    assert!(!tutorial.complete(TutorialStep::Snap));
+    assert!(!tutorial.complete(TutorialStep::Pan));
+    assert!(tutorial.complete(TutorialStep::Zoom));
+}
@@ -1,5 +1,24 @@
 use super::*;
 
+/// Rotates a vector by given number of quarter turns counterclockwise
+pub fn rotate_quarters(mut v: Vec2<f32>, quarters: u8) -> Vec2<f32> {
+    for _ in 0..quarters % 4 {
+        v = v.rotate_90();
+    }
+    v
+}
+
+/// Formats seconds like `1:02:03` or `2:03`
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
 pub fn triangle_contains(tri: [Vec2<f32>; 3], pos: Vec2<f32>) -> bool {
     let d0 = line_signed_d(pos, tri[0], tri[1]);
     let d1 = line_signed_d(pos, tri[1], tri[2]);
@@ -14,3 +33,230 @@ pub fn triangle_contains(tri: [Vec2<f32>; 3], pos: Vec2<f32>) -> bool {
 pub fn line_signed_d(p0: Vec2<f32>, p1: Vec2<f32>, p2: Vec2<f32>) -> f32 {
     (p0.x - p2.x) * (p1.y - p2.y) - (p1.x - p2.x) * (p0.y - p2.y)
 }
+
+/// Waits for the first message the function picks a reply out of, skipping the ones
+/// a connection can get at any time like latency probes and announcements.
+/// `None` if the connection closes first
+pub async fn wait_for_reply<T>(
+    con: &mut Connection,
+    mut reply: impl FnMut(ServerMessage) -> Option<T>,
+) -> Option<T> {
+    while let Some(message) = con.next().await {
+        if let Some(result) = reply(message) {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// A future that is polled manually from a state's update
+pub struct Task<T> {
+    future: Option<std::pin::Pin<Box<dyn std::future::Future<Output = T>>>>,
+}
+
+impl<T> Task<T> {
+    pub fn new(future: impl std::future::Future<Output = T> + 'static) -> Self {
+        Self {
+            future: Some(Box::pin(future)),
+        }
+    }
+
+    /// Returns the result once the future has completed
+    pub fn poll(&mut self) -> Option<T> {
+        let future = self.future.as_mut()?;
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(result) => {
+                self.future = None;
+                Some(result)
+            }
+            std::task::Poll::Pending => None,
+        }
+    }
+}
+
+/// Downloads larger than this many bytes are refused by [fetch_bytes]
+const MAX_FETCH_SIZE: u64 = 16 * 1024 * 1024;
+
+fn too_large_error() -> String {
+    format!("File is larger than {} MB", MAX_FETCH_SIZE / 1024 / 1024)
+}
+
+/// Downloads contents of the url
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fetch_bytes(url: &str) -> impl std::future::Future<Output = Result<Vec<u8>, String>> {
+    use std::io::Read;
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let url = url.to_owned();
+    std::thread::spawn(move || {
+        let result = ureq::get(&url)
+            .call()
+            .map_err(|e| e.to_string())
+            .and_then(|response| {
+                let mut data = Vec::new();
+                response
+                    .into_reader()
+                    // One byte more than allowed tells a file of exactly the limit from a larger one
+                    .take(MAX_FETCH_SIZE + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|e| e.to_string())?;
+                if data.len() as u64 > MAX_FETCH_SIZE {
+                    return Err(too_large_error());
+                }
+                Ok(data)
+            });
+        let _ = sender.send(result);
+    });
+    async move {
+        receiver
+            .await
+            .unwrap_or_else(|_| Err("Download was interrupted".to_owned()))
+    }
+}
+
+/// Downloads contents of the url
+#[cfg(target_arch = "wasm32")]
+pub fn fetch_bytes(url: &str) -> impl std::future::Future<Output = Result<Vec<u8>, String>> {
+    let url = url.to_owned();
+    async move {
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+        let window = web_sys::window().unwrap();
+        let response = JsFuture::from(window.fetch_with_str(&url))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let response: web_sys::Response = response.dyn_into().unwrap();
+        if !response.ok() {
+            return Err(format!("Server responded with {}", response.status()));
+        }
+        let buffer = JsFuture::from(response.array_buffer().map_err(|e| format!("{:?}", e))?)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let data = js_sys::Uint8Array::new(&buffer);
+        if data.length() as u64 > MAX_FETCH_SIZE {
+            return Err(too_large_error());
+        }
+        Ok(data.to_vec())
+    }
+}
+
+/// Room given in the page url as `?room=CODE` or `#CODE`
+#[cfg(target_arch = "wasm32")]
+pub fn room_from_url() -> Option<String> {
+    let location = web_sys::window().unwrap().location();
+    let search = location.search().unwrap_or_default();
+    let hash = location.hash().unwrap_or_default();
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|param| param.strip_prefix("room="))
+        .or_else(|| {
+            hash.strip_prefix('#')
+                .map(|hash| hash.strip_prefix("room=").unwrap_or(hash))
+        })
+        .filter(|room| !room.is_empty())
+        .map(|room| room.to_owned())
+}
+
+/// Shows the room in the page url without reloading, so that the link can be shared
+/// and reloading the page rejoins it, `None` clears it
+#[cfg(target_arch = "wasm32")]
+pub fn set_room_url(room: Option<&str>) {
+    let window = web_sys::window().unwrap();
+    let url = match room {
+        Some(room) => format!("?room={room}"),
+        None => window.location().pathname().unwrap(),
+    };
+    window
+        .history()
+        .unwrap()
+        .replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url))
+        .unwrap();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_room_url(_room: Option<&str>) {}
+
+/// What to share for others to join the room, a link on the web and just the code on native
+pub fn invite_text(room: &str) -> String {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let location = web_sys::window().unwrap().location();
+        format!(
+            "{}{}?room={}",
+            location.origin().unwrap(),
+            location.pathname().unwrap(),
+            room,
+        )
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    room.to_owned()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn copy_to_clipboard(text: &str) {
+    if let Err(e) =
+        arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text.to_owned()))
+    {
+        warn!("Failed to copy: {}", e);
+    }
+}
+
+/// Lets the player pick where to save the file
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_file(name: &str, data: &[u8]) {
+    let path = match rfd::FileDialog::new().set_file_name(name).save_file() {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(e) = std::fs::write(&path, data) {
+        warn!("Failed to save {:?}: {}", path, e);
+    }
+}
+
+/// Downloads the file through a temporary link
+#[cfg(target_arch = "wasm32")]
+pub fn save_file(name: &str, data: &[u8]) {
+    use wasm_bindgen::JsCast;
+    let result = (|| -> Result<(), wasm_bindgen::JsValue> {
+        let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+        let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+        let document = web_sys::window().unwrap().document().unwrap();
+        let link: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+        link.set_href(&url);
+        link.set_download(name);
+        link.click();
+        // The download reads the url after this returns, so give it time before freeing the blob
+        let revoke = wasm_bindgen::closure::Closure::once_into_js(move || {
+            let _ = web_sys::Url::revoke_object_url(&url);
+        });
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                revoke.unchecked_ref(),
+                10_000,
+            )?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        warn!("Failed to save {}: {:?}", name, e);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn copy_to_clipboard(text: &str) {
+    use wasm_bindgen::{JsCast, JsValue};
+    // navigator.clipboard is behind unstable apis in web-sys, so call it dynamically
+    let navigator = web_sys::window().unwrap().navigator();
+    let result =
+        js_sys::Reflect::get(&navigator, &JsValue::from_str("clipboard")).and_then(|clipboard| {
+            let write_text: js_sys::Function =
+                js_sys::Reflect::get(&clipboard, &JsValue::from_str("writeText"))?.dyn_into()?;
+            write_text.call1(&clipboard, &JsValue::from_str(text))
+        });
+    if let Err(e) = result {
+        warn!("Failed to copy: {:?}", e);
+    }
+}
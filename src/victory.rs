@@ -0,0 +1,138 @@
+use super::*;
+
+/// Shown after the puzzle is completed
+pub struct Victory {
+    geng: Geng,
+    addr: String,
+    client_config: ClientConfig,
+    theme: UiTheme,
+    image: ImageTexture,
+    stats: RoomStats,
+    transition: Option<geng::Transition>,
+}
+
+/// Image of the completed puzzle
+pub enum ImageTexture {
+    Builtin(Rc<Assets>, usize),
+    Custom(Rc<ugli::Texture>),
+}
+
+impl ImageTexture {
+    pub fn get(&self) -> &ugli::Texture {
+        match self {
+            Self::Builtin(assets, index) => &assets.images[*index],
+            Self::Custom(texture) => texture,
+        }
+    }
+}
+
+impl Victory {
+    pub fn new(
+        geng: &Geng,
+        addr: &str,
+        client_config: ClientConfig,
+        image: ImageTexture,
+        stats: RoomStats,
+    ) -> Self {
+        Self {
+            geng: geng.clone(),
+            addr: addr.to_owned(),
+            theme: Settings::load(&client_config).ui_theme,
+            client_config,
+            image,
+            stats,
+            transition: None,
+        }
+    }
+}
+
+impl geng::State for Victory {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(
+            framebuffer,
+            Some(self.theme.palette().background),
+            None,
+            None,
+        );
+        let framebuffer_size = framebuffer.size().map(|x| x as f32);
+        let texture = self.image.get();
+        let size = texture.size().map(|x| x as f32);
+        let ratio = (framebuffer_size.y * 0.5 / size.y).min(framebuffer_size.x * 0.8 / size.x);
+        let center = vec2(framebuffer_size.x / 2.0, framebuffer_size.y * 0.6);
+        self.geng.draw_2d(
+            framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw_2d::TexturedQuad::new(
+                AABB::point(center).extend_symmetric(size * ratio * 0.5),
+                texture,
+            ),
+        );
+        let font_size = framebuffer_size.y * 0.05;
+        self.geng.default_font().draw_with_outline(
+            framebuffer,
+            &geng::PixelPerfectCamera,
+            &tr_with(
+                if self.stats.players.len() == 1 {
+                    "victory.completed_one"
+                } else {
+                    "victory.completed"
+                },
+                &[
+                    ("time", &util::format_duration(self.stats.time)),
+                    ("players", &self.stats.players.len().to_string()),
+                ],
+            ),
+            vec2(
+                framebuffer_size.x / 2.0,
+                framebuffer_size.y - font_size * 1.5,
+            ),
+            geng::TextAlign::CENTER,
+            font_size,
+            Rgba::WHITE,
+            font_size * 0.05,
+            Rgba::BLACK,
+        );
+        let font_size = framebuffer_size.y * 0.03;
+        for (i, player) in self.stats.players.iter().take(5).enumerate() {
+            let name = if player.name.is_empty() {
+                tr("game.someone")
+            } else {
+                player.name.clone()
+            };
+            self.geng.default_font().draw_with_outline(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &tr_with(
+                    "victory.player",
+                    &[("name", &name), ("pieces", &player.pieces.to_string())],
+                ),
+                vec2(
+                    framebuffer_size.x / 2.0,
+                    framebuffer_size.y * 0.3 - i as f32 * font_size * 1.3,
+                ),
+                geng::TextAlign::CENTER,
+                font_size,
+                Rgba::WHITE,
+                font_size * 0.05,
+                Rgba::BLACK,
+            );
+        }
+    }
+    fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
+        use geng::ui::*;
+        let menu_button = button::Button::new(cx, self.theme, &tr("common.back_to_menu"));
+        if menu_button.was_clicked() {
+            // Drop the room from the url so that reloading does not rejoin it
+            util::set_room_url(None);
+            self.transition = Some(geng::Transition::Switch(Box::new(main_menu::run(
+                &self.geng,
+                &self.addr,
+                &self.client_config,
+            ))));
+        }
+        (menu_button.align(vec2(0.5, 0.05)),).stack().boxed()
+    }
+    fn transition(&mut self) -> Option<geng::Transition> {
+        self.transition.take()
+    }
+}